@@ -0,0 +1,61 @@
+use crate::diagnostics::{Diagnostic, Span};
+use crate::parser::{Rule, rule_name};
+use std::fmt;
+
+pub type BuildResult<T> = Result<T, Vec<BuildError>>;
+
+#[derive(Debug, Clone)]
+pub enum BuildError {
+    /// The parser accepted a construct the AST builder can't represent yet.
+    UnsupportedConstruct { rule: Rule, span: Span },
+    /// A literal whose text can't be converted to its value.
+    MalformedLiteral {
+        text: String,
+        reason: String,
+        span: Span,
+    },
+    /// A node is missing a child the grammar should always produce.
+    MissingChild {
+        parent: Rule,
+        expected: &'static str,
+        span: Span,
+    },
+}
+
+impl BuildError {
+    pub fn span(&self) -> Span {
+        match self {
+            BuildError::UnsupportedConstruct { span, .. }
+            | BuildError::MalformedLiteral { span, .. }
+            | BuildError::MissingChild { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::UnsupportedConstruct { rule, .. } => {
+                write!(f, "{} is not supported yet", rule_name(*rule))
+            }
+            BuildError::MalformedLiteral { text, reason, .. } => {
+                write!(f, "malformed literal `{}`: {}", text, reason)
+            }
+            BuildError::MissingChild {
+                parent, expected, ..
+            } => write!(f, "{} is missing its {}", rule_name(*parent), expected),
+        }
+    }
+}
+
+impl From<BuildError> for Vec<BuildError> {
+    fn from(error: BuildError) -> Self {
+        vec![error]
+    }
+}
+
+impl From<&BuildError> for Diagnostic {
+    fn from(error: &BuildError) -> Self {
+        Diagnostic::error(error.to_string()).with_span(error.span())
+    }
+}
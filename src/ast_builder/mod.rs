@@ -1,197 +1,918 @@
+mod error;
+
+use std::sync::LazyLock;
 use std::vec;
 
+pub use error::{BuildError, BuildResult};
+use pest::pratt_parser::{Assoc, Op, PrattParser};
+use tracing::trace;
+
 use crate::ast_nodes::{
+    array::{ArrayLitNode, ArrayRepeatNode, IndexNode},
     block::BlockNode,
+    control_flow::{ForExprNode, IfExprNode, WhileExprNode},
+    enum_def::{EnumDefNode, EnumInitNode, EnumVariantField, EnumVariantNode},
     expression::{
-        AddExprNode, AddExprPart, AddOp, CImportNode, ExpressionKind, ExpressionNode, MulExprNode,
-        MulExprPart, MulOp, PrimaryKind, PrimaryNode, ReturnExprNode,
+        BinaryExprNode, BinaryOp, CImportNode, ExpressionKind, ExpressionNode, ReturnExprNode,
+        UnaryExprNode, UnaryOp,
     },
-    func_call::FuncCallNode,
-    func_def::{FuncDefNode, FuncParam, GenericTypingNode},
+    func_call::{FuncCallNode, MethodCallNode},
+    func_def::{FuncDefNode, FuncParam, GenericTypingNode, SelfParam, TypeParamNode},
+    match_expr::{MatchArmNode, MatchExprNode, PatternKind, PatternNode},
+    module::{ModuleNode, UseNode},
     program::ProgramNode,
     struct_def::{StructDefNode, StructFieldAccessNode, StructFieldNode},
-    term::VarDeclNode,
+    term::{AssignmentNode, PlaceSegment, StructFieldInitNode, StructInitNode, VarDeclNode},
+    trait_def::{ImplBlockNode, TraitDefNode, TraitMethodNode},
+    types::Type,
     var_access::VarAccessNode,
 };
+use crate::diagnostics::Span;
 use crate::parser::Rule;
 
 type Pair<'a> = pest::iterators::Pair<'a, Rule>;
-
-pub fn build_ast_from_pairs(pair: Pair) -> ProgramNode {
+type Pairs<'a> = pest::iterators::Pairs<'a, Rule>;
+
+/// Operator precedence, from loosest to tightest binding.
+static PRATT_PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
+    PrattParser::new()
+        .op(Op::infix(Rule::or, Assoc::Left))
+        .op(Op::infix(Rule::and, Assoc::Left))
+        .op(Op::infix(Rule::equal, Assoc::Left) | Op::infix(Rule::unequal, Assoc::Left))
+        .op(Op::infix(Rule::less_than, Assoc::Left)
+            | Op::infix(Rule::greater_than, Assoc::Left)
+            | Op::infix(Rule::less_or_equal, Assoc::Left)
+            | Op::infix(Rule::greater_or_equal, Assoc::Left))
+        .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+        .op(Op::infix(Rule::multiply, Assoc::Left)
+            | Op::infix(Rule::divide, Assoc::Left)
+            | Op::infix(Rule::modulo, Assoc::Left))
+        .op(Op::prefix(Rule::negate) | Op::prefix(Rule::not))
+        // `-x ** 2` is `-(x ** 2)`, like in maths.
+        .op(Op::infix(Rule::power, Assoc::Right))
+        .op(Op::postfix(Rule::field_access)
+            | Op::postfix(Rule::method_call)
+            | Op::postfix(Rule::index))
+});
+
+/// Builds the AST of a file parsed on its own. Its spans are moved by `base`,
+/// where the file starts in the source map.
+pub fn build_ast_from_pairs(pair: Pair, base: usize) -> BuildResult<ProgramNode> {
     match pair.as_rule() {
-        Rule::program => build_program(pair),
-        rule => panic!("Unsupported root rule: {:?}", rule),
+        Rule::program => build_program(pair, base),
+        rule => Err(BuildError::UnsupportedConstruct {
+            rule,
+            span: span_of(&pair, base),
+        }
+        .into()),
     }
 }
 
-fn build_program(pair: Pair) -> ProgramNode {
-    let expressions = pair
-        .into_inner()
-        .filter_map(|p| match p.as_rule() {
-            Rule::expression => Some(build_expression(p)),
-            Rule::EOI => None,
-            _ => panic!("Invalid node in program: {:?}", p.as_rule()),
+/// Builds every item and keeps going after failures, so all errors of a
+/// program are reported in one run instead of only the first one.
+fn collect_all<T>(results: impl Iterator<Item = BuildResult<T>>) -> BuildResult<Vec<T>> {
+    let mut nodes = vec![];
+    let mut errors = vec![];
+
+    for result in results {
+        match result {
+            Ok(node) => nodes.push(node),
+            Err(mut e) => errors.append(&mut e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Span of a pair in the source map, for a file starting at `base`.
+fn span_of(pair: &Pair, base: usize) -> Span {
+    let span = Span::from(pair.as_span());
+    Span {
+        start: base + span.start,
+        end: base + span.end,
+    }
+}
+
+fn next_child<'a>(
+    inner: &mut Pairs<'a>,
+    parent: Rule,
+    span: Span,
+    expected: &'static str,
+) -> Result<Pair<'a>, BuildError> {
+    inner.next().ok_or(BuildError::MissingChild {
+        parent,
+        expected,
+        span,
+    })
+}
+
+/// Takes the next child if it is a `rule`, for the optional parts of a rule.
+fn optional_child<'a>(inner: &mut Pairs<'a>, rule: Rule) -> Option<Pair<'a>> {
+    inner.peek().filter(|p| p.as_rule() == rule)?;
+    inner.next()
+}
+
+fn unsupported(pair: &Pair, base: usize) -> Vec<BuildError> {
+    BuildError::UnsupportedConstruct {
+        rule: pair.as_rule(),
+        span: span_of(pair, base),
+    }
+    .into()
+}
+
+fn parse_literal<T: std::str::FromStr>(pair: &Pair, base: usize) -> Result<T, BuildError>
+where
+    T::Err: std::fmt::Display,
+{
+    pair.as_str()
+        .parse()
+        .map_err(|e: T::Err| BuildError::MalformedLiteral {
+            text: pair.as_str().to_string(),
+            reason: e.to_string(),
+            span: span_of(pair, base),
         })
-        .collect::<Vec<ExpressionNode>>();
+}
 
-    ProgramNode { expressions }
+fn build_program(pair: Pair, base: usize) -> BuildResult<ProgramNode> {
+    let expressions = collect_all(pair.into_inner().filter_map(|p| match p.as_rule() {
+        Rule::expression => Some(build_expression(p, base)),
+        Rule::EOI => None,
+        _ => Some(Err(unsupported(&p, base))),
+    }))?;
+
+    Ok(ProgramNode { expressions })
 }
 
-fn build_expression(pair: Pair) -> ExpressionNode {
-    println!("EXPR: {}", pair);
+fn build_expression(pair: Pair, base: usize) -> BuildResult<ExpressionNode> {
+    trace!(pair = %pair, "building expression");
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let expr = inner.next().expect("Expression has to have a child node");
+    let mut expr = next_child(&mut inner, Rule::expression, span, "value")?;
+    let public = expr.as_rule() == Rule::pub_kw;
+    if public {
+        expr = next_child(&mut inner, Rule::expression, span, "item")?;
+    }
 
     let expression_kind = match expr.as_rule() {
-        Rule::var_decl => ExpressionKind::VarDecl(build_var_decl(expr)),
-        Rule::add_expr => ExpressionKind::AddExpr(build_add_expr(expr)),
-        Rule::func_def => ExpressionKind::FuncDef(build_func_def(expr)),
-        Rule::return_expr => ExpressionKind::ReturnExpr(build_return_expr(expr)),
-        Rule::c_import => ExpressionKind::CImport(build_c_import(expr)),
-        Rule::func_call => ExpressionKind::FuncCall(build_func_call(expr)),
-        Rule::int_lit => ExpressionKind::IntLit(expr.as_str().parse().unwrap()),
-        Rule::str_lit => ExpressionKind::StrLit(expr.as_str().replace("\\", "\\\\").to_string()),
-        Rule::struct_def => ExpressionKind::StructDef(build_struct_def(expr)),
-        Rule::struct_field_access => {
-            ExpressionKind::StructFieldAccess(build_struct_field_access(expr))
-        }
-        _ => panic!("Invalid node in expression: {:?}", expr.as_rule()),
+        Rule::var_decl => ExpressionKind::VarDecl(build_var_decl(expr, base)?),
+        Rule::assignment => ExpressionKind::Assignment(build_assignment(expr, base)?),
+        Rule::operation => return build_operation(expr, base),
+        Rule::func_def => ExpressionKind::FuncDef(FuncDefNode {
+            public,
+            ..build_func_def(expr, base)?
+        }),
+        Rule::return_expr => ExpressionKind::ReturnExpr(build_return_expr(expr, base)?),
+        Rule::c_import => ExpressionKind::CImport(build_c_import(expr, base)?),
+        Rule::struct_def => ExpressionKind::StructDef(StructDefNode {
+            public,
+            ..build_struct_def(expr, base)?
+        }),
+        Rule::enum_def => ExpressionKind::EnumDef(EnumDefNode {
+            public,
+            ..build_enum_def(expr, base)?
+        }),
+        Rule::trait_def => ExpressionKind::TraitDef(TraitDefNode {
+            public,
+            ..build_trait_def(expr, base)?
+        }),
+        Rule::mod_decl => ExpressionKind::Module(ModuleNode {
+            public,
+            ..build_module(expr, base)?
+        }),
+        Rule::use_decl => ExpressionKind::Use(UseNode {
+            public,
+            ..build_use(expr, base)?
+        }),
+        Rule::impl_block => ExpressionKind::ImplBlock(build_impl_block(expr, base)?),
+        Rule::while_expr => ExpressionKind::While(build_while_expr(expr, base)?),
+        Rule::for_expr => ExpressionKind::For(build_for_expr(expr, base)?),
+        Rule::break_expr => ExpressionKind::Break,
+        Rule::continue_expr => ExpressionKind::Continue,
+        _ => return Err(unsupported(&expr, base)),
     };
 
-    ExpressionNode {
+    Ok(ExpressionNode {
         kind: expression_kind,
-    }
+        span,
+    })
 }
 
-fn build_struct_field_access(pair: Pair) -> StructFieldAccessNode {
+fn build_struct_init(pair: Pair, base: usize) -> BuildResult<StructInitNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
-    StructFieldAccessNode {
-        struct_name: inner.next().unwrap().as_str().to_string(),
-        field_name: inner.next().unwrap().as_str().to_string(),
+
+    let struct_name = next_child(&mut inner, Rule::struct_init, span, "struct name")?
+        .as_str()
+        .to_string();
+    let generic_params = optional_child(&mut inner, Rule::generic_params)
+        .map(build_generic_params)
+        .unwrap_or_default();
+    let fields = collect_all(inner.map(|field| {
+        let span = span_of(&field, base);
+        let mut inner = field.into_inner();
+        let rule = Rule::struct_field_init;
+
+        Ok(StructFieldInitNode {
+            name: next_child(&mut inner, rule, span, "field name")?
+                .as_str()
+                .to_string(),
+            value: build_expression(next_child(&mut inner, rule, span, "value")?, base)?,
+            span,
+        })
+    }))?;
+
+    Ok(StructInitNode {
+        struct_name,
+        generic_params,
+        fields,
+        span,
+    })
+}
+
+/// Applies `.field`, `.method(args)` or `[index]` to the value before it.
+fn build_postfix(object: ExpressionNode, op: Pair, base: usize) -> BuildResult<ExpressionNode> {
+    let span = Span {
+        start: object.span.start,
+        end: base + op.as_span().end(),
+    };
+    let rule = op.as_rule();
+    let op_span = span_of(&op, base);
+    let mut inner = op.into_inner();
+
+    if rule == Rule::index {
+        let index = build_expression(next_child(&mut inner, rule, op_span, "index")?, base)?;
+        let kind = ExpressionKind::Index(IndexNode {
+            object: Box::new(object),
+            index: Box::new(index),
+        });
+        return Ok(ExpressionNode { kind, span });
     }
+
+    let name = next_child(&mut inner, rule, op_span, "name")?
+        .as_str()
+        .to_string();
+
+    let kind = match rule {
+        Rule::field_access => ExpressionKind::StructFieldAccess(StructFieldAccessNode {
+            object: Box::new(object),
+            field_name: name,
+            span,
+        }),
+        Rule::method_call => ExpressionKind::MethodCall(MethodCallNode {
+            object: Box::new(object),
+            method: name,
+            args: match inner.next() {
+                Some(param_list) => {
+                    collect_all(param_list.into_inner().map(|p| build_expression(p, base)))?
+                }
+                None => vec![],
+            },
+            receiver_type: None,
+            static_call: false,
+            trait_name: None,
+            span,
+        }),
+        _ => return Err(BuildError::UnsupportedConstruct { rule, span }.into()),
+    };
+
+    Ok(ExpressionNode { kind, span })
 }
 
-fn build_struct_def(pair: Pair) -> StructDefNode {
+fn build_struct_def(pair: Pair, base: usize) -> BuildResult<StructDefNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let name = inner.next().unwrap().as_str().to_string();
+    let name = next_child(&mut inner, Rule::struct_def, span, "name")?
+        .as_str()
+        .to_string();
+    let generic_typing =
+        optional_child(&mut inner, Rule::generic_typing).map(|p| build_generic_typing(p, base));
 
     let mut fields = vec![];
+    let mut methods = vec![];
+    if let Some(struct_def_content) = inner.next() {
+        for member in struct_def_content.into_inner() {
+            match member.as_rule() {
+                Rule::func_def => methods.push(build_func_def(member, base)),
+                _ => fields.push(build_struct_field_def(member, base)),
+            }
+        }
+    }
 
-    let struct_def_content = inner.next().unwrap();
+    let (fields, methods) = match (
+        collect_all(fields.into_iter()),
+        collect_all(methods.into_iter()),
+    ) {
+        (Ok(fields), Ok(methods)) => (fields, methods),
+        (fields, methods) => {
+            let errors = [fields.err(), methods.err()];
+            return Err(errors.into_iter().flatten().flatten().collect());
+        }
+    };
 
-    for field_def in struct_def_content.into_inner() {
-        println!("field_def {}", field_def);
-        fields.push(build_struct_field_def(field_def));
-    }
+    Ok(StructDefNode {
+        name,
+        public: false,
+        generic_typing,
+        fields,
+        methods,
+        span,
+    })
+}
+
+fn build_trait_def(pair: Pair, base: usize) -> BuildResult<TraitDefNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    // Skip the `trait` keyword.
+    next_child(&mut inner, Rule::trait_def, span, "`trait`")?;
+    let name = next_child(&mut inner, Rule::trait_def, span, "name")?
+        .as_str()
+        .to_string();
+    let methods = collect_all(inner.map(|p| build_trait_method(p, base)))?;
 
-    StructDefNode { name, fields }
+    Ok(TraitDefNode {
+        name,
+        public: false,
+        methods,
+        span,
+    })
 }
 
-fn build_struct_field_def(pair: Pair) -> StructFieldNode {
-    println!("SFD {}", pair);
+fn build_trait_method(pair: Pair, base: usize) -> BuildResult<TraitMethodNode> {
+    let required = !pair
+        .clone()
+        .into_inner()
+        .any(|p| p.as_rule() == Rule::block);
+    let mut func = build_func_def(pair, base)?;
+    // Impls are checked against the declared signature, so it can't be inferred.
+    func.return_type.get_or_insert(Type::Void);
 
+    Ok(TraitMethodNode { func, required })
+}
+
+fn build_impl_block(pair: Pair, base: usize) -> BuildResult<ImplBlockNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
-    let name = inner.next().unwrap().as_str().to_string();
-    let type_name = inner.next().unwrap().as_str().to_string();
 
-    StructFieldNode { name, type_name }
+    next_child(&mut inner, Rule::impl_block, span, "`impl`")?;
+    let generic_typing =
+        optional_child(&mut inner, Rule::generic_typing).map(|p| build_generic_typing(p, base));
+    let trait_name = next_child(&mut inner, Rule::impl_block, span, "trait")?
+        .as_str()
+        .to_string();
+    next_child(&mut inner, Rule::impl_block, span, "`for`")?;
+    let for_type = build_type(next_child(&mut inner, Rule::impl_block, span, "type")?);
+    let methods = collect_all(inner.map(|p| build_func_def(p, base)))?;
+
+    Ok(ImplBlockNode {
+        trait_name,
+        generic_typing,
+        for_type,
+        methods,
+        span,
+    })
 }
 
-fn build_c_import(pair: Pair) -> CImportNode {
-    let string = pair.into_inner().next().unwrap().as_str().to_string();
+fn build_struct_field_def(pair: Pair, base: usize) -> BuildResult<StructFieldNode> {
+    trace!(pair = %pair, "building struct field");
 
-    CImportNode {
-        module: string[1..(string.len() - 1)].to_string(),
-    }
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+    let name = next_child(&mut inner, Rule::struct_field_def, span, "name")?
+        .as_str()
+        .to_string();
+    let field_type = build_type(next_child(
+        &mut inner,
+        Rule::struct_field_def,
+        span,
+        "type",
+    )?);
+    let default = inner.next().map(|p| build_operation(p, base)).transpose()?;
+
+    Ok(StructFieldNode {
+        name,
+        field_type,
+        default,
+        span,
+    })
 }
-fn build_return_expr(pair: Pair) -> ReturnExprNode {
+
+fn build_enum_def(pair: Pair, base: usize) -> BuildResult<EnumDefNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let expr = build_expression(inner.next().unwrap());
+    let name = next_child(&mut inner, Rule::enum_def, span, "name")?
+        .as_str()
+        .to_string();
+    let generic_typing =
+        optional_child(&mut inner, Rule::generic_typing).map(|p| build_generic_typing(p, base));
+    let content = next_child(&mut inner, Rule::enum_def, span, "variants")?;
+    let variants = collect_all(content.into_inner().map(|p| build_enum_variant(p, base)))?;
 
-    ReturnExprNode {
-        expression: Box::new(expr),
-    }
+    Ok(EnumDefNode {
+        name,
+        public: false,
+        generic_typing,
+        variants,
+        span,
+    })
 }
 
-fn build_add_expr(pair: Pair) -> AddExprNode {
+fn build_enum_variant(pair: Pair, base: usize) -> BuildResult<EnumVariantNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let left_pair = inner.next().unwrap();
-    let left = build_mul_expr(left_pair);
+    let name = next_child(&mut inner, Rule::enum_variant, span, "name")?
+        .as_str()
+        .to_string();
 
-    let mut addent = vec![];
-    while inner.len() > 0 {
-        let op_pair = inner.next().unwrap();
+    let fields = match inner.next() {
+        Some(fields) if fields.as_rule() == Rule::variant_fields => {
+            collect_all(fields.into_inner().map(|field_def| {
+                let field = build_field_def(field_def, base)?;
+                Ok(EnumVariantField {
+                    name: Some(field.name),
+                    field_type: field.param_type,
+                    span: field.span,
+                })
+            }))?
+        }
+        Some(types) => types
+            .into_inner()
+            .map(|type_name| EnumVariantField {
+                name: None,
+                span: span_of(&type_name, base),
+                field_type: build_type(type_name),
+            })
+            .collect(),
+        None => vec![],
+    };
 
-        let op = match op_pair.as_rule() {
-            Rule::add => AddOp::Add,
-            Rule::subtract => AddOp::Subtract,
-            rule => panic!("{:?}", rule),
-        };
+    Ok(EnumVariantNode { name, fields, span })
+}
 
-        let value = build_mul_expr(inner.next().unwrap());
+fn build_enum_init(pair: Pair, base: usize) -> BuildResult<EnumInitNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
 
-        addent.push(AddExprPart { op, value });
-    }
+    let enum_name = next_child(&mut inner, Rule::enum_init, span, "enum name")?
+        .as_str()
+        .to_string();
+    let generic_params = optional_child(&mut inner, Rule::generic_params)
+        .map(build_generic_params)
+        .unwrap_or_default();
+    let variant = next_child(&mut inner, Rule::enum_init, span, "variant")?
+        .as_str()
+        .to_string();
+    let args = match inner.next() {
+        Some(param_list) => {
+            collect_all(param_list.into_inner().map(|p| build_expression(p, base)))?
+        }
+        None => vec![],
+    };
 
-    AddExprNode { left, addent }
+    Ok(EnumInitNode {
+        enum_name,
+        generic_params,
+        variant,
+        args,
+        span,
+    })
 }
 
-fn build_mul_expr(pair: Pair) -> MulExprNode {
+fn build_module(pair: Pair, base: usize) -> BuildResult<ModuleNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let primary_pair = inner.next().unwrap();
-    let left = build_primary(primary_pair);
+    next_child(&mut inner, Rule::mod_decl, span, "`mod`")?;
+    let name = next_child(&mut inner, Rule::mod_decl, span, "name")?
+        .as_str()
+        .to_string();
+    let items = inner
+        .next()
+        .map(|block| build_block(block, base).map(|block| block.expressions))
+        .transpose()?;
 
-    let mut factor = vec![];
-    while inner.len() > 0 {
-        let op_pair = inner.next().unwrap();
+    Ok(ModuleNode {
+        name,
+        public: false,
+        items,
+        span,
+    })
+}
 
-        let op = match op_pair.as_rule() {
-            Rule::multiply => MulOp::Multiply,
-            Rule::divide => MulOp::Divide,
-            rule => panic!("{:?}", rule),
-        };
+fn build_use(pair: Pair, base: usize) -> BuildResult<UseNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    next_child(&mut inner, Rule::use_decl, span, "`use`")?;
+    let path = next_child(&mut inner, Rule::use_decl, span, "path")?
+        .into_inner()
+        .map(|segment| segment.as_str().to_string())
+        .collect();
+    let alias = inner
+        .find(|p| p.as_rule() == Rule::ident)
+        .map(|p| p.as_str().to_string());
+
+    Ok(UseNode {
+        path,
+        alias,
+        public: false,
+        span,
+    })
+}
+
+fn build_c_import(pair: Pair, base: usize) -> BuildResult<CImportNode> {
+    let span = span_of(&pair, base);
+    let string = next_child(&mut pair.into_inner(), Rule::c_import, span, "header")?
+        .as_str()
+        .to_string();
+
+    Ok(CImportNode {
+        module: string[1..(string.len() - 1)].to_string(),
+        span,
+    })
+}
+fn build_return_expr(pair: Pair, base: usize) -> BuildResult<ReturnExprNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    next_child(&mut inner, Rule::return_expr, span, "`return`")?;
+    let expr = inner
+        .next()
+        .map(|p| build_expression(p, base))
+        .transpose()?;
 
-        let value = build_primary(inner.next().unwrap());
+    Ok(ReturnExprNode {
+        expression: expr.map(Box::new),
+        span,
+    })
+}
+
+fn build_operation(pair: Pair, base: usize) -> BuildResult<ExpressionNode> {
+    PRATT_PARSER
+        .map_primary(|p| build_primary(p, base))
+        .map_prefix(|op, operand| {
+            let operand = operand?;
+            let span = Span {
+                start: base + op.as_span().start(),
+                end: operand.span.end,
+            };
+            let op = match op.as_rule() {
+                Rule::negate => UnaryOp::Negate,
+                Rule::not => UnaryOp::Not,
+                _ => return Err(unsupported(&op, base)),
+            };
+
+            Ok(ExpressionNode {
+                kind: unary_expr(op, operand),
+                span,
+            })
+        })
+        .map_postfix(|object, op| build_postfix(object?, op, base))
+        .map_infix(|left, op, right| {
+            let (left, right) = match (left, right) {
+                (Ok(left), Ok(right)) => (left, right),
+                (left, right) => {
+                    let errors = [left.err(), right.err()];
+                    return Err(errors.into_iter().flatten().flatten().collect());
+                }
+            };
+            let op = binary_op(&op, base)?;
+            let span = Span {
+                start: left.span.start,
+                end: right.span.end,
+            };
+
+            Ok(ExpressionNode {
+                kind: ExpressionKind::BinaryExpr(BinaryExprNode {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                    result_type: None,
+                    span,
+                }),
+                span,
+            })
+        })
+        .parse(pair.into_inner())
+}
 
-        factor.push(MulExprPart { op, value });
+/// `-` applied to an integer literal is folded into it, so the range of the
+/// literal is checked with its sign, like `-2147483648` as an `i32`.
+fn unary_expr(op: UnaryOp, operand: ExpressionNode) -> ExpressionKind {
+    match (op, operand.kind) {
+        (UnaryOp::Negate, ExpressionKind::IntLit(value)) => ExpressionKind::IntLit(-value),
+        (op, kind) => ExpressionKind::UnaryExpr(UnaryExprNode {
+            op,
+            operand: Box::new(ExpressionNode { kind, ..operand }),
+        }),
     }
+}
+
+fn binary_op(pair: &Pair, base: usize) -> BuildResult<BinaryOp> {
+    Ok(match pair.as_rule() {
+        Rule::add => BinaryOp::Add,
+        Rule::subtract => BinaryOp::Subtract,
+        Rule::multiply => BinaryOp::Multiply,
+        Rule::divide => BinaryOp::Divide,
+        Rule::modulo => BinaryOp::Modulo,
+        Rule::power => BinaryOp::Power,
+        Rule::equal => BinaryOp::Equal,
+        Rule::unequal => BinaryOp::NotEqual,
+        Rule::less_than => BinaryOp::Less,
+        Rule::greater_than => BinaryOp::Greater,
+        Rule::less_or_equal => BinaryOp::LessOrEqual,
+        Rule::greater_or_equal => BinaryOp::GreaterOrEqual,
+        Rule::and => BinaryOp::And,
+        Rule::or => BinaryOp::Or,
+        _ => return Err(unsupported(pair, base)),
+    })
+}
+
+fn build_primary(pair: Pair, base: usize) -> BuildResult<ExpressionNode> {
+    let span = span_of(&pair, base);
+
+    let kind = match pair.as_rule() {
+        Rule::operation => return build_operation(pair, base),
+        Rule::if_expr => ExpressionKind::If(build_if_expr(pair, base)?),
+        Rule::match_expr => ExpressionKind::Match(build_match_expr(pair, base)?),
+        Rule::var_access => ExpressionKind::VarAccess(build_var_access(pair, base)?),
+        Rule::int_lit => ExpressionKind::IntLit(parse_literal::<u64>(&pair, base)?.into()),
+        Rule::float_lit => ExpressionKind::FloatLit(parse_literal(&pair, base)?),
+        Rule::str_lit => ExpressionKind::StrLit(pair.as_str().to_string()),
+        Rule::func_call => ExpressionKind::FuncCall(build_func_call(pair, base)?),
+        Rule::enum_init => ExpressionKind::EnumInit(build_enum_init(pair, base)?),
+        Rule::struct_init => ExpressionKind::StructInit(build_struct_init(pair, base)?),
+        Rule::array_lit => ExpressionKind::ArrayLit(ArrayLitNode {
+            elements: collect_all(pair.into_inner().map(|p| build_expression(p, base)))?,
+            array_type: None,
+            span,
+        }),
+        Rule::array_repeat => ExpressionKind::ArrayRepeat(build_array_repeat(pair, base)?),
+        _ => return Err(unsupported(&pair, base)),
+    };
 
-    MulExprNode { left, factor }
+    Ok(ExpressionNode { kind, span })
 }
 
-fn build_primary(pair: Pair) -> PrimaryNode {
+fn build_array_repeat(pair: Pair, base: usize) -> BuildResult<ArrayRepeatNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let primary = inner.next().unwrap();
+    let value = build_expression(
+        next_child(&mut inner, Rule::array_repeat, span, "value")?,
+        base,
+    )?;
+    let length = build_type(next_child(&mut inner, Rule::array_repeat, span, "length")?);
+
+    Ok(ArrayRepeatNode {
+        value: Box::new(value),
+        length,
+        array_type: None,
+        span,
+    })
+}
+
+fn build_if_expr(pair: Pair, base: usize) -> BuildResult<IfExprNode> {
+    let span = span_of(&pair, base);
+
+    let mut condition = None;
+    let mut then_block = None;
+    let mut else_block = None;
+
+    for node in pair.into_inner() {
+        match node.as_rule() {
+            Rule::if_kw | Rule::else_kw => {}
+            Rule::condition => condition = Some(build_operation(node, base)?),
+            Rule::block if then_block.is_none() => then_block = Some(build_block(node, base)?),
+            Rule::block => else_block = Some(build_block(node, base)?),
+            Rule::if_expr => {
+                let span = span_of(&node, base);
+                let nested = ExpressionNode {
+                    kind: ExpressionKind::If(build_if_expr(node, base)?),
+                    span,
+                };
+                else_block = Some(BlockNode {
+                    expressions: vec![nested],
+                    span,
+                });
+            }
+            _ => return Err(unsupported(&node, base)),
+        }
+    }
 
-    let kind = match primary.as_rule() {
-        Rule::var_access => PrimaryKind::VarAccess(build_var_access(primary)),
-        Rule::int_lit => PrimaryKind::IntLit(primary.as_str().parse().unwrap()),
-        Rule::str_lit => PrimaryKind::StrLit(primary.as_str().to_string()),
-        Rule::float_lit => PrimaryKind::FloatLit(primary.as_str().parse().unwrap()),
-        _ => todo!("{:?}", primary),
+    let missing = |expected| BuildError::MissingChild {
+        parent: Rule::if_expr,
+        expected,
+        span,
     };
 
-    PrimaryNode { kind }
+    Ok(IfExprNode {
+        condition: Box::new(condition.ok_or_else(|| missing("condition"))?),
+        then_block: then_block.ok_or_else(|| missing("block"))?,
+        else_block,
+        value_type: None,
+    })
 }
 
-fn build_var_access(pair: Pair) -> VarAccessNode {
+fn build_match_expr(pair: Pair, base: usize) -> BuildResult<MatchExprNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
 
-    let name = inner.next().unwrap().as_str().to_string();
+    next_child(&mut inner, Rule::match_expr, span, "`match`")?;
+    let subject = build_operation(
+        next_child(&mut inner, Rule::match_expr, span, "value")?,
+        base,
+    )?;
+    let arms = collect_all(inner.map(|p| build_match_arm(p, base)))?;
+
+    Ok(MatchExprNode {
+        subject: Box::new(subject),
+        arms,
+        subject_type: None,
+        value_type: None,
+        span,
+    })
+}
+
+fn build_match_arm(pair: Pair, base: usize) -> BuildResult<MatchArmNode> {
+    let span = span_of(&pair, base);
+
+    let mut pattern = None;
+    let mut guard = None;
+    let mut body = None;
 
-    VarAccessNode { name }
+    for node in pair.into_inner() {
+        match node.as_rule() {
+            Rule::pattern => pattern = Some(build_pattern(node, base)?),
+            Rule::match_guard => {
+                let guard_span = span_of(&node, base);
+                let condition = node
+                    .into_inner()
+                    .find(|p| p.as_rule() == Rule::operation)
+                    .ok_or(BuildError::MissingChild {
+                        parent: Rule::match_guard,
+                        expected: "condition",
+                        span: guard_span,
+                    })?;
+                guard = Some(build_operation(condition, base)?);
+            }
+            Rule::block => body = Some(build_block(node, base)?),
+            Rule::expression => {
+                let expression = build_expression(node, base)?;
+                body = Some(BlockNode {
+                    span: expression.span,
+                    expressions: vec![expression],
+                });
+            }
+            _ => return Err(unsupported(&node, base)),
+        }
+    }
+
+    let missing = |expected| BuildError::MissingChild {
+        parent: Rule::match_arm,
+        expected,
+        span,
+    };
+
+    Ok(MatchArmNode {
+        pattern: pattern.ok_or_else(|| missing("pattern"))?,
+        guard,
+        body: body.ok_or_else(|| missing("value"))?,
+    })
+}
+
+fn build_pattern(pair: Pair, base: usize) -> BuildResult<PatternNode> {
+    let span = span_of(&pair, base);
+    let pattern = next_child(&mut pair.into_inner(), Rule::pattern, span, "pattern")?;
+    let rule = pattern.as_rule();
+    let mut inner = pattern.clone().into_inner();
+
+    let kind = match rule {
+        Rule::wildcard_pattern => PatternKind::Wildcard,
+        Rule::binding_pattern => PatternKind::Binding {
+            name: pattern.as_str().trim().to_string(),
+            value_type: None,
+        },
+        Rule::literal_pattern => {
+            let mut literal = next_child(&mut inner, rule, span, "literal")?;
+            let negated = literal.as_rule() == Rule::negate;
+            if negated {
+                literal = next_child(&mut inner, rule, span, "literal")?;
+            }
+
+            let mut value = build_primary(literal, base)?;
+            if negated {
+                value = ExpressionNode {
+                    kind: unary_expr(UnaryOp::Negate, value),
+                    span,
+                };
+            }
+            PatternKind::Literal(Box::new(value))
+        }
+        Rule::comparison_pattern => {
+            let op = binary_op(&next_child(&mut inner, rule, span, "operator")?, base)?;
+            let value = build_operation(next_child(&mut inner, rule, span, "value")?, base)?;
+            PatternKind::Comparison {
+                op,
+                value: Box::new(value),
+            }
+        }
+        Rule::variant_pattern => {
+            let mut names = vec![];
+            let mut fields = vec![];
+            for node in inner {
+                match node.as_rule() {
+                    Rule::ident | Rule::enum_path => names.push(node.as_str().to_string()),
+                    Rule::pattern_list => {
+                        fields = collect_all(node.into_inner().map(|p| build_pattern(p, base)))?
+                    }
+                    _ => return Err(unsupported(&node, base)),
+                }
+            }
+
+            let variant = names.pop().ok_or(BuildError::MissingChild {
+                parent: rule,
+                expected: "variant",
+                span,
+            })?;
+            PatternKind::Variant {
+                enum_name: names.pop(),
+                enum_type: None,
+                variant,
+                fields,
+            }
+        }
+        _ => return Err(unsupported(&pattern, base)),
+    };
+
+    Ok(PatternNode { kind, span })
 }
 
-fn build_func_call(pair: Pair) -> FuncCallNode {
+fn build_while_expr(pair: Pair, base: usize) -> BuildResult<WhileExprNode> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
+    let rule = Rule::while_expr;
+
+    next_child(&mut inner, rule, span, "`while`")?;
+    let condition = build_operation(next_child(&mut inner, rule, span, "condition")?, base)?;
+    let body = build_block(next_child(&mut inner, rule, span, "body")?, base)?;
 
-    let name = inner.next().unwrap().as_str().to_string();
+    Ok(WhileExprNode {
+        condition: Box::new(condition),
+        body,
+    })
+}
+
+fn build_for_expr(pair: Pair, base: usize) -> BuildResult<ForExprNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+    let rule = Rule::for_expr;
+
+    next_child(&mut inner, rule, span, "`for`")?;
+    let variable = next_child(&mut inner, rule, span, "loop variable")?;
+    let variable_span = span_of(&variable, base);
+    let variable = variable.as_str().to_string();
+    next_child(&mut inner, rule, span, "`in`")?;
+    let start = build_operation(next_child(&mut inner, rule, span, "range start")?, base)?;
+    let end = build_operation(next_child(&mut inner, rule, span, "range end")?, base)?;
+    let body = build_block(next_child(&mut inner, rule, span, "body")?, base)?;
+
+    Ok(ForExprNode {
+        variable,
+        variable_span,
+        start: Box::new(start),
+        end: Box::new(end),
+        body,
+        var_type: None,
+    })
+}
+
+fn build_var_access(pair: Pair, base: usize) -> BuildResult<VarAccessNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    let name = next_child(&mut inner, Rule::var_access, span, "name")?
+        .as_str()
+        .to_string();
+
+    Ok(VarAccessNode { name, span })
+}
+
+fn build_func_call(pair: Pair, base: usize) -> BuildResult<FuncCallNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    let name = next_child(&mut inner, Rule::func_call, span, "name")?
+        .as_str()
+        .to_string();
 
     let mut param_list = None;
     let mut generic_params = None;
@@ -200,47 +921,127 @@ fn build_func_call(pair: Pair) -> FuncCallNode {
         match rule.as_rule() {
             Rule::param_list => param_list = Some(rule),
             Rule::generic_params => generic_params = Some(rule),
-            _ => panic!("Invalid rule in func_call {}", rule),
+            _ => return Err(unsupported(&rule, base)),
         };
     }
 
-    let params = param_list
-        .unwrap()
-        .into_inner()
-        .map(|e| match e.as_rule() {
-            Rule::expression => build_expression(e),
-            _ => panic!("Invalid Rule {:?} in param_list", e.as_rule()),
-        })
-        .collect();
+    let params = match param_list {
+        Some(param_list) => collect_all(param_list.into_inner().map(|e| match e.as_rule() {
+            Rule::expression => build_expression(e, base),
+            _ => Err(unsupported(&e, base)),
+        }))?,
+        None => vec![],
+    };
 
     let generic_params = match generic_params {
         Some(p) => build_generic_params(p),
         None => vec![],
     };
 
-    FuncCallNode {
+    Ok(FuncCallNode {
         name,
         params,
         generic_params,
-    }
+        span,
+    })
+}
+
+fn build_generic_params(pair: Pair) -> Vec<Type> {
+    pair.into_inner().map(build_type).collect()
 }
 
-fn build_generic_params(pair: Pair) -> Vec<String> {
-    pair.into_inner().map(|p| p.as_str().to_string()).collect()
+fn build_var_decl(pair: Pair, base: usize) -> BuildResult<VarDeclNode> {
+    let span = span_of(&pair, base);
+
+    let mut name = None;
+    let mut value = None;
+    let mut mutable = false;
+    let mut var_type = None;
+
+    for node in pair.into_inner() {
+        match node.as_rule() {
+            Rule::let_kw => {}
+            Rule::mut_kw => mutable = true,
+            Rule::ident => name = Some(node.as_str().to_string()),
+            Rule::expression => value = Some(build_expression(node, base)?),
+            Rule::var_type => {
+                let span = span_of(&node, base);
+                let type_ref = node
+                    .into_inner()
+                    .find(|p| p.as_rule() != Rule::as_kw)
+                    .ok_or(BuildError::MissingChild {
+                        parent: Rule::var_type,
+                        expected: "type",
+                        span,
+                    })?;
+                var_type = Some(build_type(type_ref));
+            }
+            _ => return Err(unsupported(&node, base)),
+        }
+    }
+
+    let missing = |expected| BuildError::MissingChild {
+        parent: Rule::var_decl,
+        expected,
+        span,
+    };
+
+    Ok(VarDeclNode {
+        name: name.ok_or_else(|| missing("name"))?,
+        value: Box::new(value.ok_or_else(|| missing("value"))?),
+        mutable,
+        var_type,
+        span,
+    })
 }
 
-fn build_var_decl(_pair: Pair) -> VarDeclNode {
-    todo!()
+fn build_assignment(pair: Pair, base: usize) -> BuildResult<AssignmentNode> {
+    let span = span_of(&pair, base);
+    let mut inner = pair.into_inner();
+
+    let name = next_child(&mut inner, Rule::assignment, span, "target")?
+        .as_str()
+        .to_string();
+
+    let mut path = vec![];
+    let mut value = None;
+    for node in inner {
+        match node.as_rule() {
+            Rule::field_ident => path.push(PlaceSegment::Field(node.as_str().to_string())),
+            Rule::index => {
+                let span = span_of(&node, base);
+                let index = next_child(&mut node.into_inner(), Rule::index, span, "index")?;
+                path.push(PlaceSegment::Index(Box::new(build_expression(
+                    index, base,
+                )?)));
+            }
+            Rule::expression => value = Some(build_expression(node, base)?),
+            _ => return Err(unsupported(&node, base)),
+        }
+    }
+    let value = value.ok_or(BuildError::MissingChild {
+        parent: Rule::assignment,
+        expected: "value",
+        span,
+    })?;
+
+    Ok(AssignmentNode {
+        name,
+        path,
+        value: Box::new(value),
+        span,
+    })
 }
 
-fn build_func_def(pair: Pair) -> FuncDefNode {
+fn build_func_def(pair: Pair, base: usize) -> BuildResult<FuncDefNode> {
+    let span = span_of(&pair, base);
+    let rule = pair.as_rule();
     let mut inner = pair.into_inner();
 
-    let name = inner
-        .next()
-        .expect("Function requires a name")
+    let name = next_child(&mut inner, rule, span, "name")?
         .as_str()
         .to_string();
+    let mut self_param = None;
     let mut param_def_list = None;
     let mut return_type = None;
     let mut body = None;
@@ -248,62 +1049,141 @@ fn build_func_def(pair: Pair) -> FuncDefNode {
 
     for node in inner {
         match node.as_rule() {
-            Rule::param_def_list => param_def_list = Some(build_param_def_list(node)),
-            Rule::block => body = Some(build_block(node)),
+            Rule::self_param => {
+                self_param = Some(SelfParam {
+                    mutable: node
+                        .clone()
+                        .into_inner()
+                        .any(|p| p.as_rule() == Rule::mut_kw),
+                    span: span_of(&node, base),
+                })
+            }
+            Rule::param_def_list => param_def_list = Some(build_param_def_list(node, base)?),
+            Rule::block => body = Some(build_block(node, base)?),
             Rule::return_type => return_type = Some(build_return_type(node)),
-            Rule::generic_typing => generic_typing = Some(build_generic_typing(node)),
-            _ => panic!("{}", node),
+            Rule::generic_typing => generic_typing = Some(build_generic_typing(node, base)),
+            _ => return Err(unsupported(&node, base)),
         };
     }
 
-    FuncDefNode {
+    let body = match body {
+        Some(body) => body,
+        // Required trait methods have no body.
+        None if rule == Rule::trait_method => BlockNode {
+            expressions: vec![],
+            span,
+        },
+        None => {
+            return Err(vec![BuildError::MissingChild {
+                parent: rule,
+                expected: "body",
+                span,
+            }]);
+        }
+    };
+
+    Ok(FuncDefNode {
         name,
+        public: false,
+        self_param,
         params: param_def_list.unwrap_or(vec![]),
-        body: body.unwrap(),
+        body,
         generic_typing,
         return_type,
-    }
+        span,
+    })
 }
 
-fn build_generic_typing(pair: Pair) -> GenericTypingNode {
-    let inner = pair.into_inner();
+fn build_generic_typing(pair: Pair, base: usize) -> GenericTypingNode {
+    let params = pair
+        .into_inner()
+        .map(|p| build_type_param(p, base))
+        .collect();
 
-    let generic_params = inner
-        .map(|p| p.as_str().to_string())
-        .collect::<Vec<String>>();
+    GenericTypingNode { params }
+}
 
-    GenericTypingNode {
-        types: generic_params,
+/// Builds `T impl ToString` or `const N: i32`.
+fn build_type_param(pair: Pair, base: usize) -> TypeParamNode {
+    let span = span_of(&pair, base);
+    let is_const = pair.as_rule() == Rule::const_param;
+    let mut inner = pair.into_inner();
+    if is_const {
+        inner.next();
     }
-}
+    let name = inner.next().map(|p| p.as_str().to_string());
 
-fn build_block(pair: Pair) -> BlockNode {
-    let expressions = pair
-        .into_inner()
-        .map(|n| match n.as_rule() {
-            Rule::expression => build_expression(n),
+    let (bounds, const_type) = match is_const {
+        true => (vec![], inner.next().map(build_type)),
+        false => {
+            let traits = inner.filter(|p| p.as_rule() == Rule::path);
+            (traits.map(|p| p.as_str().to_string()).collect(), None)
+        }
+    };
 
-            _ => panic!("Invalid node in block: {:?}", n.as_rule()),
-        })
-        .collect();
+    TypeParamNode {
+        name: name.unwrap_or_default(),
+        bounds,
+        const_type,
+        span,
+    }
+}
+
+fn build_block(pair: Pair, base: usize) -> BuildResult<BlockNode> {
+    let span = span_of(&pair, base);
+    let expressions = collect_all(pair.into_inner().map(|n| match n.as_rule() {
+        Rule::expression => build_expression(n, base),
+        _ => Err(unsupported(&n, base)),
+    }))?;
 
-    BlockNode { expressions }
+    Ok(BlockNode { expressions, span })
 }
 
-fn build_param_def_list(pair: Pair) -> Vec<FuncParam> {
+fn build_param_def_list(pair: Pair, base: usize) -> BuildResult<Vec<FuncParam>> {
     let inner = pair.into_inner();
 
-    inner.map(|p| build_field_def(p)).collect()
+    collect_all(inner.map(|p| build_field_def(p, base)))
 }
 
-fn build_field_def(pair: Pair) -> FuncParam {
+fn build_field_def(pair: Pair, base: usize) -> BuildResult<FuncParam> {
+    let span = span_of(&pair, base);
     let mut inner = pair.into_inner();
-    FuncParam {
-        name: inner.next().unwrap().as_str().to_string(),
-        param_type: inner.next().unwrap().as_str().to_string(),
-    }
+
+    Ok(FuncParam {
+        name: next_child(&mut inner, Rule::field_def, span, "name")?
+            .as_str()
+            .to_string(),
+        param_type: build_type(next_child(&mut inner, Rule::field_def, span, "type")?),
+        span,
+    })
 }
 
-fn build_return_type(pair: Pair) -> String {
-    pair.as_str().to_string()
+fn build_return_type(pair: Pair) -> Type {
+    pair.into_inner().next().map_or(Type::Void, build_type)
+}
+
+/// Type named by a `type_ref`: a plain name, `dyn Trait` or a generic type
+/// with its arguments.
+fn build_type(pair: Pair) -> Type {
+    match pair.as_rule() {
+        Rule::generic_type => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().map(|p| p.as_str().to_string());
+            let args = inner.next().map(build_generic_params);
+            Type::Generic(name.unwrap_or_default(), args.unwrap_or_default())
+        }
+        Rule::dyn_type => {
+            let trait_name = pair.into_inner().last().map(|p| p.as_str().to_string());
+            Type::Dyn(trait_name.unwrap_or_default())
+        }
+        Rule::array_type => {
+            let mut inner = pair.into_inner();
+            let element = inner.next().map(build_type).unwrap_or(Type::Unknown);
+            let length = inner.next().map(build_type).unwrap_or(Type::Unknown);
+            Type::Array(Box::new(element), Box::new(length))
+        }
+        // Too large for any array anyway, so the type checker reports it.
+        Rule::int_lit => Type::Const(pair.as_str().parse().unwrap_or(i64::MAX)),
+        _ => Type::from_name(pair.as_str()),
+    }
 }
@@ -0,0 +1,56 @@
+use super::expression::{ExpressionNode, Indent, IndentDisplay};
+use super::types::Type;
+use crate::diagnostics::Span;
+use std::fmt::{Debug, Formatter, Result};
+
+/// `[1, 2, 3]`, an array holding the given values.
+#[derive(Debug, Clone)]
+pub struct ArrayLitNode {
+    pub elements: Vec<ExpressionNode>,
+    /// Type of the array, filled in by the type checker.
+    pub array_type: Option<Type>,
+    pub span: Span,
+}
+
+/// `[0; N]`, an array holding the same value `length` times. The length is a
+/// `Const` or the name of a const parameter.
+#[derive(Debug, Clone)]
+pub struct ArrayRepeatNode {
+    pub value: Box<ExpressionNode>,
+    pub length: Type,
+    /// Type of the array, filled in by the type checker.
+    pub array_type: Option<Type>,
+    pub span: Span,
+}
+
+/// `object[index]`, one value of an array.
+#[derive(Debug, Clone)]
+pub struct IndexNode {
+    pub object: Box<ExpressionNode>,
+    pub index: Box<ExpressionNode>,
+}
+
+impl IndentDisplay for ArrayLitNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        for element in &self.elements {
+            element.fmt_with_indent(f, indent)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IndentDisplay for ArrayRepeatNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Length: {}", indent.as_str(), self.length)?;
+        self.value.fmt_with_indent(f, indent)
+    }
+}
+
+impl IndentDisplay for IndexNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Index:", indent.as_str())?;
+        self.index.fmt_with_indent(f, indent.increment(1))?;
+        self.object.fmt_with_indent(f, indent)
+    }
+}
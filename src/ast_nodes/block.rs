@@ -1,9 +1,11 @@
 use super::expression::ExpressionNode;
+use crate::diagnostics::Span;
 use std::fmt;
 
 #[derive(Debug, Clone)]
 pub struct BlockNode {
     pub expressions: Vec<ExpressionNode>,
+    pub span: Span,
 }
 
 impl fmt::Display for BlockNode {
@@ -0,0 +1,72 @@
+use super::{
+    block::BlockNode,
+    expression::{ExpressionNode, Indent, IndentDisplay},
+    types::Type,
+};
+use crate::diagnostics::Span;
+use std::fmt::{Formatter, Result};
+
+#[derive(Debug, Clone)]
+pub struct IfExprNode {
+    pub condition: Box<ExpressionNode>,
+    pub then_block: BlockNode,
+    /// `else if` is stored as an else block holding only the nested `if`.
+    pub else_block: Option<BlockNode>,
+    /// Type of the value of the branches, filled in by the type checker.
+    /// `void` unless both branches end in a value.
+    pub value_type: Option<Type>,
+}
+
+impl IndentDisplay for IfExprNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Condition:", indent.as_str())?;
+        self.condition.fmt_with_indent(f, indent.increment(1))?;
+        writeln!(f, "{}Then:", indent.as_str())?;
+        self.then_block.fmt_with_indent(f, indent.increment(1))?;
+        if let Some(else_block) = &self.else_block {
+            writeln!(f, "{}Else:", indent.as_str())?;
+            else_block.fmt_with_indent(f, indent.increment(1))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WhileExprNode {
+    pub condition: Box<ExpressionNode>,
+    pub body: BlockNode,
+}
+
+impl IndentDisplay for WhileExprNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Condition:", indent.as_str())?;
+        self.condition.fmt_with_indent(f, indent.increment(1))?;
+        writeln!(f, "{}Body:", indent.as_str())?;
+        self.body.fmt_with_indent(f, indent.increment(1))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ForExprNode {
+    pub variable: String,
+    pub variable_span: Span,
+    pub start: Box<ExpressionNode>,
+    /// Exclusive upper bound, evaluated once before the first iteration.
+    pub end: Box<ExpressionNode>,
+    pub body: BlockNode,
+    /// Type of the loop variable, filled in by the type checker.
+    pub var_type: Option<Type>,
+}
+
+impl IndentDisplay for ForExprNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Variable: {}", indent.as_str(), self.variable)?;
+        writeln!(f, "{}Start:", indent.as_str())?;
+        self.start.fmt_with_indent(f, indent.increment(1))?;
+        writeln!(f, "{}End:", indent.as_str())?;
+        self.end.fmt_with_indent(f, indent.increment(1))?;
+        writeln!(f, "{}Body:", indent.as_str())?;
+        self.body.fmt_with_indent(f, indent.increment(1))
+    }
+}
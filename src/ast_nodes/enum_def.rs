@@ -0,0 +1,75 @@
+use super::expression::{ExpressionNode, Indent, IndentDisplay};
+use super::func_def::GenericTypingNode;
+use super::types::Type;
+use crate::diagnostics::Span;
+use std::fmt::{Debug, Formatter, Result};
+
+#[derive(Debug, Clone)]
+pub struct EnumDefNode {
+    pub name: String,
+    /// Marked `pub`, so it can be used outside of its module.
+    pub public: bool,
+    pub generic_typing: Option<GenericTypingNode>,
+    pub variants: Vec<EnumVariantNode>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumVariantNode {
+    pub name: String,
+    pub fields: Vec<EnumVariantField>,
+    pub span: Span,
+}
+
+/// Value held by a variant, `name` is `None` for tuple-like variants.
+#[derive(Debug, Clone)]
+pub struct EnumVariantField {
+    pub name: Option<String>,
+    pub field_type: Type,
+    pub span: Span,
+}
+
+impl IndentDisplay for EnumDefNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}{}", indent.as_str(), self.name)?;
+        for variant in &self.variants {
+            let fields = variant
+                .fields
+                .iter()
+                .map(|field| match &field.name {
+                    Some(name) => format!("{}: {}", name, field.field_type),
+                    None => field.field_type.to_string(),
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "{}{}({})",
+                indent.increment(1).as_str(),
+                variant.name,
+                fields.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumInitNode {
+    pub enum_name: String,
+    /// Type arguments of a generic enum, inferred from the values by the
+    /// type checker when omitted.
+    pub generic_params: Vec<Type>,
+    pub variant: String,
+    pub args: Vec<ExpressionNode>,
+    pub span: Span,
+}
+
+impl IndentDisplay for EnumInitNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}{}::{}", indent.as_str(), self.enum_name, self.variant)?;
+        self.args
+            .iter()
+            .try_for_each(|arg| arg.fmt_with_indent(f, indent.increment(1)))
+    }
+}
@@ -1,11 +1,19 @@
 use super::{
+    array::{ArrayLitNode, ArrayRepeatNode, IndexNode},
     block::BlockNode,
-    func_call::FuncCallNode,
+    control_flow::{ForExprNode, IfExprNode, WhileExprNode},
+    enum_def::{EnumDefNode, EnumInitNode},
+    func_call::{FuncCallNode, MethodCallNode},
     func_def::FuncDefNode,
+    match_expr::MatchExprNode,
+    module::{ModuleNode, UseNode},
     struct_def::{StructDefNode, StructFieldAccessNode},
-    term::{StructInitNode, VarDeclNode},
+    term::{AssignmentNode, StructInitNode, VarDeclNode},
+    trait_def::{DynCoercionNode, ImplBlockNode, TraitDefNode},
+    types::Type,
     var_access::VarAccessNode,
 };
+use crate::diagnostics::Span;
 use colored::Colorize;
 use std::fmt::{Debug, Display, Formatter, Result};
 
@@ -37,6 +45,7 @@ pub trait IndentDisplay {
 #[derive(Debug, Clone)]
 pub struct ExpressionNode {
     pub kind: ExpressionKind,
+    pub span: Span,
 }
 
 impl IndentDisplay for ExpressionNode {
@@ -60,20 +69,44 @@ impl Display for ExpressionNode {
 #[derive(Debug, Clone)]
 pub enum ExpressionKind {
     VarDecl(VarDeclNode),
-    AddExpr(AddExprNode),
+    Assignment(AssignmentNode),
+    BinaryExpr(BinaryExprNode),
+    UnaryExpr(UnaryExprNode),
     FuncDef(FuncDefNode),
     ReturnExpr(ReturnExprNode),
     CImport(CImportNode),
     FuncCall(FuncCallNode),
-    IntLit(i32),
+    MethodCall(MethodCallNode),
+    /// Any value from `i64::MIN` to `u64::MAX`, its type decides which fit.
+    IntLit(i128),
+    FloatLit(f32),
     StrLit(String),
+    VarAccess(VarAccessNode),
     StructDef(StructDefNode),
+    StructInit(StructInitNode),
     StructFieldAccess(StructFieldAccessNode),
+    EnumDef(EnumDefNode),
+    EnumInit(EnumInitNode),
+    TraitDef(TraitDefNode),
+    ImplBlock(ImplBlockNode),
+    Module(ModuleNode),
+    Use(UseNode),
+    DynCoercion(DynCoercionNode),
+    ArrayLit(ArrayLitNode),
+    ArrayRepeat(ArrayRepeatNode),
+    Index(IndexNode),
+    If(IfExprNode),
+    Match(MatchExprNode),
+    While(WhileExprNode),
+    For(ForExprNode),
+    Break,
+    Continue,
 }
 
 #[derive(Debug, Clone)]
 pub struct CImportNode {
     pub module: String,
+    pub span: Span,
 }
 impl IndentDisplay for CImportNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
@@ -85,12 +118,16 @@ impl IndentDisplay for CImportNode {
 
 #[derive(Debug, Clone)]
 pub struct ReturnExprNode {
-    pub expression: Box<ExpressionNode>,
+    /// `None` for a bare `return`, which returns nothing.
+    pub expression: Option<Box<ExpressionNode>>,
+    pub span: Span,
 }
 
 impl IndentDisplay for ReturnExprNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        self.expression.fmt_with_indent(f, indent.increment(1))?;
+        if let Some(expression) = &self.expression {
+            expression.fmt_with_indent(f, indent.increment(1))?;
+        }
 
         Ok(())
     }
@@ -99,57 +136,106 @@ impl IndentDisplay for ReturnExprNode {
 impl IndentDisplay for ExpressionKind {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
         let string = match self {
-            ExpressionKind::AddExpr(_) => "AddExpr".on_truecolor(100, 149, 237).black(),
+            ExpressionKind::BinaryExpr(_) => "BinaryExpr".on_truecolor(100, 149, 237).black(),
+            ExpressionKind::UnaryExpr(_) => "UnaryExpr".on_truecolor(100, 149, 237).black(),
             ExpressionKind::VarDecl(_) => "VarDecl".on_truecolor(100, 150, 200).black(),
+            ExpressionKind::Assignment(_) => "Assignment".on_truecolor(100, 150, 200).black(),
             ExpressionKind::FuncDef(_) => "FuncDef".on_truecolor(10, 150, 200).black(),
             ExpressionKind::ReturnExpr(_) => "ReturnExpr".on_truecolor(50, 150, 200).black(),
             ExpressionKind::CImport(node) => format!("CImport({})", node.module)
                 .on_truecolor(50, 150, 200)
                 .black(),
             ExpressionKind::FuncCall(_) => "FuncCall()".on_truecolor(245, 184, 8).black(),
+            ExpressionKind::MethodCall(_) => "MethodCall()".on_truecolor(245, 184, 8).black(),
             ExpressionKind::IntLit(_) => "IntLit()".on_truecolor(25, 67, 1).black(),
+            ExpressionKind::FloatLit(_) => "FloatLit()".on_truecolor(25, 67, 1).black(),
             ExpressionKind::StrLit(_) => "StrLit()".on_truecolor(5, 67, 1).black(),
+            ExpressionKind::VarAccess(_) => "VarAccess()".on_truecolor(147, 112, 219).black(),
             ExpressionKind::StructDef(_) => "StructDef()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::StructInit(_) => "StructInit()".on_truecolor(5, 78, 155).black(),
             ExpressionKind::StructFieldAccess(_) => {
                 "StructFieldAccess()".on_truecolor(5, 78, 155).black()
             }
+            ExpressionKind::EnumDef(_) => "EnumDef()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::EnumInit(_) => "EnumInit()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::TraitDef(_) => "TraitDef()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::ImplBlock(_) => "ImplBlock()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::Module(_) => "Module()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::Use(_) => "Use()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::DynCoercion(_) => "DynCoercion()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::ArrayLit(_) => "ArrayLit()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::ArrayRepeat(_) => "ArrayRepeat()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::Index(_) => "Index()".on_truecolor(5, 78, 155).black(),
+            ExpressionKind::If(_) => "If".on_truecolor(60, 179, 113).black(),
+            ExpressionKind::Match(_) => "Match".on_truecolor(60, 179, 113).black(),
+            ExpressionKind::While(_) => "While".on_truecolor(60, 179, 113).black(),
+            ExpressionKind::For(_) => "For".on_truecolor(60, 179, 113).black(),
+            ExpressionKind::Break => "Break".on_truecolor(60, 179, 113).black(),
+            ExpressionKind::Continue => "Continue".on_truecolor(60, 179, 113).black(),
         };
         writeln!(f, "{}{}", indent.as_str(), string)?;
 
         match self {
-            ExpressionKind::AddExpr(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::BinaryExpr(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::UnaryExpr(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::VarAccess(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::VarDecl(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Assignment(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::FuncDef(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::ReturnExpr(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::StructDef(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::CImport(_) => Ok(()),
             ExpressionKind::FuncCall(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::MethodCall(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::StructInit(node) => node.fmt_with_indent(f, indent.increment(1)),
             ExpressionKind::StructFieldAccess(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::EnumDef(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::EnumInit(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::TraitDef(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::ImplBlock(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Module(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Use(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::DynCoercion(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::ArrayLit(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::ArrayRepeat(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Index(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::If(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Match(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::While(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::For(node) => node.fmt_with_indent(f, indent.increment(1)),
+            ExpressionKind::Break | ExpressionKind::Continue => Ok(()),
             ExpressionKind::StrLit(_) => writeln!(
                 f,
                 "{}{}",
                 indent.increment(1).as_str(),
                 "StrLit".on_truecolor(200, 120, 125).black()
             ),
-            ExpressionKind::IntLit(_) => writeln!(
+            ExpressionKind::IntLit(val) => writeln!(
                 f,
-                "{}{}",
+                "{}{}({})",
                 indent.increment(1).as_str(),
-                "IntLit".on_truecolor(200, 120, 125).black()
+                "IntLiteral".black().on_truecolor(200, 85, 85),
+                val
             ),
+            ExpressionKind::FloatLit(val) => {
+                writeln!(f, "{}FloatLiteral({})", indent.increment(1).as_str(), val)
+            }
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct AddExprNode {
-    pub left: MulExprNode,
-    pub addent: Vec<AddExprPart>,
+pub struct BinaryExprNode {
+    pub left: Box<ExpressionNode>,
+    pub op: BinaryOp,
+    pub right: Box<ExpressionNode>,
+    /// Type of the result, filled in by the type checker.
+    pub result_type: Option<Type>,
+    pub span: Span,
 }
 
-impl IndentDisplay for AddExprNode {
+impl IndentDisplay for BinaryExprNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        // Display left part
         writeln!(
             f,
             "{}{}:",
@@ -157,176 +243,115 @@ impl IndentDisplay for AddExprNode {
             "Left".black().on_truecolor(200, 177, 54)
         )?;
         self.left.fmt_with_indent(f, indent.increment(1))?;
-
-        // Display addents if any
-        if !self.addent.is_empty() {
-            writeln!(
-                f,
-                "{}{}",
-                indent.as_str(),
-                "Addents".on_truecolor(128, 180, 12).black(),
-            )?;
-            let inner_indent = indent.increment(1);
-            for (i, part) in self.addent.iter().enumerate() {
-                writeln!(f, "{}[{}]:", inner_indent.as_str(), i)?;
-                part.fmt_with_indent(f, inner_indent.increment(1))?;
-            }
-        }
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct MulExprPart {
-    pub op: MulOp,
-    pub value: PrimaryNode,
-}
-
-impl IndentDisplay for MulExprPart {
-    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
         writeln!(
             f,
-            "{}{}: {}",
+            "{}{}({})",
             indent.as_str(),
             "Operator".black().on_truecolor(199, 78, 211),
             self.op
         )?;
-        writeln!(f, "{}Value:", indent.as_str())?;
-        self.value.fmt_with_indent(f, indent.increment(1))
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct AddExprPart {
-    pub op: AddOp,
-    pub value: MulExprNode,
-}
-
-impl IndentDisplay for AddExprPart {
-    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
         writeln!(
             f,
-            "{}{}({})",
+            "{}{}:",
             indent.as_str(),
-            "Operator".black().on_truecolor(199, 78, 211),
-            self.op
+            "Right".black().on_truecolor(200, 177, 54)
         )?;
-        writeln!(f, "{}Value:", indent.as_str())?;
-        self.value.fmt_with_indent(f, indent.increment(1))
+        self.right.fmt_with_indent(f, indent.increment(1))
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct MulExprNode {
-    pub left: PrimaryNode,
-    pub factor: Vec<MulExprPart>,
+pub struct UnaryExprNode {
+    pub op: UnaryOp,
+    pub operand: Box<ExpressionNode>,
 }
 
-impl IndentDisplay for MulExprNode {
+impl IndentDisplay for UnaryExprNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
         writeln!(
             f,
-            "{}{}",
+            "{}{}({})",
             indent.as_str(),
-            "MulExpr".on_truecolor(255, 165, 0).black()
-        )?;
-
-        let inner_indent = indent.increment(1);
-        writeln!(
-            f,
-            "{}{}:",
-            inner_indent.as_str(),
-            "Left".black().on_truecolor(200, 177, 54)
+            "Operator".black().on_truecolor(199, 78, 211),
+            self.op
         )?;
-        self.left.fmt_with_indent(f, inner_indent.increment(1))?;
-
-        if !self.factor.is_empty() {
-            writeln!(f, "{}Factors:", inner_indent.as_str())?;
-            let factors_indent = inner_indent.increment(1);
-            for (i, factor) in self.factor.iter().enumerate() {
-                writeln!(f, "{}[{}]:", factors_indent.as_str(), i)?;
-                factor.fmt_with_indent(f, factors_indent.increment(1))?;
-            }
-        }
-
-        Ok(())
+        self.operand.fmt_with_indent(f, indent.increment(1))
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct PrimaryNode {
-    pub kind: PrimaryKind,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    And,
+    Or,
 }
 
-impl IndentDisplay for PrimaryNode {
-    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        writeln!(
-            f,
-            "{}{}",
-            indent.as_str(),
-            "Primary".on_truecolor(147, 112, 219).black()
-        )?;
-
-        let inner_indent = indent.increment(1);
-        match &self.kind {
-            PrimaryKind::IntLit(val) => {
-                writeln!(
-                    f,
-                    "{}{}({})",
-                    inner_indent.as_str(),
-                    "IntLiteral".black().on_truecolor(200, 85, 85),
-                    val
-                )
-            }
-            PrimaryKind::FloatLit(val) => {
-                writeln!(f, "{}FloatLiteral({})", inner_indent.as_str(), val)
-            }
-            PrimaryKind::StrLit(val) => {
-                writeln!(f, "{}StringLiteral(\"{}\")", inner_indent.as_str(), val)
-            }
-            PrimaryKind::VarAccess(expr) => {
-                writeln!(f, "{}VarAccess:", inner_indent.as_str())?;
-                expr.fmt_with_indent(f, inner_indent.increment(1))
-            }
-        }
+impl BinaryOp {
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Add
+                | BinaryOp::Subtract
+                | BinaryOp::Multiply
+                | BinaryOp::Divide
+                | BinaryOp::Modulo
+                | BinaryOp::Power
+        )
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum PrimaryKind {
-    FloatLit(f32),
-    StrLit(String),
-    VarAccess(VarAccessNode),
-    IntLit(i32),
-}
+    pub fn is_equality(&self) -> bool {
+        matches!(self, BinaryOp::Equal | BinaryOp::NotEqual)
+    }
 
-#[derive(Debug, Clone)]
-pub enum MulOp {
-    Multiply,
-    Divide,
+    pub fn is_logical(&self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
 }
 
-impl Display for MulOp {
+impl Display for BinaryOp {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        match self {
-            MulOp::Divide => write!(f, "/"),
-            MulOp::Multiply => write!(f, "*"),
-        }
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Power => "**",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::LessOrEqual => "<=",
+            BinaryOp::GreaterOrEqual => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        };
+        write!(f, "{}", symbol)
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum AddOp {
-    Add,
-    Subtract, // Fixed typo in the enum variant name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
 }
 
-impl Display for AddOp {
+impl Display for UnaryOp {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            AddOp::Add => write!(f, "+"),
-            AddOp::Subtract => write!(f, "-"),
+            UnaryOp::Negate => write!(f, "-"),
+            UnaryOp::Not => write!(f, "!"),
         }
     }
 }
@@ -336,8 +361,24 @@ impl Display for AddOp {
 
 impl IndentDisplay for VarDeclNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        // Implementation would depend on the actual structure of VarDeclNode
-        writeln!(f, "{}Name: {}", indent.as_str(), self.name)?;
+        writeln!(
+            f,
+            "{}Name: {}{}",
+            indent.as_str(),
+            if self.mutable { "mut " } else { "" },
+            self.name
+        )?;
+        if let Some(var_type) = &self.var_type {
+            writeln!(f, "{}Type: {}", indent.as_str(), var_type)?;
+        }
+        writeln!(f, "{}Value:", indent.as_str())?;
+        self.value.fmt_with_indent(f, indent.increment(1))
+    }
+}
+
+impl IndentDisplay for AssignmentNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Name: {}", indent.as_str(), self.target())?;
         writeln!(f, "{}Value:", indent.as_str())?;
         self.value.fmt_with_indent(f, indent.increment(1))
     }
@@ -356,9 +397,25 @@ impl IndentDisplay for FuncCallNode {
     }
 }
 
+impl IndentDisplay for MethodCallNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Method: {}", indent.as_str(), self.method)?;
+        self.object.fmt_with_indent(f, indent.increment(1))?;
+        self.args
+            .iter()
+            .try_for_each(|arg| arg.fmt_with_indent(f, indent.increment(1)))
+    }
+}
+
 impl IndentDisplay for StructInitNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        writeln!(f, "{}StructInitCONTENT", indent.as_str())
+        writeln!(f, "{}{}", indent.as_str(), self.struct_name)?;
+        for field in &self.fields {
+            writeln!(f, "{}{}:", indent.increment(1).as_str(), field.name)?;
+            field.value.fmt_with_indent(f, indent.increment(2))?;
+        }
+
+        Ok(())
     }
 }
 
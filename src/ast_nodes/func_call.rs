@@ -1,12 +1,14 @@
 use std::fmt;
 
-use super::expression::ExpressionNode;
+use super::{expression::ExpressionNode, types::Type};
+use crate::diagnostics::Span;
 
 #[derive(Debug, Clone)]
 pub struct FuncCallNode {
     pub name: String,
     pub params: Vec<ExpressionNode>,
-    pub generic_params: Vec<String>,
+    pub generic_params: Vec<Type>,
+    pub span: Span,
 }
 
 impl fmt::Display for FuncCallNode {
@@ -14,3 +16,20 @@ impl fmt::Display for FuncCallNode {
         writeln!(f, "FuncCall(NAME)")
     }
 }
+
+/// `object.method(args)`.
+#[derive(Debug, Clone)]
+pub struct MethodCallNode {
+    pub object: Box<ExpressionNode>,
+    pub method: String,
+    pub args: Vec<ExpressionNode>,
+    /// Type of the object, filled in by the type checker.
+    pub receiver_type: Option<Type>,
+    /// Whether the object names a struct rather than a value, like in
+    /// `X.add_two(5)`. Filled in by the type checker.
+    pub static_call: bool,
+    /// Trait whose `impl` block defines the method, filled in by the type
+    /// checker. `None` for methods defined in the struct itself.
+    pub trait_name: Option<String>,
+    pub span: Span,
+}
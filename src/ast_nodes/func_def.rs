@@ -1,21 +1,37 @@
 use super::expression::Indent;
+use crate::diagnostics::Span;
 use std::fmt::{Debug, Formatter, Result};
 
-use super::{block::BlockNode, expression::IndentDisplay};
+use super::{block::BlockNode, expression::IndentDisplay, types::Type};
 
 #[derive(Debug, Clone)]
 pub struct FuncDefNode {
     pub name: String,
+    /// Marked `pub`, so it can be used outside of its module.
+    pub public: bool,
+    /// Receiver of a method. Methods without one are called on the struct,
+    /// like `X.add_two(5)`.
+    pub self_param: Option<SelfParam>,
     pub params: Vec<FuncParam>,
     pub body: BlockNode,
-    pub return_type: Option<String>,
+    pub return_type: Option<Type>,
     pub generic_typing: Option<GenericTypingNode>,
+    pub span: Span,
+}
+
+/// `&self` or `&mut self`. Methods receive a pointer to the value they are
+/// called on.
+#[derive(Debug, Clone, Copy)]
+pub struct SelfParam {
+    pub mutable: bool,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct FuncParam {
     pub name: String,
-    pub param_type: String,
+    pub param_type: Type,
+    pub span: Span,
 }
 
 impl IndentDisplay for FuncParam {
@@ -26,5 +42,23 @@ impl IndentDisplay for FuncParam {
 
 #[derive(Debug, Clone)]
 pub struct GenericTypingNode {
-    pub types: Vec<String>,
+    pub params: Vec<TypeParamNode>,
+}
+
+impl GenericTypingNode {
+    pub fn names(&self) -> Vec<String> {
+        self.params.iter().map(|p| p.name.clone()).collect()
+    }
+}
+
+/// `T impl ToString`, a type parameter with the traits every type it is
+/// instantiated with has to implement, or `const N: i32`, a parameter taking
+/// a number.
+#[derive(Debug, Clone)]
+pub struct TypeParamNode {
+    pub name: String,
+    pub bounds: Vec<String>,
+    /// Type of the value of a const parameter.
+    pub const_type: Option<Type>,
+    pub span: Span,
 }
@@ -0,0 +1,113 @@
+use super::{
+    block::BlockNode,
+    expression::{BinaryOp, ExpressionNode, Indent, IndentDisplay},
+    types::Type,
+};
+use crate::diagnostics::Span;
+use std::fmt::{Formatter, Result};
+
+#[derive(Debug, Clone)]
+pub struct MatchExprNode {
+    pub subject: Box<ExpressionNode>,
+    pub arms: Vec<MatchArmNode>,
+    /// Type of the matched value, filled in by the type checker.
+    pub subject_type: Option<Type>,
+    /// Type of the value of the arms, filled in by the type checker. `void`
+    /// unless every arm ends in a value.
+    pub value_type: Option<Type>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArmNode {
+    pub pattern: PatternNode,
+    pub guard: Option<ExpressionNode>,
+    /// Arms with a single expression are stored as a block holding it.
+    pub body: BlockNode,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternNode {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternKind {
+    Wildcard,
+    /// Matches values equal to a number or string literal.
+    Literal(Box<ExpressionNode>),
+    /// `_ > 18`, matches values the comparison holds for.
+    Comparison {
+        op: BinaryOp,
+        value: Box<ExpressionNode>,
+    },
+    /// Binds the value to a name. The type of the value is filled in by the
+    /// type checker.
+    Binding {
+        name: String,
+        value_type: Option<Type>,
+    },
+    /// Matches one variant and the values it holds. The enum is filled in by
+    /// the type checker when omitted, it also turns bindings naming variants
+    /// without values, like `None`, into variant patterns.
+    Variant {
+        enum_name: Option<String>,
+        /// Type of the matched enum, with the arguments of generic enums.
+        /// Filled in by the type checker.
+        enum_type: Option<Type>,
+        variant: String,
+        fields: Vec<PatternNode>,
+    },
+}
+
+impl PatternKind {
+    /// Whether the pattern matches every value.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, PatternKind::Wildcard | PatternKind::Binding { .. })
+    }
+}
+
+impl IndentDisplay for MatchExprNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}Subject:", indent.as_str())?;
+        self.subject.fmt_with_indent(f, indent.increment(1))?;
+        for arm in &self.arms {
+            writeln!(f, "{}Arm: {}", indent.as_str(), arm.pattern)?;
+            if let Some(guard) = &arm.guard {
+                writeln!(f, "{}Guard:", indent.increment(1).as_str())?;
+                guard.fmt_with_indent(f, indent.increment(2))?;
+            }
+            arm.body.fmt_with_indent(f, indent.increment(1))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PatternNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.kind {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Literal(_) => write!(f, "<literal>"),
+            PatternKind::Comparison { op, .. } => write!(f, "_ {} <value>", op),
+            PatternKind::Binding { name, .. } => write!(f, "{}", name),
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                fields,
+                ..
+            } => {
+                if let Some(enum_name) = enum_name {
+                    write!(f, "{}::", enum_name)?;
+                }
+                write!(f, "{}", variant)?;
+                if !fields.is_empty() {
+                    let fields = fields.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
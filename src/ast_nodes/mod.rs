@@ -1,8 +1,15 @@
+pub mod array;
 pub mod block;
+pub mod control_flow;
+pub mod enum_def;
 pub mod expression;
 pub mod func_call;
 pub mod func_def;
+pub mod match_expr;
+pub mod module;
 pub mod program;
 pub mod struct_def;
 pub mod term;
+pub mod trait_def;
+pub mod types;
 pub mod var_access;
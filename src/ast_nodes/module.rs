@@ -0,0 +1,65 @@
+use super::expression::{ExpressionNode, Indent, IndentDisplay};
+use crate::diagnostics::Span;
+use std::fmt::{Debug, Formatter, Result};
+
+/// `mod math { ... }`. Modules declared without a body, like `mod net`, are
+/// loaded from their own file.
+#[derive(Debug, Clone)]
+pub struct ModuleNode {
+    pub name: String,
+    pub public: bool,
+    /// Items of the module, `None` until the file of the module is loaded.
+    pub items: Option<Vec<ExpressionNode>>,
+    pub span: Span,
+}
+
+/// `use math::square`, makes an item of another module usable by its last
+/// name or by the name given with `as`.
+#[derive(Debug, Clone)]
+pub struct UseNode {
+    pub path: Vec<String>,
+    pub alias: Option<String>,
+    /// Marked `pub`, so other modules can use the item through this one.
+    pub public: bool,
+    pub span: Span,
+}
+
+impl UseNode {
+    /// Name the item is usable by.
+    pub fn name(&self) -> &str {
+        match &self.alias {
+            Some(alias) => alias,
+            None => self.path.last().map_or("", String::as_str),
+        }
+    }
+}
+
+impl IndentDisplay for ModuleNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        let visibility = if self.public { "pub " } else { "" };
+        writeln!(f, "{}{}{}", indent.as_str(), visibility, self.name)?;
+        match &self.items {
+            Some(items) => items
+                .iter()
+                .try_for_each(|item| item.fmt_with_indent(f, indent.increment(1))),
+            None => writeln!(f, "{}<not loaded>", indent.increment(1).as_str()),
+        }
+    }
+}
+
+impl IndentDisplay for UseNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        let visibility = if self.public { "pub " } else { "" };
+        write!(
+            f,
+            "{}{}{}",
+            indent.as_str(),
+            visibility,
+            self.path.join("::")
+        )?;
+        match &self.alias {
+            Some(alias) => writeln!(f, " as {}", alias),
+            None => writeln!(f),
+        }
+    }
+}
@@ -1,39 +1,55 @@
+use super::expression::ExpressionNode;
 use super::expression::Indent;
 use super::expression::IndentDisplay;
+use super::func_def::{FuncDefNode, GenericTypingNode};
+use super::types::Type;
+use crate::diagnostics::Span;
 use std::fmt::{Debug, Formatter, Result};
 
 #[derive(Debug, Clone)]
 pub struct StructDefNode {
     pub name: String,
+    /// Marked `pub`, so it can be used outside of its module.
+    pub public: bool,
+    pub generic_typing: Option<GenericTypingNode>,
     pub fields: Vec<StructFieldNode>,
+    pub methods: Vec<FuncDefNode>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct StructFieldNode {
     pub name: String,
-    pub type_name: String,
+    pub field_type: Type,
+    /// Value used by struct literals omitting the field.
+    pub default: Option<ExpressionNode>,
+    pub span: Span,
 }
 
 impl IndentDisplay for StructDefNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        writeln!(f, "{}{}", indent.as_str(), self.name)
+        writeln!(f, "{}{}", indent.as_str(), self.name)?;
+        for method in &self.methods {
+            writeln!(f, "{}Method: {}", indent.increment(1).as_str(), method.name)?;
+            method.fmt_with_indent(f, indent.increment(2))?;
+        }
+
+        Ok(())
     }
 }
 
+/// `object.field_name`, where the object may itself be a field access like
+/// in `a.b.c`.
 #[derive(Debug, Clone)]
 pub struct StructFieldAccessNode {
-    pub struct_name: String,
+    pub object: Box<ExpressionNode>,
     pub field_name: String,
+    pub span: Span,
 }
 
 impl IndentDisplay for StructFieldAccessNode {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
-        writeln!(
-            f,
-            "{}{}.{}",
-            indent.as_str(),
-            self.struct_name,
-            self.field_name
-        )
+        writeln!(f, "{}Field: {}", indent.as_str(), self.field_name)?;
+        self.object.fmt_with_indent(f, indent.increment(1))
     }
 }
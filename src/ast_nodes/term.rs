@@ -1,10 +1,59 @@
-use super::expression::ExpressionNode;
+use super::{expression::ExpressionNode, types::Type};
+use crate::diagnostics::Span;
 
 #[derive(Debug, Clone)]
 pub struct VarDeclNode {
     pub name: String,
     pub value: Box<ExpressionNode>,
+    pub mutable: bool,
+    /// Explicit type given with `as`, filled in by the type checker otherwise.
+    pub var_type: Option<Type>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
-pub struct StructInitNode {}
+pub struct AssignmentNode {
+    pub name: String,
+    /// Fields and array elements assigned to instead of the variable itself,
+    /// `a.b[i].c = 1` has the path `b`, `[i]`, `c`.
+    pub path: Vec<PlaceSegment>,
+    pub value: Box<ExpressionNode>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum PlaceSegment {
+    Field(String),
+    Index(Box<ExpressionNode>),
+}
+
+impl AssignmentNode {
+    /// The assigned place as written, like `a.b[..].c`.
+    pub fn target(&self) -> String {
+        let mut target = self.name.clone();
+        for segment in &self.path {
+            match segment {
+                PlaceSegment::Field(field) => target += &format!(".{}", field),
+                PlaceSegment::Index(_) => target += "[..]",
+            }
+        }
+        target
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StructInitNode {
+    pub struct_name: String,
+    /// Type arguments of a generic struct, inferred from the fields by the
+    /// type checker when omitted.
+    pub generic_params: Vec<Type>,
+    pub fields: Vec<StructFieldInitNode>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructFieldInitNode {
+    pub name: String,
+    pub value: ExpressionNode,
+    pub span: Span,
+}
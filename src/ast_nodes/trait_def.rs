@@ -0,0 +1,102 @@
+use super::expression::{ExpressionNode, Indent, IndentDisplay};
+use super::func_def::{FuncDefNode, GenericTypingNode};
+use super::types::Type;
+use crate::diagnostics::Span;
+use std::fmt::{Debug, Formatter, Result};
+
+#[derive(Debug, Clone)]
+pub struct TraitDefNode {
+    pub name: String,
+    /// Marked `pub`, so it can be used outside of its module.
+    pub public: bool,
+    pub methods: Vec<TraitMethodNode>,
+    pub span: Span,
+}
+
+/// Method of a trait. Required methods have an empty body and a return type
+/// of `void` when none is given.
+#[derive(Debug, Clone)]
+pub struct TraitMethodNode {
+    pub func: FuncDefNode,
+    /// Has no default body, so every impl has to define it.
+    pub required: bool,
+}
+
+/// `impl Trait for Type { ... }`, methods missing here use the trait's
+/// default body. Generic impls, like `impl<T impl ToString> ToString for
+/// Pair<T>`, implement the trait for every instance of a generic type.
+#[derive(Debug, Clone)]
+pub struct ImplBlockNode {
+    pub trait_name: String,
+    pub generic_typing: Option<GenericTypingNode>,
+    pub for_type: Type,
+    pub methods: Vec<FuncDefNode>,
+    pub span: Span,
+}
+
+/// A value turned into a `dyn Trait` where one is expected. Inserted by the
+/// type checker, which knows the type of the value.
+#[derive(Debug, Clone)]
+pub struct DynCoercionNode {
+    pub value: Box<ExpressionNode>,
+    pub value_type: Type,
+    pub trait_name: String,
+    pub span: Span,
+}
+
+impl IndentDisplay for TraitDefNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(f, "{}{}", indent.as_str(), self.name)?;
+        for method in &self.methods {
+            if method.required {
+                writeln!(
+                    f,
+                    "{}Required: {}",
+                    indent.increment(1).as_str(),
+                    method.func.name
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "{}Default: {}",
+                    indent.increment(1).as_str(),
+                    method.func.name
+                )?;
+                method.func.fmt_with_indent(f, indent.increment(2))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl IndentDisplay for ImplBlockNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(
+            f,
+            "{}{} for {}",
+            indent.as_str(),
+            self.trait_name,
+            self.for_type
+        )?;
+        for method in &self.methods {
+            writeln!(f, "{}Method: {}", indent.increment(1).as_str(), method.name)?;
+            method.fmt_with_indent(f, indent.increment(2))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IndentDisplay for DynCoercionNode {
+    fn fmt_with_indent(&self, f: &mut Formatter<'_>, indent: Indent) -> Result {
+        writeln!(
+            f,
+            "{}{} as dyn {}",
+            indent.as_str(),
+            self.value_type,
+            self.trait_name
+        )?;
+        self.value.fmt_with_indent(f, indent.increment(1))
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    String,
+    Void,
+    /// A struct or a generic type parameter, told apart by name resolution.
+    Named(String),
+    /// `dyn Trait`, a pointer to a value of any type implementing the trait
+    /// together with the methods of the implementation.
+    Dyn(String),
+    /// A generic struct or enum with its type arguments, like `Pair<i32>`.
+    Generic(String, Vec<Type>),
+    /// `[i32; 4]`, a fixed number of values. The length is a `Const`, or the
+    /// name of a const parameter inside generic definitions.
+    Array(Box<Type>, Box<Type>),
+    /// Value of a const parameter, like the `4` in `Buf<4>`. Only appears as
+    /// a type argument or the length of an array.
+    Const(i64),
+    /// Type of values coming from C code, which can't be checked.
+    Unknown,
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Type {
+        match name {
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "void" => Type::Void,
+            name => Type::Named(name.to_string()),
+        }
+    }
+
+    /// Name of the type in the generated C code, also used in the names of
+    /// the instances of generic functions. Trait objects are structs named
+    /// after the trait, like `dyn_Show_`, instances of generic types are
+    /// named by `instance_name`, like `Option__11_Pair__3_i32`. Arrays are
+    /// structs wrapping a C array, like `array_i32_4_`. Declared names can't
+    /// end with `_`, so these can't be taken already.
+    pub fn c_name(&self) -> String {
+        match self {
+            Type::Named(name) => c_identifier(name),
+            Type::Dyn(trait_name) => format!("dyn_{}_", c_identifier(trait_name)),
+            Type::Array(element, length) => {
+                format!("array_{}_{}_", element.c_name(), length.c_name())
+            }
+            Type::Const(value) if *value < 0 => format!("_{}", value.unsigned_abs()),
+            Type::Generic(name, args) => instance_name(name, args),
+            other => other.to_string(),
+        }
+    }
+
+    /// Name of the struct, enum or type parameter, without type arguments.
+    pub fn base_name(&self) -> Option<&str> {
+        match self {
+            Type::Named(name) | Type::Generic(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::I8
+                | Type::I16
+                | Type::I32
+                | Type::I64
+                | Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+        )
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Type::U8 | Type::U16 | Type::U32 | Type::U64)
+    }
+
+    /// Smallest and largest value of an integer type.
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        Some(match self {
+            Type::I8 => (i8::MIN.into(), i8::MAX.into()),
+            Type::I16 => (i16::MIN.into(), i16::MAX.into()),
+            Type::I32 => (i32::MIN.into(), i32::MAX.into()),
+            Type::I64 => (i64::MIN.into(), i64::MAX.into()),
+            Type::U8 => (0, u8::MAX.into()),
+            Type::U16 => (0, u16::MAX.into()),
+            Type::U32 => (0, u32::MAX.into()),
+            Type::U64 => (0, u64::MAX.into()),
+            _ => return None,
+        })
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// Whether every value of this type is also a value of `target`, so it
+    /// converts without a cast. Integers widen to integers holding their
+    /// whole range and to floats precise enough to hold every value.
+    pub fn widens_to(&self, target: &Type) -> bool {
+        if let (Some((min, max)), Some((target_min, target_max))) =
+            (self.int_range(), target.int_range())
+        {
+            return target_min <= min && max <= target_max;
+        }
+
+        match target {
+            Type::F32 => matches!(
+                self,
+                Type::I8 | Type::U8 | Type::I16 | Type::U16 | Type::F32
+            ),
+            Type::F64 => {
+                self.widens_to(&Type::F32) || matches!(self, Type::I32 | Type::U32 | Type::F64)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Name of an item in the generated C code. Items of modules are named by
+/// their path, like `math::square`, which becomes `math__square`. A `main`
+/// of the program becomes `main_`, as `main` is the C function running the
+/// top level code.
+pub fn c_identifier(name: &str) -> String {
+    match name {
+        "main" => String::from("main_"),
+        name => name.replace("::", "__"),
+    }
+}
+
+/// C name of the instance of a generic item for some type arguments. The
+/// name of every argument follows `__` and its length, like `Pair__3_i32`
+/// for `Pair<i32>`, which tells nested arguments apart from consecutive ones.
+/// Declared names can't contain `__`, and what follows a `__` in a module
+/// path never starts with a number and `_`, so instances don't collide with
+/// other items either.
+pub fn instance_name(name: &str, type_args: &[Type]) -> String {
+    let mut instance = c_identifier(name);
+    for type_arg in type_args {
+        let arg_name = type_arg.c_name();
+        instance += &format!("__{}_{}", arg_name.len(), arg_name);
+    }
+    instance
+}
+
+/// C name of something generated for declared items, like the function a
+/// method is emitted as. `kind` ends with `_`, and the parts follow `__` and
+/// their length like type arguments in `instance_name`, so the method `push`
+/// of `Vec` becomes `method___3_Vec__4_push`. No declared name or instance
+/// starts like that, and the lengths tell the parts apart.
+pub fn generated_name(kind: &str, parts: &[&str]) -> String {
+    let mut name = String::from(kind);
+    for part in parts {
+        name += &format!("__{}_{}", part.len(), part);
+    }
+    name
+}
+
+/// Why a name can't be declared, if it can't. Names with `__` or a trailing
+/// `_` are left to the generated C code, which builds names like
+/// `math__square` from them, so the names it builds can't be taken already.
+pub fn reserved_name(name: &str) -> Option<&'static str> {
+    if name.contains("__") {
+        Some("contains `__`")
+    } else if name.ends_with('_') {
+        Some("ends with `_`")
+    } else {
+        None
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            Type::I8 => "i8",
+            Type::I16 => "i16",
+            Type::I32 => "i32",
+            Type::I64 => "i64",
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+            Type::U32 => "u32",
+            Type::U64 => "u64",
+            Type::F32 => "f32",
+            Type::F64 => "f64",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Void => "void",
+            Type::Named(name) => name,
+            Type::Dyn(trait_name) => return write!(f, "dyn {}", trait_name),
+            Type::Generic(name, args) => {
+                let args = args.iter().map(Type::to_string).collect::<Vec<_>>();
+                return write!(f, "{}<{}>", name, args.join(", "));
+            }
+            Type::Array(element, length) => return write!(f, "[{}; {}]", element, length),
+            Type::Const(value) => return write!(f, "{}", value),
+            Type::Unknown => "{unknown}",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,10 +1,12 @@
 use super::expression::Indent;
 use super::expression::IndentDisplay;
+use crate::diagnostics::Span;
 use std::fmt::{Formatter, Result};
 
 #[derive(Debug, Clone)]
 pub struct VarAccessNode {
     pub name: String,
+    pub span: Span,
 }
 
 impl IndentDisplay for VarAccessNode {
@@ -1,91 +1,264 @@
-use std::collections::HashMap;
-
 use crate::ast_nodes::{
+    array::{ArrayLitNode, ArrayRepeatNode, IndexNode},
     block::BlockNode,
+    control_flow::{ForExprNode, IfExprNode, WhileExprNode},
+    enum_def::{EnumDefNode, EnumInitNode},
     expression::{
-        AddExprNode, AddOp, CImportNode, ExpressionKind, ExpressionNode, MulExprNode, MulOp,
-        PrimaryKind, PrimaryNode, ReturnExprNode,
+        BinaryExprNode, BinaryOp, CImportNode, ExpressionKind, ExpressionNode, ReturnExprNode,
+        UnaryExprNode,
     },
-    func_call::FuncCallNode,
+    func_call::{FuncCallNode, MethodCallNode},
     func_def::{FuncDefNode, FuncParam},
+    match_expr::{MatchArmNode, MatchExprNode, PatternKind, PatternNode},
     program::ProgramNode,
     struct_def::{StructDefNode, StructFieldAccessNode},
+    term::{AssignmentNode, PlaceSegment, StructInitNode, VarDeclNode},
+    trait_def::{DynCoercionNode, ImplBlockNode, TraitDefNode},
+    types::{Type, c_identifier, generated_name},
 };
+use crate::diagnostics::{Diagnostic, SourceMap, Span};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tracing::trace;
 
-struct GenericFuncDeclaration {
-    node: FuncDefNode,
-    generic_params: Vec<String>,
+/// What the generated C is compiled to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// A program running the top-level statements in `main`.
+    #[default]
+    Executable,
+    /// A library of the functions, with no `main` and so no top-level
+    /// statements.
+    Library,
 }
 
-struct Context {
-    _scope_stack: Vec<String>,
-    _current_scope: u32,
+#[derive(Default)]
+struct Context<'a> {
+    /// Prototypes of all functions, which may call each other in any order.
+    function_prototypes: Vec<String>,
     function_declarations: Vec<String>,
-    pub generic_function_declarations: HashMap<String, GenericFuncDeclaration>,
     pub main_function_content: String,
-    pub struct_definitions: Vec<String>,
+    pub struct_definitions: Vec<TypeDefinition>,
     pub imports: Vec<String>,
-    pub generic_function_implementations: HashMap<Vec<String>, String>,
+    /// Types `**` is used with, each needs its own power helper.
+    pub power_types: Vec<Type>,
+    /// C statements that must run before the statement being generated, like
+    /// the temporary an `if` used as a value is lowered to.
+    pub hoisted_statements: String,
+    pub temporary_count: usize,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Sources the program was parsed from, if the C is annotated with the
+    /// Fusion lines it was generated for.
+    pub sources: Option<&'a SourceMap>,
 }
 
-impl Context {
+impl Context<'_> {
     pub fn add_function_declaration(&mut self, code: String) {
         self.function_declarations.push(code);
     }
+
+    pub fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// C name of a type. Arrays are wrapped in a struct, so they can be
+    /// copied, passed and returned like any other value, which is defined on
+    /// first use.
+    pub fn type_name(&mut self, value_type: &Type) -> String {
+        let name = value_type.c_name();
+        if let Type::Array(element, length) = value_type
+            && !self.struct_definitions.iter().any(|d| d.name == name)
+        {
+            let element_name = self.type_name(element);
+            self.struct_definitions.push(TypeDefinition {
+                code: format!(
+                    "typedef struct {{ {} items[{}]; }} {};",
+                    element_name, length, name
+                ),
+                name: name.clone(),
+                dependencies: vec![element_name],
+            });
+        }
+        name
+    }
+
+    /// `#line` directive attributing the C that follows to the Fusion line
+    /// `span` starts on, so the C compiler reports its errors there.
+    pub fn line_directive(&self, span: Span) -> String {
+        let Some(sources) = self.sources else {
+            return String::new();
+        };
+        let file = sources.file(span.start);
+        let (line, _) = file.line_col(span.start);
+        let name = file.name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\n#line {} \"{}\"\n", line, name)
+    }
+
+    /// Fresh name for a compiler generated C variable. It ends with `_`, so
+    /// no declared variable has it.
+    pub fn temporary(&mut self, purpose: &str) -> String {
+        self.temporary_count += 1;
+        format!("fusion_{}_{}_", purpose, self.temporary_count)
+    }
 }
 
-impl Default for Context {
-    fn default() -> Self {
-        Self {
-            _scope_stack: vec![String::from("Global")],
-            function_declarations: vec![],
-            struct_definitions: vec![],
-            generic_function_declarations: HashMap::new(),
-            generic_function_implementations: HashMap::new(),
-            _current_scope: 0,
-            main_function_content: String::from(""),
-            imports: vec![],
+/// C definition of a struct, enum or trait object type.
+struct TypeDefinition {
+    name: String,
+    /// Types that have to be defined first, like the types of the fields of
+    /// a struct.
+    dependencies: Vec<String>,
+    code: String,
+}
+
+/// Orders the type definitions so that every type comes after the types it
+/// depends on, keeping the order of the source otherwise. Instances of
+/// generic types are created wherever they are first used, so they may
+/// depend on types defined later in the source.
+fn ordered_type_definitions(definitions: &[TypeDefinition]) -> String {
+    fn visit<'a>(
+        definition: &'a TypeDefinition,
+        by_name: &HashMap<&str, &'a TypeDefinition>,
+        visited: &mut HashSet<&'a str>,
+        code: &mut String,
+    ) {
+        if !visited.insert(&definition.name) {
+            return;
         }
+        for dependency in &definition.dependencies {
+            if let Some(dependency) = by_name.get(dependency.as_str()) {
+                visit(dependency, by_name, visited, code);
+            }
+        }
+        *code += &definition.code;
+    }
+
+    let by_name = definitions.iter().map(|d| (d.name.as_str(), d)).collect();
+    let mut visited = HashSet::new();
+    let mut code = String::new();
+    for definition in definitions {
+        visit(definition, &by_name, &mut visited, &mut code);
     }
+    code
 }
 
 struct CodeGenResult {
     code: String,
 }
 
-pub fn gen_code(program: ProgramNode) -> String {
-    let mut ctx = Context::default();
+/// Generates the C code of a program. Given the `sources` of the program,
+/// the code is annotated with `#line` directives pointing at the Fusion
+/// source.
+pub fn gen_code(
+    program: ProgramNode,
+    target: Target,
+    sources: Option<&SourceMap>,
+) -> Result<String, Vec<Diagnostic>> {
+    let mut ctx = Context {
+        sources,
+        ..Context::default()
+    };
+    if target == Target::Library {
+        check_library_items(&program, &mut ctx);
+    }
     walk_program(program, &mut ctx);
-    let main_function = format!("int main(){{{}return 0;}}", ctx.main_function_content);
 
-    let default_type_defs = ["typedef char* string;"];
+    if !ctx.diagnostics.is_empty() {
+        return Err(ctx.diagnostics);
+    }
 
-    format!(
-        "{}{}{}{}{}{}",
+    let main_function = match target {
+        Target::Executable => format!("int main(){{{}return 0;}}", ctx.main_function_content),
+        Target::Library => String::new(),
+    };
+
+    let default_type_defs = [
+        "#include <stdint.h>\n#include <stdbool.h>\n#include <math.h>\n#include <string.h>\n",
+        "typedef int8_t i8;typedef int16_t i16;typedef int32_t i32;typedef int64_t i64;",
+        "typedef uint8_t u8;typedef uint16_t u16;typedef uint32_t u32;typedef uint64_t u64;",
+        "typedef float f32;typedef double f64;",
+        "typedef char* string;\n",
+    ];
+
+    let power_helpers = ctx.power_types.iter().map(power_helper);
+
+    Ok(format!(
+        "{}{}{}{}{}{}{}",
         default_type_defs.join(""),
-        ctx.struct_definitions.join(""),
-        ctx.imports.join(";"),
+        ctx.imports.join(""),
+        power_helpers.collect::<Vec<_>>().join(""),
+        ordered_type_definitions(&ctx.struct_definitions),
+        ctx.function_prototypes.join(""),
         ctx.function_declarations.join(";"),
-        ctx.generic_function_implementations
-            .values()
-            .cloned()
-            .collect::<Vec<_>>()
-            .join(";"),
         main_function
-    )
+    ))
+}
+
+/// Reports the top-level statements of a library, which has no `main` to
+/// run them in.
+fn check_library_items(program: &ProgramNode, ctx: &mut Context) {
+    for expr in &program.expressions {
+        if !matches!(
+            expr.kind,
+            ExpressionKind::FuncDef(_)
+                | ExpressionKind::StructDef(_)
+                | ExpressionKind::EnumDef(_)
+                | ExpressionKind::TraitDef(_)
+                | ExpressionKind::ImplBlock(_)
+                | ExpressionKind::CImport(_)
+        ) {
+            ctx.error(
+                Diagnostic::error("expected an item, found a statement")
+                    .with_span(expr.span)
+                    .with_label("not allowed at the top level of a library")
+                    .with_note("a library has no `main` function to run statements in"),
+            );
+        }
+    }
 }
 
 fn walk_program(program: ProgramNode, ctx: &mut Context) {
     for statement in &program.expressions {
-        let code = walk_expression(statement.clone(), ctx);
+        let code = walk_statement(statement.clone(), ctx);
+
+        ctx.main_function_content += code.as_str();
+    }
+}
+
+/// Generates an expression used as a statement, preceded by the statements
+/// hoisted out of it.
+fn walk_statement(expr: ExpressionNode, ctx: &mut Context) -> String {
+    let line_directive = ctx.line_directive(expr.span);
+    let code = with_hoisted(ctx, |ctx| match expr.kind {
+        ExpressionKind::If(node) => walk_if_expr(node, None, ctx),
+        ExpressionKind::Match(node) => walk_match_expr(node, None, ctx),
+        ExpressionKind::While(node) => walk_while_expr(node, ctx),
+        ExpressionKind::For(node) => walk_for_expr(node, ctx),
+        kind => walk_expression(ExpressionNode { kind, ..expr }, ctx) + ";",
+    });
 
-        ctx.main_function_content += (code.as_str().to_owned() + ";").as_str();
+    // Items generate no code here, only their definitions are annotated.
+    if code.is_empty() || code == ";" {
+        code
+    } else {
+        line_directive + &code
     }
 }
 
+/// Runs `walk` and puts the statements it hoisted in front of its code.
+fn with_hoisted(ctx: &mut Context, walk: impl FnOnce(&mut Context) -> String) -> String {
+    let outer = std::mem::take(&mut ctx.hoisted_statements);
+    let code = walk(ctx);
+    let hoisted = std::mem::replace(&mut ctx.hoisted_statements, outer);
+
+    hoisted + &code
+}
+
 fn walk_expression(expr: ExpressionNode, ctx: &mut Context) -> String {
     match expr.kind {
-        ExpressionKind::AddExpr(node) => walk_add_expr(node, ctx),
+        ExpressionKind::BinaryExpr(node) => walk_binary_expr(node, ctx),
+        ExpressionKind::UnaryExpr(node) => walk_unary_expr(node, ctx),
 
         ExpressionKind::FuncDef(node) => {
             walk_func_def(node, ctx);
@@ -97,155 +270,1044 @@ fn walk_expression(expr: ExpressionNode, ctx: &mut Context) -> String {
             walk_c_import(node, ctx);
             String::from("")
         }
-        ExpressionKind::IntLit(val) => val.to_string(),
+        ExpressionKind::IntLit(val) => int_literal(val),
+        ExpressionKind::FloatLit(val) => format!("{:?}", val),
+        ExpressionKind::VarAccess(node) => place(&node.name, vec![], ctx),
         ExpressionKind::FuncCall(node) => walk_func_call(node, ctx),
         ExpressionKind::StrLit(str) => walk_str_lit(str, ctx),
         ExpressionKind::StructDef(node) => {
             walk_struct_def(node, ctx);
             String::from("")
         }
+        ExpressionKind::StructInit(node) => walk_struct_init(node, ctx),
+        ExpressionKind::ArrayLit(node) => walk_array_lit(node, ctx),
+        ExpressionKind::ArrayRepeat(node) => walk_array_repeat(node, ctx),
+        ExpressionKind::Index(node) => walk_index(node, ctx),
         ExpressionKind::StructFieldAccess(node) => walk_struct_field_access(node, ctx),
-        _ => todo!("{:?}", expr.kind),
+        ExpressionKind::MethodCall(node) => walk_method_call(node, ctx),
+        ExpressionKind::EnumDef(node) => {
+            walk_enum_def(node, ctx);
+            String::from("")
+        }
+        ExpressionKind::EnumInit(node) => walk_enum_init(node, ctx),
+        // Default methods of traits are generated as part of every impl
+        // using them.
+        ExpressionKind::TraitDef(node) => {
+            walk_trait_def(node, ctx);
+            String::from("")
+        }
+        ExpressionKind::ImplBlock(node) => {
+            walk_impl_block(node, ctx);
+            String::from("")
+        }
+        ExpressionKind::DynCoercion(node) => walk_dyn_coercion(node, ctx),
+        ExpressionKind::Module(_) | ExpressionKind::Use(_) => {
+            unreachable!("modules are flattened before name resolution")
+        }
+        ExpressionKind::VarDecl(node) => walk_var_decl(node, ctx),
+        ExpressionKind::If(node) => walk_if_value(node, ctx),
+        ExpressionKind::Match(node) => walk_match_value(node, ctx),
+        ExpressionKind::Break => String::from("break"),
+        ExpressionKind::Continue => String::from("continue"),
+        // Loops have no value, they are only generated as statements.
+        ExpressionKind::While(node) => {
+            let code = walk_while_expr(node, ctx);
+            ctx.hoisted_statements += &code;
+            String::from("")
+        }
+        ExpressionKind::For(node) => {
+            let code = walk_for_expr(node, ctx);
+            ctx.hoisted_statements += &code;
+            String::from("")
+        }
+        ExpressionKind::Assignment(node) => walk_assignment(node, ctx),
     }
 }
 
-fn walk_struct_field_access(node: StructFieldAccessNode, _ctx: &mut Context) -> String {
-    format!("{}.{}", node.struct_name, node.field_name)
+/// Lowers an `if` used as a value to a temporary assigned in both branches.
+fn walk_if_value(node: IfExprNode, ctx: &mut Context) -> String {
+    let value_type = node
+        .value_type
+        .clone()
+        .expect("the type checker infers the type of every `if`");
+
+    if value_type == Type::Void {
+        let code = walk_if_expr(node, None, ctx);
+        ctx.hoisted_statements += &code;
+        return String::from("");
+    }
+
+    let temporary = ctx.temporary("if");
+    let code = walk_if_expr(node, Some(&temporary), ctx);
+    let type_name = ctx.type_name(&value_type);
+    ctx.hoisted_statements += &format!("{} {}; {}", type_name, temporary, code);
+
+    temporary
 }
 
-fn walk_struct_def(node: StructDefNode, ctx: &mut Context) {
-    println!("SAAAAAA   {:?}", node);
-    ctx.struct_definitions.push(format!(
-        "struct _{name} {{ {} }}; typedef struct _{name} {name};",
-        node.fields
-            .into_iter()
-            .map(|field| format!("{} {};", field.type_name, field.name))
-            .collect::<Vec<String>>()
-            .join(""),
-        name = node.name,
-    ));
+/// Generates an `if` statement. With a `target` the value of each branch is
+/// assigned to it.
+fn walk_if_expr(node: IfExprNode, target: Option<&str>, ctx: &mut Context) -> String {
+    let condition = walk_expression(*node.condition, ctx);
+    let then_code = walk_branch(node.then_block, target, ctx);
+
+    match node.else_block {
+        Some(else_block) => format!(
+            "if ({}) {} else {}",
+            condition,
+            then_code,
+            walk_branch(else_block, target, ctx)
+        ),
+        None => format!("if ({}) {}", condition, then_code),
+    }
 }
 
-fn walk_str_lit(str: String, _ctx: &mut Context) -> String {
-    str
+fn walk_branch(block: BlockNode, target: Option<&str>, ctx: &mut Context) -> String {
+    let mut expressions = block.expressions;
+    let value = target.and_then(|target| Some((target, expressions.pop()?)));
+
+    let mut code: String = expressions
+        .into_iter()
+        .map(|expr| walk_statement(expr, ctx))
+        .collect();
+
+    if let Some((target, value)) = value {
+        code += &with_hoisted(ctx, |ctx| {
+            format!("{} = {};", target, walk_expression(value, ctx))
+        });
+    }
+
+    format!("{{ {} }}", code)
 }
 
-fn walk_c_import(node: CImportNode, ctx: &mut Context) {
-    ctx.imports
-        .push(format!("#include {}\n", node.module.as_str()));
+/// Lowers a `match` used as a value to a temporary assigned in every arm.
+fn walk_match_value(node: MatchExprNode, ctx: &mut Context) -> String {
+    let value_type = node
+        .value_type
+        .clone()
+        .expect("the type checker infers the type of every `match`");
+
+    if value_type == Type::Void {
+        let code = walk_match_expr(node, None, ctx);
+        ctx.hoisted_statements += &code;
+        return String::from("");
+    }
+
+    let temporary = ctx.temporary("match_value");
+    let code = walk_match_expr(node, Some(&temporary), ctx);
+    let type_name = ctx.type_name(&value_type);
+    ctx.hoisted_statements += &format!("{} {}; {}", type_name, temporary, code);
+
+    temporary
 }
 
-fn walk_return_expr(ret: ReturnExprNode, ctx: &mut Context) -> String {
-    format!("return {};", walk_expression(*ret.expression, ctx))
+/// Generates a `match` statement. The matched value is evaluated once into a
+/// temporary. Matches on enum tags or integers without guards or nested
+/// patterns become a `switch`, anything else a chain of `if`s jumping past
+/// the remaining arms once one matched.
+fn walk_match_expr(node: MatchExprNode, target: Option<&str>, ctx: &mut Context) -> String {
+    let subject_type = node
+        .subject_type
+        .clone()
+        .expect("the type checker infers the type of every matched value");
+    let subject = walk_expression(*node.subject, ctx);
+    let temporary = ctx.temporary("match");
+    let declaration = format!(
+        "{} {} = {};",
+        ctx.type_name(&subject_type),
+        temporary,
+        subject
+    );
+
+    let code = if is_switchable(&node.arms, &subject_type) {
+        walk_match_switch(node.arms, &temporary, target, ctx)
+    } else {
+        walk_match_chain(node.arms, &temporary, target, ctx)
+    };
+
+    format!("{{ {} {} }}", declaration, code)
 }
 
-fn walk_add_expr(add: AddExprNode, ctx: &mut Context) -> String {
-    let mut left_code = walk_mul_expr_node(add.left, ctx);
+/// Whether the arms can be generated as cases of a C `switch`. `break` in an
+/// arm would leave the `switch` instead of the loop around the `match`.
+fn is_switchable(arms: &[MatchArmNode], subject_type: &Type) -> bool {
+    arms.iter().all(|arm| {
+        let flat = match &arm.pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding { .. } => true,
+            PatternKind::Literal(value) => {
+                subject_type.is_integer() && literal_case(value).is_some()
+            }
+            PatternKind::Variant { fields, .. } => {
+                fields.iter().all(|field| field.kind.is_irrefutable())
+            }
+            PatternKind::Comparison { .. } => false,
+        };
 
-    for addent in add.addent {
-        left_code += match addent.op {
-            AddOp::Add => format!("+{}", walk_mul_expr_node(addent.value, ctx)),
-            AddOp::Subtract => format!("-{}", walk_mul_expr_node(addent.value, ctx)),
-        }
-        .as_str();
+        flat && arm.guard.is_none() && !contains_break(&arm.body.expressions)
+    })
+}
+
+/// Integer literal usable as a `case` label.
+fn literal_case(value: &ExpressionNode) -> Option<String> {
+    match &value.kind {
+        ExpressionKind::IntLit(value) => Some(int_literal(*value)),
+        ExpressionKind::UnaryExpr(node) => literal_case(&node.operand).map(|v| format!("-{}", v)),
+        _ => None,
     }
+}
 
-    left_code
+/// Whether a `break` in the expressions leaves the innermost enclosing loop.
+fn contains_break(expressions: &[ExpressionNode]) -> bool {
+    expressions.iter().any(|expr| match &expr.kind {
+        ExpressionKind::Break => true,
+        ExpressionKind::If(node) => {
+            contains_break(&node.then_block.expressions)
+                || node
+                    .else_block
+                    .as_ref()
+                    .is_some_and(|block| contains_break(&block.expressions))
+        }
+        ExpressionKind::Match(node) => node
+            .arms
+            .iter()
+            .any(|arm| contains_break(&arm.body.expressions)),
+        _ => false,
+    })
 }
 
-fn walk_mul_expr_node(mul: MulExprNode, ctx: &mut Context) -> String {
-    let mut left_code = walk_primary(mul.left, ctx);
+fn walk_match_switch(
+    arms: Vec<MatchArmNode>,
+    subject: &str,
+    target: Option<&str>,
+    ctx: &mut Context,
+) -> String {
+    let is_enum = arms
+        .iter()
+        .any(|arm| matches!(arm.pattern.kind, PatternKind::Variant { .. }));
+
+    let cases = arms
+        .into_iter()
+        .map(|arm| {
+            let label = match &arm.pattern.kind {
+                PatternKind::Literal(value) => {
+                    format!("case {}:", literal_case(value).expect("checked above"))
+                }
+                PatternKind::Variant {
+                    enum_type, variant, ..
+                } => format!("case {}:", enum_tag(&variant_enum(enum_type), variant)),
+                _ => String::from("default:"),
+            };
+            let bindings = pattern_bindings(&arm.pattern, subject);
+
+            format!(
+                "{} {{ {} {} break; }}",
+                label,
+                bindings,
+                walk_branch(arm.body, target, ctx)
+            )
+        })
+        .collect::<String>();
+
+    let subject = if is_enum {
+        format!("{}.tag", subject)
+    } else {
+        subject.to_string()
+    };
+    format!("switch ({}) {{ {} }}", subject, cases)
+}
+
+fn walk_match_chain(
+    arms: Vec<MatchArmNode>,
+    subject: &str,
+    target: Option<&str>,
+    ctx: &mut Context,
+) -> String {
+    let end = ctx.temporary("match_end");
+
+    let mut code = arms
+        .into_iter()
+        .map(|arm| {
+            let conditions = pattern_conditions(&arm.pattern, subject, ctx);
+            let condition = if conditions.is_empty() {
+                String::from("1")
+            } else {
+                conditions.join(" && ")
+            };
+            let bindings = pattern_bindings(&arm.pattern, subject);
+
+            let body = match arm.guard {
+                // Bindings are visible in the guard, so it is checked after
+                // they are declared.
+                Some(guard) => with_hoisted(ctx, |ctx| {
+                    let guard = walk_expression(guard, ctx);
+                    let branch = walk_branch(arm.body, target, ctx);
+                    format!("if ({}) {{ {} goto {}; }}", guard, branch, end)
+                }),
+                None => format!("{} goto {};", walk_branch(arm.body, target, ctx), end),
+            };
+
+            format!("if ({}) {{ {} {} }}", condition, bindings, body)
+        })
+        .collect::<String>();
 
-    for factor in mul.factor {
-        left_code += match factor.op {
-            MulOp::Multiply => format!("*{}", walk_primary(factor.value, ctx)),
-            MulOp::Divide => format!("/{}", walk_primary(factor.value, ctx)),
+    code += &format!("{}:;", end);
+    code
+}
+
+/// C conditions that all hold when `pattern` matches the value at `path`.
+fn pattern_conditions(pattern: &PatternNode, path: &str, ctx: &mut Context) -> Vec<String> {
+    match &pattern.kind {
+        PatternKind::Wildcard | PatternKind::Binding { .. } => vec![],
+        PatternKind::Literal(value) => {
+            let is_string = matches!(value.kind, ExpressionKind::StrLit(_));
+            let value = walk_expression(*value.clone(), ctx);
+            if is_string {
+                vec![format!("strcmp({}, {}) == 0", path, value)]
+            } else {
+                vec![format!("{} == {}", path, value)]
+            }
+        }
+        PatternKind::Comparison { op, value } => {
+            vec![format!(
+                "({} {} {})",
+                path,
+                op,
+                walk_expression(*value.clone(), ctx)
+            )]
+        }
+        PatternKind::Variant {
+            enum_type,
+            variant,
+            fields,
+            ..
+        } => {
+            let enum_name = variant_enum(enum_type);
+            let mut conditions = vec![format!("{}.tag == {}", path, enum_tag(&enum_name, variant))];
+            for (i, field) in fields.iter().enumerate() {
+                let field_path = format!("{}.data.{}.{}", path, variant, variant_field_name(i));
+                conditions.extend(pattern_conditions(field, &field_path, ctx));
+            }
+            conditions
         }
-        .as_str()
     }
+}
 
-    left_code
+/// C name of the enum a variant pattern matches, the instance in case of a
+/// generic enum.
+fn variant_enum(enum_type: &Option<Type>) -> String {
+    enum_type
+        .as_ref()
+        .expect("the type checker fills in the enum of every variant")
+        .c_name()
 }
 
-fn walk_primary(primary: PrimaryNode, _ctx: &mut Context) -> String {
-    match primary.kind {
-        PrimaryKind::IntLit(val) => val.to_string(),
-        PrimaryKind::VarAccess(val) => val.name,
-        PrimaryKind::FloatLit(val) => val.to_string(),
+/// C declarations of the names `pattern` binds in the value at `path`.
+fn pattern_bindings(pattern: &PatternNode, path: &str) -> String {
+    match &pattern.kind {
+        PatternKind::Binding { name, value_type } => format!(
+            "const {} {} = {};",
+            value_type
+                .as_ref()
+                .expect("the type checker infers the type of every binding")
+                .c_name(),
+            name,
+            path
+        ),
+        PatternKind::Variant {
+            variant, fields, ..
+        } => fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let field_path = format!("{}.data.{}.{}", path, variant, variant_field_name(i));
+                pattern_bindings(field, &field_path)
+            })
+            .collect(),
+        _ => String::new(),
+    }
+}
 
-        _ => todo!(),
+fn walk_while_expr(node: WhileExprNode, ctx: &mut Context) -> String {
+    let outer = std::mem::take(&mut ctx.hoisted_statements);
+    let condition = walk_expression(*node.condition, ctx);
+    let condition_hoisted = std::mem::replace(&mut ctx.hoisted_statements, outer);
+    let body = walk_block(node.body, ctx).code;
+
+    // Statements hoisted out of the condition have to run before every check.
+    if condition_hoisted.is_empty() {
+        format!("while ({}) {{ {} }}", condition, body)
+    } else {
+        format!(
+            "while (1) {{ {} if (!({})) break; {} }}",
+            condition_hoisted, condition, body
+        )
     }
 }
 
-fn walk_func_call(func_call: FuncCallNode, ctx: &mut Context) -> String {
-    println!("FUNCCALL {:?}", func_call);
-    let params_code = func_call
-        .clone()
-        .params
+fn walk_for_expr(node: ForExprNode, ctx: &mut Context) -> String {
+    let var_type = node
+        .var_type
+        .expect("the type checker infers the type of every loop variable");
+    let start = walk_expression(*node.start, ctx);
+    let end = walk_expression(*node.end, ctx);
+    let end_temporary = ctx.temporary("end");
+
+    format!(
+        "for ({t} {var} = {}, {end_var} = {}; {var} < {end_var}; {var}++) {{ {} }}",
+        start,
+        end,
+        walk_block(node.body, ctx).code,
+        t = ctx.type_name(&var_type),
+        var = node.variable,
+        end_var = end_temporary,
+    )
+}
+
+fn walk_var_decl(node: VarDeclNode, ctx: &mut Context) -> String {
+    let var_type = node
+        .var_type
+        .expect("the type checker infers the type of every variable");
+
+    format!(
+        "{}{} {} = {}",
+        if node.mutable { "" } else { "const " },
+        ctx.type_name(&var_type),
+        node.name,
+        walk_expression(*node.value, ctx)
+    )
+}
+
+fn walk_assignment(node: AssignmentNode, ctx: &mut Context) -> String {
+    format!(
+        "{} = {}",
+        place(&node.name, node.path, ctx),
+        walk_expression(*node.value, ctx)
+    )
+}
+
+/// C lvalue of a variable or a field or array element in it. `self` is a
+/// pointer to the value a method was called on.
+fn place(name: &str, path: Vec<PlaceSegment>, ctx: &mut Context) -> String {
+    let mut code = match name {
+        "self" => String::from("(*self)"),
+        name => name.to_string(),
+    };
+    for segment in path {
+        code += &match segment {
+            PlaceSegment::Field(field) => format!(".{}", field),
+            PlaceSegment::Index(index) => format!(".items[{}]", walk_expression(*index, ctx)),
+        };
+    }
+    code
+}
+
+/// Emits an array literal as a compound literal of the struct wrapping it.
+fn walk_array_lit(node: ArrayLitNode, ctx: &mut Context) -> String {
+    let array_type = node
+        .array_type
+        .expect("the type checker infers the type of every array");
+    let elements = node
+        .elements
         .into_iter()
-        .map(|param| walk_expression(param, ctx))
-        .collect::<Vec<String>>()
+        .map(|element| walk_expression(element, ctx))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("(({}){{ {{ {} }} }})", ctx.type_name(&array_type), elements)
+}
+
+/// Lowers `[value; N]` to a temporary filled in by a loop. The value is
+/// evaluated once, like in a variable declaration.
+fn walk_array_repeat(node: ArrayRepeatNode, ctx: &mut Context) -> String {
+    let array_type = node
+        .array_type
+        .expect("the type checker infers the type of every array");
+    let Type::Array(element_type, _) = &array_type else {
+        unreachable!("the type of an array is an array type");
+    };
+
+    let value = walk_expression(*node.value, ctx);
+    let value_temporary = ctx.temporary("repeat_value");
+    let temporary = ctx.temporary("array");
+    let index = ctx.temporary("index");
+    let element_name = ctx.type_name(element_type);
+    let array_name = ctx.type_name(&array_type);
+    ctx.hoisted_statements += &format!(
+        "{} {} = {}; {} {}; for (i32 {i} = 0; {i} < {}; {i}++) {}.items[{i}] = {};",
+        element_name,
+        value_temporary,
+        value,
+        array_name,
+        temporary,
+        node.length,
+        temporary,
+        value_temporary,
+        i = index,
+    );
+    temporary
+}
+
+fn walk_index(node: IndexNode, ctx: &mut Context) -> String {
+    let object = walk_expression(*node.object, ctx);
+    format!("{}.items[{}]", object, walk_expression(*node.index, ctx))
+}
+
+/// Emits a struct literal as a C compound literal with designated fields.
+fn walk_struct_init(node: StructInitNode, ctx: &mut Context) -> String {
+    let fields = node
+        .fields
+        .into_iter()
+        .map(|field| format!(".{} = {}", field.name, walk_expression(field.value, ctx)))
+        .collect::<Vec<_>>()
         .join(", ");
 
-    let mut new_name = func_call.name.clone();
+    format!("(({}){{ {} }})", c_identifier(&node.struct_name), fields)
+}
+
+fn walk_struct_field_access(node: StructFieldAccessNode, ctx: &mut Context) -> String {
+    format!("{}.{}", walk_expression(*node.object, ctx), node.field_name)
+}
 
-    if let Some(generic_func) = ctx.generic_function_declarations.get(&func_call.name) {
-        let node = generic_func.node.clone();
+fn walk_method_call(node: MethodCallNode, ctx: &mut Context) -> String {
+    let receiver_type = node
+        .receiver_type
+        .expect("the type checker infers the type of every method receiver");
 
-        println!(
-            "dbg_e {:?} {:?}",
-            generic_func.generic_params, func_call.generic_params
+    // Trait objects call the method through the vtable of the implementation.
+    if let Type::Dyn(_) = receiver_type {
+        let object = trait_object(*node.object, &receiver_type, ctx);
+        let mut args = vec![format!("{}.data", object)];
+        args.extend(node.args.into_iter().map(|arg| walk_expression(arg, ctx)));
+        return format!("{}.vtable->{}({})", object, node.method, args.join(", "));
+    }
+
+    let type_name = receiver_type.c_name();
+    if node.trait_name.is_none() && !matches!(receiver_type, Type::Named(_) | Type::Generic(..)) {
+        let object = walk_expression(*node.object, ctx);
+        return match (&receiver_type, node.method.as_str()) {
+            (Type::String, "len") => format!("((u64)strlen({}))", object),
+            // Receivers of generic types are only known per instance.
+            _ => {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "no method `{}` on type `{}`",
+                        node.method, receiver_type
+                    ))
+                    .with_span(node.span)
+                    .with_label("unknown method"),
+                );
+                String::new()
+            }
+        };
+    }
+
+    let mut args = vec![];
+    if !node.static_call {
+        args.push(receiver_pointer(*node.object, &receiver_type, ctx));
+    }
+    args.extend(node.args.into_iter().map(|arg| walk_expression(arg, ctx)));
+
+    format!(
+        "{}({})",
+        method_name(&type_name, node.trait_name.as_deref(), &node.method),
+        args.join(", ")
+    )
+}
+
+/// Pointer to the value a method is called on. Values that aren't stored in
+/// a variable are stored in a temporary first.
+fn receiver_pointer(object: ExpressionNode, receiver_type: &Type, ctx: &mut Context) -> String {
+    if let ExpressionKind::VarAccess(node) = &object.kind
+        && node.name == "self"
+    {
+        return String::from("self");
+    }
+
+    if is_place(&object) {
+        return format!("&{}", walk_expression(object, ctx));
+    }
+
+    let value = walk_expression(object, ctx);
+    let temporary = ctx.temporary("receiver");
+    let type_name = ctx.type_name(receiver_type);
+    ctx.hoisted_statements += &format!("{} {} = {};", type_name, temporary, value);
+    format!("&{}", temporary)
+}
+
+/// Whether an expression names a variable or a field of one, which can be
+/// evaluated more than once and has an address.
+fn is_place(mut expr: &ExpressionNode) -> bool {
+    loop {
+        match &expr.kind {
+            ExpressionKind::VarAccess(_) => return true,
+            ExpressionKind::StructFieldAccess(node) => expr = &node.object,
+            _ => return false,
+        }
+    }
+}
+
+/// Trait object a method is called on. Both its data and its vtable are
+/// used, so anything but a variable is stored in a temporary first.
+fn trait_object(object: ExpressionNode, object_type: &Type, ctx: &mut Context) -> String {
+    if is_place(&object) {
+        return walk_expression(object, ctx);
+    }
+
+    let value = walk_expression(object, ctx);
+    let temporary = ctx.temporary("receiver");
+    let type_name = ctx.type_name(object_type);
+    ctx.hoisted_statements += &format!("{} {} = {};", type_name, temporary, value);
+    temporary
+}
+
+/// C name of the function a method is emitted as. Methods from impls are
+/// named after the trait too, so traits may use the same method names.
+fn method_name(type_name: &str, trait_name: Option<&str>, method: &str) -> String {
+    match trait_name {
+        Some(trait_name) => {
+            generated_name("method_", &[type_name, &c_identifier(trait_name), method])
+        }
+        None => generated_name("method_", &[type_name, method]),
+    }
+}
+
+/// Emits a method as a C function taking a pointer to the value it is called
+/// on as `self`, which is `const` unless the method takes `&mut self`.
+fn walk_method(node: FuncDefNode, type_name: &str, trait_name: Option<&str>, ctx: &mut Context) {
+    let return_type = node
+        .return_type
+        .expect("the type checker infers the return type of every method");
+
+    let mut params = vec![];
+    if let Some(self_param) = node.self_param {
+        let qualifier = if self_param.mutable { "" } else { "const " };
+        params.push(format!("{}{}* self", qualifier, type_name));
+    }
+    if !node.params.is_empty() {
+        params.push(walk_func_def_params(node.params, ctx));
+    }
+
+    let line_directive = ctx.line_directive(node.span);
+    let prototype = format!(
+        "{} {}({})",
+        ctx.type_name(&return_type),
+        method_name(type_name, trait_name, &node.name),
+        params.join(", ")
+    );
+    ctx.function_prototypes
+        .push(format!("{}{};", line_directive, prototype));
+
+    let code = format!(
+        "{}{} {{ {} }}",
+        line_directive,
+        prototype,
+        walk_block(node.body, ctx).code
+    );
+    ctx.add_function_declaration(code);
+}
+
+/// C name of the struct of function pointers implementations of a trait
+/// are called through.
+fn vtable_type(trait_name: &str) -> String {
+    generated_name("vtable_", &[&c_identifier(trait_name)])
+}
+
+/// C name of the vtable of one implementation of a trait.
+fn vtable_name(type_name: &str, trait_name: &str) -> String {
+    generated_name("vtable_", &[type_name, &c_identifier(trait_name)])
+}
+
+/// C type of a pointer to a method, called `name`, with `self` erased to a
+/// `void` pointer so all implementations fit into the vtable.
+fn method_pointer(node: &FuncDefNode, name: &str) -> String {
+    let mut params = vec![];
+    if let Some(self_param) = node.self_param {
+        params.push(
+            if self_param.mutable {
+                "void*"
+            } else {
+                "const void*"
+            }
+            .to_string(),
         );
+    }
+    params.extend(node.params.iter().map(|p| p.param_type.c_name()));
+
+    let return_type = node.return_type.clone().unwrap_or(Type::Void);
+    format!(
+        "{} (*{})({})",
+        return_type.c_name(),
+        name,
+        params.join(", ")
+    )
+}
+
+/// Emits the vtable type of a trait, holding the methods taking `self`, and
+/// the `dyn` struct pairing a pointer to a value with its vtable.
+fn walk_trait_def(node: TraitDefNode, ctx: &mut Context) {
+    let entries: String = node
+        .methods
+        .iter()
+        .filter(|m| m.func.self_param.is_some())
+        .map(|m| format!("{};", method_pointer(&m.func, &m.func.name)))
+        .collect();
+
+    let signatures = node.methods.iter().map(|m| &m.func);
+    let dependencies = signatures
+        .flat_map(|func| {
+            let params = func.params.iter().map(|p| &p.param_type);
+            params.chain(&func.return_type)
+        })
+        .map(|value_type| ctx.type_name(value_type))
+        .collect();
+
+    let name = Type::Dyn(node.name.clone()).c_name();
+    ctx.struct_definitions.push(TypeDefinition {
+        code: format!(
+            "typedef struct {{ {} }} {vtable}; typedef struct {{ void* data; const {vtable}* vtable; }} {};",
+            entries,
+            name,
+            vtable = vtable_type(&node.name),
+        ),
+        name,
+        dependencies,
+    });
+}
+
+/// Emits the methods of an impl, including the trait's default methods the
+/// type checker added, as functions of the implementing type, and the vtable
+/// of the implementation.
+fn walk_impl_block(node: ImplBlockNode, ctx: &mut Context) {
+    let type_name = node.for_type.c_name();
+    let entries = node
+        .methods
+        .iter()
+        .filter(|m| m.self_param.is_some())
+        .map(|m| {
+            format!(
+                ".{} = ({}){}",
+                m.name,
+                method_pointer(m, ""),
+                method_name(&type_name, Some(&node.trait_name), &m.name)
+            )
+        })
+        .collect::<Vec<_>>();
+    let declaration = format!(
+        "const {} {}",
+        vtable_type(&node.trait_name),
+        vtable_name(&type_name, &node.trait_name)
+    );
+    ctx.function_prototypes
+        .push(format!("extern {};", declaration));
+
+    for method in node.methods {
+        walk_method(method, &type_name, Some(&node.trait_name), ctx);
+    }
+
+    ctx.add_function_declaration(format!("{} = {{ {} }};", declaration, entries.join(", ")));
+}
+
+/// Turns a value into a trait object pointing to it. Values that aren't
+/// stored in a variable are stored in a temporary first.
+fn walk_dyn_coercion(node: DynCoercionNode, ctx: &mut Context) -> String {
+    let type_name = node.value_type.c_name();
+    let pointer = receiver_pointer(*node.value, &node.value_type, ctx);
+
+    format!(
+        "(({}){{ (void*){}, &{} }})",
+        Type::Dyn(node.trait_name.clone()).c_name(),
+        pointer,
+        vtable_name(&type_name, &node.trait_name)
+    )
+}
+
+fn walk_struct_def(node: StructDefNode, ctx: &mut Context) {
+    trace!(?node, "generating struct");
+    let field_types: Vec<_> = node
+        .fields
+        .iter()
+        .map(|f| ctx.type_name(&f.field_type))
+        .collect();
+    let name = c_identifier(&node.name);
+    ctx.struct_definitions.push(TypeDefinition {
+        name: name.clone(),
+        code: format!(
+            "struct _{name} {{ {} }}; typedef struct _{name} {name};",
+            node.fields
+                .iter()
+                .zip(&field_types)
+                .map(|(field, field_type)| format!("{} {};", field_type, field.name))
+                .collect::<Vec<String>>()
+                .join(""),
+            name = name,
+        ),
+        dependencies: field_types,
+    });
+
+    for method in node.methods {
+        walk_method(method, &name, None, ctx);
+    }
+}
+
+/// C name of the tag value of an enum variant.
+fn enum_tag(enum_name: &str, variant: &str) -> String {
+    generated_name("tag_", &[&c_identifier(enum_name), variant])
+}
+
+/// C name of the function constructing an enum variant.
+fn enum_constructor(enum_name: &str, variant: &str) -> String {
+    generated_name("variant_", &[&c_identifier(enum_name), variant])
+}
+
+/// C member name of a value held by a variant. Values are named by their
+/// position, patterns match them positionally too.
+fn variant_field_name(index: usize) -> String {
+    format!("_{}", index)
+}
 
-        let params = func_call
-            .generic_params
+/// Emits an enum as a tagged union, a struct holding the tag of the variant
+/// and a union with one struct per variant holding values, plus a
+/// constructor function per variant.
+fn walk_enum_def(node: EnumDefNode, ctx: &mut Context) {
+    let name = &c_identifier(&node.name);
+
+    let tags = node
+        .variants
+        .iter()
+        .map(|variant| enum_tag(name, &variant.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let members = node
+        .variants
+        .iter()
+        .filter(|variant| !variant.fields.is_empty())
+        .map(|variant| {
+            let fields = variant
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    format!(
+                        "{} {};",
+                        ctx.type_name(&field.field_type),
+                        variant_field_name(i)
+                    )
+                })
+                .collect::<String>();
+            format!("struct {{ {} }} {};", fields, variant.name)
+        })
+        .collect::<String>();
+
+    // C has no empty unions, enums without values only store the tag.
+    let union = if members.is_empty() {
+        String::new()
+    } else {
+        format!("union {{ {} }} data;", members)
+    };
+
+    let mut code = format!(
+        "struct _{name} {{ enum {{ {} }} tag; {} }}; typedef struct _{name} {name};",
+        tags,
+        union,
+        name = name,
+    );
+
+    for variant in &node.variants {
+        let params = variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| format!("{} _{}", ctx.type_name(&field.field_type), i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let assignments = variant
+            .fields
             .iter()
             .enumerate()
-            .map(|(i, p)| FuncParam {
-                name: generic_func.node.params[i].name.clone(),
-                param_type: p.clone(),
+            .map(|(i, _)| {
+                let field_name = variant_field_name(i);
+                format!("value.data.{}.{} = _{};", variant.name, field_name, i)
             })
-            .collect::<Vec<_>>();
+            .collect::<String>();
 
-        println!("dbg_d {:?}", params);
+        code += &format!(
+            "{name} {}({}) {{ {name} value = {{ .tag = {} }}; {} return value; }}",
+            enum_constructor(name, &variant.name),
+            params,
+            enum_tag(name, &variant.name),
+            assignments,
+            name = name,
+        );
+    }
 
-        let return_type = if let Some(return_type) = node.return_type {
-            if let Some(pos) = generic_func
-                .generic_params
-                .iter()
-                .position(|x| *x == return_type)
-            {
-                func_call.generic_params[pos].clone()
-            } else {
-                return_type
-            }
-        } else {
-            // Handle the case where return_type is None
-            panic!("No return type specified") // or return a default/error
-        };
+    let fields = node.variants.iter().flat_map(|v| &v.fields);
+    ctx.struct_definitions.push(TypeDefinition {
+        name: name.clone(),
+        dependencies: fields.map(|f| f.field_type.c_name()).collect(),
+        code,
+    });
+}
 
-        new_name = format!("{}_{}", node.name, func_call.generic_params.join("_"));
-
-        walk_func_def(
-            FuncDefNode {
-                name: new_name.clone(),
-                params,
-                body: node.body,
-                return_type: Some(return_type),
-                generic_typing: None,
-            },
-            ctx,
-        );
+fn walk_enum_init(node: EnumInitNode, ctx: &mut Context) -> String {
+    let args = node
+        .args
+        .into_iter()
+        .map(|arg| walk_expression(arg, ctx))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "{}({})",
+        enum_constructor(&node.enum_name, &node.variant),
+        args
+    )
+}
+
+/// C integer constant. C has no negative constants, the smallest `i64` can
+/// only be written as an expression, and constants above the largest `i64`
+/// have to be marked unsigned.
+fn int_literal(value: i128) -> String {
+    if value == i64::MIN.into() {
+        format!("({} - 1)", i64::MIN + 1)
+    } else if value < 0 {
+        format!("({})", value)
+    } else if value > i64::MAX.into() {
+        format!("{}ull", value)
+    } else {
+        value.to_string()
     }
+}
 
-    format!("{}({})", new_name, params_code)
+fn walk_str_lit(str: String, _ctx: &mut Context) -> String {
+    str
+}
+
+fn walk_c_import(node: CImportNode, ctx: &mut Context) {
+    let line_directive = ctx.line_directive(node.span);
+    ctx.imports.push(format!(
+        "{}#include {}\n",
+        line_directive,
+        node.module.as_str()
+    ));
+}
+
+fn walk_return_expr(ret: ReturnExprNode, ctx: &mut Context) -> String {
+    match ret.expression {
+        Some(expression) => format!("return {};", walk_expression(*expression, ctx)),
+        None => String::from("return;"),
+    }
+}
+
+fn walk_binary_expr(node: BinaryExprNode, ctx: &mut Context) -> String {
+    let left = walk_expression(*node.left, ctx);
+
+    if node.op.is_logical() {
+        return walk_logical_expr(left, node.op, *node.right, ctx);
+    }
+
+    let right = walk_expression(*node.right, ctx);
+
+    if node.op != BinaryOp::Power {
+        return format!("({} {} {})", left, node.op, right);
+    }
+
+    // C has no power operator. Values from C are raised as doubles.
+    let power_type = match node.result_type {
+        Some(power_type) if power_type.is_numeric() => power_type,
+        _ => Type::F64,
+    };
+    let code = format!("fusion_pow_{}_({}, {})", power_type, left, right);
+
+    if !ctx.power_types.contains(&power_type) {
+        ctx.power_types.push(power_type);
+    }
+
+    code
+}
+
+/// Generates `&&` or `||`. Statements hoisted out of the right operand only
+/// run if it is evaluated, so then the operator is lowered to a temporary
+/// assigned inside an `if` on the left operand.
+fn walk_logical_expr(
+    left: String,
+    op: BinaryOp,
+    right: ExpressionNode,
+    ctx: &mut Context,
+) -> String {
+    let outer = std::mem::take(&mut ctx.hoisted_statements);
+    let right = walk_expression(right, ctx);
+    let right_hoisted = std::mem::replace(&mut ctx.hoisted_statements, outer);
+
+    if right_hoisted.is_empty() {
+        return format!("({} {} {})", left, op, right);
+    }
+
+    let temporary = ctx.temporary("logical");
+    let condition = match op {
+        BinaryOp::And => temporary.clone(),
+        _ => format!("!{}", temporary),
+    };
+    ctx.hoisted_statements += &format!(
+        "bool {t} = {}; if ({}) {{ {} {t} = {}; }}",
+        left,
+        condition,
+        right_hoisted,
+        right,
+        t = temporary,
+    );
+    temporary
+}
+
+/// Power function for one numeric type. Integers use exponentiation by
+/// squaring, so they stay exact. A negative integer exponent gives what
+/// dividing 1 by the power gives, rounded towards zero like integer division:
+/// 1 or -1 for a base of 1 or -1, and 0 for any other base, including 0.
+fn power_helper(power_type: &Type) -> String {
+    let negative = if power_type.is_unsigned() {
+        ""
+    } else {
+        "if (exponent < 0) return base == 1 ? 1 : base == -1 ? (exponent & 1 ? -1 : 1) : 0; "
+    };
+    let body = match power_type {
+        Type::F32 => "return powf(base, exponent);".to_string(),
+        Type::F64 => "return pow(base, exponent);".to_string(),
+        _ => format!(
+            "{}{} result = 1; while (exponent > 0) {{ if (exponent & 1) result *= base; \
+             base *= base; exponent >>= 1; }} return result;",
+            negative, power_type
+        ),
+    };
+
+    format!(
+        "static {t} fusion_pow_{t}_({t} base, {t} exponent) {{ {} }}",
+        body,
+        t = power_type
+    )
+}
+
+fn walk_unary_expr(node: UnaryExprNode, ctx: &mut Context) -> String {
+    format!("({}{})", node.op, walk_expression(*node.operand, ctx))
+}
+
+/// Calls of generic functions were renamed to their instances by the
+/// monomorphizer already.
+fn walk_func_call(func_call: FuncCallNode, ctx: &mut Context) -> String {
+    let params_code = func_call
+        .params
+        .into_iter()
+        .map(|param| walk_expression(param, ctx))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("{}({})", c_identifier(&func_call.name), params_code)
 }
 
 fn walk_block(block: BlockNode, ctx: &mut Context) -> CodeGenResult {
     let results: Vec<String> = block
         .expressions
         .into_iter()
-        .map(|expr| walk_expression(expr, ctx) + ";\n")
+        .map(|expr| walk_statement(expr, ctx) + "\n")
         .collect();
 
     CodeGenResult {
@@ -254,36 +1316,29 @@ fn walk_block(block: BlockNode, ctx: &mut Context) -> CodeGenResult {
 }
 
 fn walk_func_def(node: FuncDefNode, ctx: &mut Context) {
-    let code = format!(
-        "{} {}({}) {{ {} }}",
-        match node.clone().return_type {
-            Some(ret_type) => ret_type,
-            None => "void".to_string(),
-        },
-        node.name,
-        walk_func_def_params(node.clone().params, ctx),
-        walk_block(node.clone().body, ctx).code
-    )
-    .to_string();
-
-    if let Some(generic_typing) = node.clone().generic_typing {
-        ctx.generic_function_declarations.insert(
-            node.clone().name,
-            GenericFuncDeclaration {
-                node,
-                generic_params: generic_typing.types,
-            },
-        );
-        return;
-    }
+    let line_directive = ctx.line_directive(node.span);
+    let prototype = format!(
+        "{} {}({})",
+        ctx.type_name(&node.return_type.unwrap_or(Type::Void)),
+        c_identifier(&node.name),
+        walk_func_def_params(node.params, ctx),
+    );
+    ctx.function_prototypes
+        .push(format!("{}{};", line_directive, prototype));
 
+    let code = format!(
+        "{}{} {{ {} }}",
+        line_directive,
+        prototype,
+        walk_block(node.body, ctx).code
+    );
     ctx.add_function_declaration(code);
 }
 
-fn walk_func_def_params(params: Vec<FuncParam>, _ctx: &mut Context) -> String {
+fn walk_func_def_params(params: Vec<FuncParam>, ctx: &mut Context) -> String {
     let x = params
         .into_iter()
-        .map(|param| format!("{} {}", param.param_type, param.name))
+        .map(|param| format!("{} {}", ctx.type_name(&param.param_type), param.name))
         .collect::<Vec<String>>();
 
     x.join(", ")
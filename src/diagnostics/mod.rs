@@ -0,0 +1,260 @@
+use colored::{ColoredString, Colorize};
+use std::fmt;
+
+/// Byte range into the source a node was parsed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub content: String,
+    /// Offset of the first byte of the file in its `SourceMap`.
+    pub base: usize,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, content: impl Into<String>) -> Self {
+        SourceFile {
+            name: name.into(),
+            content: content.into(),
+            base: 0,
+        }
+    }
+
+    /// 1-based line and column of a byte offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.saturating_sub(self.base).min(self.content.len());
+        let before = &self.content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let col = before[line_start..].chars().count() + 1;
+
+        (line, col)
+    }
+
+    /// Span of the text of a 1-based line, without its indentation and line
+    /// break.
+    pub fn line_span(&self, line: usize) -> Span {
+        let start: usize = self
+            .content
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(str::len)
+            .sum();
+        let text = self.line(line);
+        let indent = text.len() - text.trim_start().len();
+
+        Span {
+            start: self.base + start + indent,
+            end: self.base + start + text.len(),
+        }
+    }
+
+    /// Text of a 1-based line without its line break.
+    pub fn line(&self, line: usize) -> &str {
+        self.content
+            .lines()
+            .nth(line - 1)
+            .unwrap_or("")
+            .trim_end_matches('\r')
+    }
+}
+
+/// Every file of a program. The files are laid out one after another, so a
+/// span points into exactly one of them.
+pub struct SourceMap {
+    pub files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: vec![] }
+    }
+
+    /// Adds a file and returns the offset its spans start at, which the
+    /// parser and AST builder add to the spans of the file.
+    pub fn add(&mut self, name: impl Into<String>, content: impl Into<String>) -> usize {
+        // One byte of room, so a span at the end of a file stays in it.
+        let base = self
+            .files
+            .last()
+            .map_or(0, |file| file.base + file.content.len() + 1);
+        self.files.push(SourceFile {
+            base,
+            ..SourceFile::new(name, content)
+        });
+        base
+    }
+
+    /// File the byte offset points into.
+    pub fn file(&self, offset: usize) -> &SourceFile {
+        self.files
+            .iter()
+            .rev()
+            .find(|file| file.base <= offset)
+            .expect("a source map holds at least one file")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "{}", "error".red().bold()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    /// Other places worth pointing at, like a previous definition.
+    pub secondary: Vec<(Span, String)>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+            label: None,
+            secondary: vec![],
+            notes: vec![],
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.secondary.push((span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Renders the diagnostic with the offending source line and a caret
+    /// underline, e.g.
+    ///
+    /// ```text
+    /// error: unknown function `foo`
+    ///  --> main.fu:3:9
+    ///   |
+    /// 3 | let x = foo(1)
+    ///   |         ^^^ not defined
+    /// ```
+    pub fn render(&self, sources: &SourceMap) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message.bold());
+
+        let gutter = match self.span {
+            Some(span) => {
+                let source = sources.file(span.start);
+                let underline = |width| match self.severity {
+                    Severity::Error => "^".repeat(width).red().bold(),
+                };
+                out += &snippet(source, span, underline, self.label.as_deref());
+                " ".repeat(source.line_col(span.start).0.to_string().len())
+            }
+            None => " ".to_string(),
+        };
+
+        for (span, label) in &self.secondary {
+            out += &snippet(
+                sources.file(span.start),
+                *span,
+                |width| "-".repeat(width).blue().bold(),
+                Some(label),
+            );
+        }
+        for note in &self.notes {
+            out += &format!("{} {} note: {}\n", gutter, "=".blue().bold(), note);
+        }
+
+        out
+    }
+}
+
+/// Location line plus the first source line of `span`, underlined.
+fn snippet(
+    source: &SourceFile,
+    span: Span,
+    underline: impl Fn(usize) -> ColoredString,
+    label: Option<&str>,
+) -> String {
+    let (line, col) = source.line_col(span.start);
+    let (end_line, end_col) = source.line_col(span.end);
+    let gutter = " ".repeat(line.to_string().len());
+    let text = source.line(line);
+    let indent: usize = text
+        .chars()
+        .take(col - 1)
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+
+    // Multi-line spans are underlined up to the end of their first line.
+    let width = if end_line == line {
+        end_col.saturating_sub(col).max(1)
+    } else {
+        (text.chars().count() + 1).saturating_sub(col).max(1)
+    };
+
+    let mut out = format!(
+        "{}{} {}:{}:{}\n",
+        gutter,
+        "-->".blue().bold(),
+        source.name,
+        line,
+        col
+    );
+    out += &format!("{} {}\n", gutter, "|".blue().bold());
+    out += &format!(
+        "{} {} {}\n",
+        line.to_string().blue().bold(),
+        "|".blue().bold(),
+        text.replace('\t', "    ")
+    );
+    out += &format!(
+        "{} {} {}{}",
+        gutter,
+        "|".blue().bold(),
+        " ".repeat(indent),
+        underline(width)
+    );
+    if let Some(label) = label {
+        out += &format!(" {}", label);
+    }
+    out += "\n";
+
+    out
+}
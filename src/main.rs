@@ -2,87 +2,527 @@ mod ast_builder;
 
 mod ast_nodes;
 mod codegen;
+mod diagnostics;
+mod modules;
+mod monomorphizer;
 mod parser;
+mod project;
+mod resolver;
+mod toolchain;
+mod type_checker;
 
 use ast_builder::build_ast_from_pairs;
+use ast_nodes::program::ProgramNode;
 use clap::Parser as ClapParser;
-use codegen::gen_code;
+use codegen::{Target, gen_code};
 use colored::Colorize;
-use parser::{FusionParser, Rule};
-use pest::Parser;
-use sh::sh;
-use std::fs;
-use std::path::Path;
-use std::process::Command;
-
-/// Simple program to parse a function definition
+use diagnostics::{Diagnostic, SourceMap};
+use modules::{load_modules, resolve_modules};
+use monomorphizer::monomorphize;
+use parser::parse_program;
+use project::{MANIFEST_NAME, Project};
+use resolver::resolve_names;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::{env, fs, process};
+use toolchain::{TempDir, Toolchain, format_c};
+use tracing::{Level, info, info_span};
+use type_checker::check_types;
+
+/// Compiler for the Fusion language
 #[derive(ClapParser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Command,
+    /// Log what the compiler does to stderr, `-vv` and `-vvv` for more
+    /// detail
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    #[command(flatten)]
+    dumps: Dumps,
+}
+
+/// Intermediate stages printed to stderr while compiling.
+#[derive(clap::Args, Debug, Default)]
+struct Dumps {
+    /// Print the parse tree of every file
+    #[arg(long, global = true)]
+    dump_pairs: bool,
+    /// Print the syntax tree, with its modules loaded
+    #[arg(long, global = true)]
+    dump_ast: bool,
+    /// Print the generated C code
+    #[arg(long, global = true)]
+    dump_c: bool,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Check a file or project for errors without compiling it
+    Check {
+        #[command(flatten)]
+        input: Input,
+    },
+    /// Compile a file or project to an executable or library
+    Build {
+        #[command(flatten)]
+        input: Input,
+        /// Where to write the executable or library, by default the name of
+        /// the file in the current directory, or `build/<name>` or
+        /// `build/lib<name>.a` in a project
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        cc: CcArgs,
+    },
+    /// Compile a file or project and run it
+    Run {
+        #[command(flatten)]
+        input: Input,
+        /// Arguments passed to the program
+        #[arg(last = true)]
+        args: Vec<String>,
+        #[command(flatten)]
+        cc: CcArgs,
+    },
+    /// Print one intermediate stage of the compilation
+    Emit {
+        #[command(flatten)]
+        input: Input,
+        #[arg(long)]
+        stage: Stage,
+    },
+}
+
+/// What a command compiles.
+#[derive(clap::Args, Debug)]
+struct Input {
+    /// Fusion file to compile, by default the project the current directory
+    /// is in
+    file: Option<PathBuf>,
+    /// Manifest of the project to compile, by default the `fusion.toml` in
+    /// the current directory or the closest one above it
+    #[arg(long, conflicts_with = "file")]
+    manifest_path: Option<PathBuf>,
+}
+
+/// How the generated C is compiled.
+#[derive(clap::Args, Debug)]
+struct CcArgs {
+    /// C compiler to use, by default `$CC` or else `cc`
     #[arg(long)]
-    input: String,
-    #[arg(long)]
-    output: String,
+    cc: Option<String>,
+    /// Flags passed to the C compiler, separated by spaces
+    #[arg(long, allow_hyphen_values = true)]
+    cflags: Option<String>,
+    /// Flags passed to the C compiler when linking, separated by spaces
+    #[arg(long, allow_hyphen_values = true)]
+    ldflags: Option<String>,
+    /// Optimization level of the C compiler
+    #[arg(short = 'O', value_name = "LEVEL", value_parser = ["0", "1", "2", "3", "s"])]
+    opt_level: Option<String>,
+    /// Keep the generated C in this file, formatted with clang-format if it
+    /// is installed, instead of a temporary one
+    #[arg(long, value_name = "PATH")]
+    c_file: Option<PathBuf>,
+}
+
+impl CcArgs {
+    fn toolchain(&self, project: Option<&Project>) -> Toolchain {
+        let mut toolchain = Toolchain::from_env();
+        if let Some(cc) = &self.cc {
+            toolchain.cc = cc.clone();
+        }
+        toolchain.opt_level = self.opt_level.clone();
+        toolchain.cflags = split_flags(self.cflags.as_deref());
+        toolchain.ldflags = split_flags(self.ldflags.as_deref());
+        if let Some(project) = project {
+            toolchain.include_paths = project.include_paths();
+            toolchain.libraries = project.manifest.c.libraries.clone();
+        }
+        toolchain
+    }
+}
+
+fn split_flags(flags: Option<&str>) -> Vec<String> {
+    let flags = flags.unwrap_or_default().split_whitespace();
+    flags.map(String::from).collect()
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Stage {
+    /// Parse tree of every file
+    Pairs,
+    /// Syntax tree of the program, with its modules loaded
+    Ast,
+    /// Generated C code
+    C,
+}
+
+/// A file or project loaded for compilation.
+struct Compilation {
+    ast: ProgramNode,
+    sources: SourceMap,
+    /// `None` when compiling a single file.
+    project: Option<Project>,
+}
+
+impl Compilation {
+    fn target(&self) -> Target {
+        match &self.project {
+            Some(project) => project.manifest.project.kind,
+            None => Target::Executable,
+        }
+    }
+
+    /// Name of the program, the name of the project or of the file.
+    fn name(&self, input: &Input) -> String {
+        match (&self.project, &input.file) {
+            (Some(project), _) => project.manifest.project.name.clone(),
+            (None, Some(file)) => file_stem(file),
+            (None, None) => unreachable!("a single file is only compiled if one is given"),
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let file_name = &args.input;
+    let level = match args.verbose {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    let dumps = &args.dumps;
+    match args.command {
+        Command::Check { input } => {
+            let compilation = load(&input, dumps);
+            let target = compilation.target();
+            compile(compilation.ast, target, &compilation.sources, dumps, false);
+        }
+        Command::Build { input, output, cc } => {
+            let compilation = load(&input, dumps);
+            let output = match (output, &compilation.project, &input.file) {
+                (Some(output), _, _) => output,
+                (None, Some(project), _) => project.output(),
+                (None, None, Some(file)) => PathBuf::from(file_stem(file)),
+                (None, None, None) => {
+                    unreachable!("a single file is only compiled if one is given")
+                }
+            };
+            build(compilation, &output, &cc, dumps);
+        }
+        Command::Run { input, args, cc } => run(&input, &args, &cc, dumps),
+        Command::Emit { input, stage } => emit(&input, stage, dumps),
+    }
+}
+
+/// Loads the file given on the command line with its modules, or else the
+/// project of the manifest.
+fn load(input: &Input, dumps: &Dumps) -> Compilation {
+    let compilation = run_stage("parse", || load_input(input));
+
+    if dumps.dump_pairs {
+        eprint!("{}", pairs(&compilation.sources));
+    }
+    if dumps.dump_ast {
+        eprint!("{}", compilation.ast);
+    }
+
+    compilation
+}
+
+fn load_input(input: &Input) -> Compilation {
+    let mut sources = SourceMap::new();
+
+    if let Some(file) = &input.file {
+        let ast = load_file(file, &mut sources);
+        return Compilation {
+            ast,
+            sources,
+            project: None,
+        };
+    }
+
+    let project = match &input.manifest_path {
+        Some(path) => Project::load(path, &mut sources),
+        None => Project::find(&env::current_dir().unwrap(), &mut sources),
+    }
+    .unwrap_or_else(|diagnostics| report_and_exit(&sources, diagnostics));
+
+    let ast = project
+        .load_program(&mut sources)
+        .unwrap_or_else(|diagnostics| report_and_exit(&sources, diagnostics));
+
+    Compilation {
+        ast,
+        sources,
+        project: Some(project),
+    }
+}
 
-    let file_path = Path::new(file_name.as_str());
+fn load_file(path: &Path, sources: &mut SourceMap) -> ProgramNode {
+    info!(path = %path.display(), "parsing file");
+    let content = fs::read_to_string(path).unwrap_or_else(|e| {
+        let message = format!("couldn't read `{}`: {}", path.display(), e);
+        report_and_exit(sources, vec![Diagnostic::error(message)])
+    });
+    let base = sources.add(path.display().to_string(), content.as_str());
 
-    let file_content = fs::read_to_string(file_path)
-        .expect("Failed to read source file.")
-        .to_string();
-    let mut rules = FusionParser::parse(Rule::program, &file_content).unwrap();
+    let pair = match parse_program(&content, base) {
+        Ok(pair) => pair,
+        Err(diagnostics) => report_and_exit(sources, diagnostics),
+    };
 
-    let pair = rules.next().unwrap();
+    let mut ast = match build_ast_from_pairs(pair, base) {
+        Ok(ast) => ast,
+        Err(errors) => report_and_exit(sources, errors.iter().map(Diagnostic::from).collect()),
+    };
 
-    println!("{}{}", "Pairs: \n".yellow(), pair);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    if let Err(diagnostics) = load_modules(&mut ast, dir, sources) {
+        report_and_exit(sources, diagnostics);
+    }
+
+    ast
+}
 
-    let ast = match pair.as_rule() {
-        Rule::program => build_ast_from_pairs(pair),
-        _ => panic!(
-            "Top Level Node can only be a program. Not a {}",
-            pair.as_str()
+/// Compiles to `output`, an executable or a static library depending on the
+/// target of the project.
+fn build(compilation: Compilation, output: &Path, cc: &CcArgs, dumps: &Dumps) {
+    let target = compilation.target();
+    let Compilation {
+        ast,
+        sources,
+        project,
+    } = compilation;
+    let code = compile(ast, target, &sources, dumps, true);
+
+    let result = run_stage("compile C", || {
+        compile_c(&code, output, target, cc, project.as_ref(), &sources)
+    });
+    if let Err(diagnostics) = result {
+        report_and_exit(&sources, diagnostics);
+    }
+}
+
+/// Writes the C code to a temporary file, or the one asked for, and
+/// compiles it.
+fn compile_c(
+    code: &str,
+    output: &Path,
+    target: Target,
+    cc: &CcArgs,
+    project: Option<&Project>,
+    sources: &SourceMap,
+) -> Result<(), Vec<Diagnostic>> {
+    let temp_dir = TempDir::new().map_err(|e| {
+        let message = format!("couldn't create a temporary directory: {}", e);
+        vec![Diagnostic::error(message)]
+    })?;
+    let (c_file, code) = match &cc.c_file {
+        Some(c_file) => (c_file.clone(), format_c(code)),
+        None => (
+            temp_dir.path().join(file_stem(output) + ".c"),
+            code.to_string(),
         ),
     };
 
-    println!("{}{}", "AST:\n".purple(), ast);
+    for dir in [c_file.parent(), output.parent()].into_iter().flatten() {
+        fs::create_dir_all(dir).map_err(|e| {
+            let message = format!("couldn't create `{}`: {}", dir.display(), e);
+            vec![Diagnostic::error(message)]
+        })?;
+    }
+    fs::write(&c_file, code).map_err(|e| {
+        let message = format!("couldn't write `{}`: {}", c_file.display(), e);
+        vec![Diagnostic::error(message)]
+    })?;
 
-    let code = gen_code(ast);
+    cc.toolchain(project)
+        .compile(&c_file, output, target, sources)
+}
 
-    println!(
-        "\n\n{}\n--------------------\n{}\n--------------------\n\n",
-        "Generated C-Code:".green(),
-        code
-    );
+/// Builds into a temporary directory and runs the program with `args`,
+/// exiting with its exit code.
+fn run(input: &Input, args: &[String], cc: &CcArgs, dumps: &Dumps) -> ! {
+    let compilation = load(input, dumps);
+    let name = compilation.name(input);
+    let target = compilation.target();
+    let Compilation {
+        ast,
+        sources,
+        project,
+    } = compilation;
+    if target == Target::Library {
+        let message = format!("`{}` is a library, which can't be run", name);
+        report_and_exit(&sources, vec![Diagnostic::error(message)]);
+    }
+    let code = compile(ast, target, &sources, dumps, true);
+
+    let temp_dir = TempDir::new().unwrap_or_else(|e| {
+        let message = format!("couldn't create a temporary directory: {}", e);
+        report_and_exit(&sources, vec![Diagnostic::error(message)])
+    });
+    let executable = temp_dir.path().join(&name);
+    let result = run_stage("compile C", || {
+        compile_c(&code, &executable, target, cc, project.as_ref(), &sources)
+    });
+    let status = result.map(|()| process::Command::new(&executable).args(args).status());
+    drop(temp_dir);
+
+    match status {
+        Ok(Ok(status)) => process::exit(exit_code(status, &name, &sources)),
+        Ok(Err(e)) => {
+            let message = format!("couldn't run `{}`: {}", executable.display(), e);
+            report_and_exit(&sources, vec![Diagnostic::error(message)])
+        }
+        Err(diagnostics) => report_and_exit(&sources, diagnostics),
+    }
+}
 
-    let formatter_output = if std::env::consts::OS == "windows" {
-        Command::new("powershell")
-            .arg("-c")
-            .arg(format!("echo '{}' | clang-format", code))
-            .output()
-            .unwrap()
-    } else {
-        Command::new("sh")
-            .arg("-c")
-            .arg(format!("echo '{}' | clang-format", code))
-            .output()
-            .unwrap()
+/// Exit code `run` passes on from the program. A program killed by a signal
+/// is reported and, like in a shell, exits with 128 plus the signal number.
+fn exit_code(status: process::ExitStatus, name: &str, sources: &SourceMap) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        let message = format!("`{}` was killed by {}", name, signal_name(signal));
+        eprintln!("{}", Diagnostic::error(message).render(sources));
+        return 128 + signal;
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Name of a signal whose number is the same on every Unix.
+#[cfg(unix)]
+fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        5 => "SIGTRAP",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return format!("signal {}", signal),
     };
+    format!("signal {} ({})", signal, name)
+}
 
-    let formatted_code = String::from_utf8(formatter_output.stdout).unwrap();
+fn emit(input: &Input, stage: Stage, dumps: &Dumps) {
+    let compilation = load(input, dumps);
 
-    println!(
-        "\n\n{}\n--------------------\n{}\n--------------------\n\n",
-        "Formatted C-Code:".on_green().black(),
-        formatted_code
-    );
+    match stage {
+        Stage::Pairs => print!("{}", pairs(&compilation.sources)),
+        Stage::Ast => print!("{}", compilation.ast),
+        Stage::C => {
+            let target = compilation.target();
+            let code = compile(compilation.ast, target, &compilation.sources, dumps, false);
+            println!("{}", format_c(&code));
+        }
+    }
+}
+
+/// Parse trees of the Fusion files of the source map, each headed by the
+/// name of its file if there are several.
+fn pairs(sources: &SourceMap) -> String {
+    let files = sources.files.iter();
+    let files: Vec<_> = files
+        .filter(|file| !file.name.ends_with(MANIFEST_NAME))
+        .collect();
+
+    let mut out = String::new();
+    for file in &files {
+        let pair = match parse_program(&file.content, file.base) {
+            Ok(pair) => pair,
+            Err(diagnostics) => report_and_exit(sources, diagnostics),
+        };
+        if files.len() > 1 {
+            out += &format!("{}:\n", file.name);
+        }
+        out += &format!("{}\n", pair);
+    }
+    out
+}
+
+/// Runs the passes from module resolution to code generation, exiting with
+/// the diagnostics of the first one that fails. With `line_directives`, the
+/// C points the C compiler at the Fusion lines it was generated for.
+fn compile(
+    mut ast: ProgramNode,
+    target: Target,
+    source: &SourceMap,
+    dumps: &Dumps,
+    line_directives: bool,
+) -> String {
+    if let Err(diagnostics) = run_stage("resolve modules", || resolve_modules(&mut ast)) {
+        report_and_exit(source, diagnostics);
+    }
+
+    if let Err(diagnostics) = run_stage("resolve names", || resolve_names(&ast)) {
+        report_and_exit(source, diagnostics);
+    }
+
+    if let Err(diagnostics) = run_stage("check types", || check_types(&mut ast)) {
+        report_and_exit(source, diagnostics);
+    }
 
-    fs::write("output.c", formatted_code).unwrap();
+    if let Err(diagnostics) = run_stage("monomorphize", || monomorphize(&mut ast)) {
+        report_and_exit(source, diagnostics);
+    }
 
-    sh!(gcc "output.c" "-o" {args.output});
-    //sh!(rm "output.c");
+    let sources = line_directives.then_some(source);
+    let code = match run_stage("generate C", || gen_code(ast, target, sources)) {
+        Ok(code) => code,
+        Err(diagnostics) => report_and_exit(source, diagnostics),
+    };
+    if dumps.dump_c {
+        eprintln!("{}", format_c(&code));
+    }
+    code
+}
+
+/// Runs one stage of the compiler inside a span named after it, logging how
+/// long it took.
+fn run_stage<T>(name: &str, stage: impl FnOnce() -> T) -> T {
+    let _span = info_span!("stage", name).entered();
+    let start = Instant::now();
+    let result = stage();
+    info!(elapsed = ?start.elapsed(), "finished");
+    result
+}
+
+fn file_stem(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or(path.as_os_str());
+    stem.to_string_lossy().into_owned()
+}
+
+fn report_and_exit(source: &SourceMap, diagnostics: Vec<Diagnostic>) -> ! {
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic.render(source));
+    }
+    eprintln!(
+        "{}",
+        format!("aborting due to {} error(s)", diagnostics.len())
+            .red()
+            .bold()
+    );
+    std::process::exit(1);
 }
@@ -0,0 +1,104 @@
+use crate::ast_builder::build_ast_from_pairs;
+use crate::ast_nodes::{
+    expression::{ExpressionKind, ExpressionNode},
+    module::ModuleNode,
+    program::ProgramNode,
+};
+use crate::diagnostics::{Diagnostic, SourceMap};
+use crate::parser::parse_program;
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+/// Loads the items of every module declared without a body from its own
+/// file. `mod net` in a file of `dir` is read from `dir/net.fu` or
+/// `dir/net/mod.fu`, and modules declared in it from the `dir/net`
+/// directory.
+pub fn load_modules(
+    program: &mut ProgramNode,
+    dir: &Path,
+    sources: &mut SourceMap,
+) -> Result<(), Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
+    load_items(&mut program.expressions, dir, sources, &mut diagnostics);
+
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Loads the modules declared without a body in `items`, which belong to a
+/// file whose module directory is `dir`.
+pub fn load_items(
+    items: &mut [ExpressionNode],
+    dir: &Path,
+    sources: &mut SourceMap,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for item in items {
+        let ExpressionKind::Module(module) = &mut item.kind else {
+            continue;
+        };
+        let dir = dir.join(&module.name);
+
+        if module.items.is_none() {
+            match load_file(module, &dir, sources) {
+                Ok(items) => module.items = Some(items),
+                Err(mut errors) => {
+                    diagnostics.append(&mut errors);
+                    continue;
+                }
+            }
+        }
+        if let Some(items) = &mut module.items {
+            load_items(items, &dir, sources, diagnostics);
+        }
+    }
+}
+
+fn load_file(
+    module: &ModuleNode,
+    dir: &Path,
+    sources: &mut SourceMap,
+) -> Result<Vec<ExpressionNode>, Vec<Diagnostic>> {
+    let candidates = [dir.with_extension("fu"), dir.join("mod.fu")];
+    let Some(path) = candidates.iter().find(|path| path.is_file()) else {
+        let diagnostic = Diagnostic::error(format!("file not found for module `{}`", module.name))
+            .with_span(module.span)
+            .with_label("declared here")
+            .with_note(format!(
+                "create `{}` or `{}`, or give the module a body",
+                candidates[0].display(),
+                candidates[1].display()
+            ));
+        return Err(vec![diagnostic]);
+    };
+
+    debug!(module = %module.name, path = %path.display(), "loading module");
+    let content = fs::read_to_string(path).map_err(|e| {
+        vec![
+            Diagnostic::error(format!("couldn't read `{}`: {}", path.display(), e))
+                .with_span(module.span),
+        ]
+    })?;
+    parse_source(path, content, sources)
+}
+
+/// Adds the content of the file at `path` to the source map and parses it
+/// into its items.
+pub fn parse_source(
+    path: &Path,
+    content: String,
+    sources: &mut SourceMap,
+) -> Result<Vec<ExpressionNode>, Vec<Diagnostic>> {
+    let base = sources.add(path.display().to_string(), content);
+    let source = &sources.file(base).content;
+
+    let pair = parse_program(source, base)?;
+    let program = build_ast_from_pairs(pair, base)
+        .map_err(|errors| errors.iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+
+    Ok(program.expressions)
+}
@@ -0,0 +1,803 @@
+mod loader;
+
+pub use loader::{load_items, load_modules, parse_source};
+
+use crate::ast_nodes::{
+    block::BlockNode,
+    enum_def::EnumInitNode,
+    expression::{ExpressionKind, ExpressionNode},
+    func_call::FuncCallNode,
+    func_def::{FuncDefNode, GenericTypingNode},
+    match_expr::{PatternKind, PatternNode},
+    program::ProgramNode,
+    term::PlaceSegment,
+    types::{Type, reserved_name},
+    var_access::VarAccessNode,
+};
+use crate::diagnostics::{Diagnostic, Span};
+use crate::resolver::reserved_name_error;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+enum ItemKind {
+    Function,
+    Struct,
+    Enum,
+    Trait,
+    Module(usize),
+    /// Type or const parameter of a generic item.
+    TypeParameter,
+    /// `use` of the item at the path, resolved when it is first looked up.
+    Import(Vec<String>),
+}
+
+impl ItemKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            ItemKind::Function => "function",
+            ItemKind::Struct => "struct",
+            ItemKind::Enum => "enum",
+            ItemKind::Trait => "trait",
+            ItemKind::Module(_) => "module",
+            ItemKind::TypeParameter => "type parameter",
+            ItemKind::Import(_) => "import",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Item {
+    kind: ItemKind,
+    /// Name of the item once modules are flattened, like `math::square`.
+    name: String,
+    public: bool,
+    span: Span,
+}
+
+struct Module {
+    /// Names of the module and the modules it is nested in, like `["net",
+    /// "http"]`. Empty for the root module.
+    path: Vec<String>,
+    parent: Option<usize>,
+    items: HashMap<String, Item>,
+}
+
+struct Context {
+    /// Every module, the root module first and the others in the order they
+    /// are declared in.
+    modules: Vec<Module>,
+    /// Module of the code being rewritten.
+    current: usize,
+    /// Number of modules entered so far, which is the id of the last one.
+    entered: usize,
+    /// Items and type parameters declared inside functions and blocks,
+    /// innermost last.
+    scopes: Vec<HashMap<String, Item>>,
+    /// Items of the `use` declarations resolved so far, `None` for the ones
+    /// that failed.
+    imports: HashMap<(usize, String), Option<Item>>,
+    /// `use` declarations being resolved, to catch the ones leading back to
+    /// themselves.
+    resolving: HashSet<(usize, String)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Context {
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Name of an item declared in the current module once modules are
+    /// flattened. Items of the root module keep their names.
+    fn qualify(&self, name: &str) -> String {
+        qualify(&self.modules[self.current].path, name)
+    }
+
+    /// Whether code in module `from` sees the private items of `module`,
+    /// which is the case for the module itself and the ones nested in it.
+    fn is_inside(&self, from: usize, module: usize) -> bool {
+        let mut current = Some(from);
+        while let Some(id) = current {
+            if id == module {
+                return true;
+            }
+            current = self.modules[id].parent;
+        }
+        false
+    }
+
+    fn module_name(&self, module: usize) -> String {
+        match self.modules[module].path.as_slice() {
+            [] => "the root module".to_string(),
+            path => format!("`{}`", path.join("::")),
+        }
+    }
+}
+
+fn qualify(path: &[String], name: &str) -> String {
+    path.iter()
+        .map(String::as_str)
+        .chain(std::iter::once(name))
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Flattens the modules of a program into one list of items. Items of a
+/// module are renamed to their path, like `math::square`, and every path in
+/// the code is replaced by the name of the item it leads to, so later passes
+/// see a program without modules. Reports paths leading nowhere and uses of
+/// items that aren't `pub` outside of their module.
+///
+/// Names that don't name an item, like variables or functions of C headers,
+/// are left for name resolution to check.
+pub fn resolve_modules(program: &mut ProgramNode) -> Result<(), Vec<Diagnostic>> {
+    let mut ctx = Context {
+        modules: vec![],
+        current: 0,
+        entered: 0,
+        scopes: vec![],
+        imports: HashMap::new(),
+        resolving: HashSet::new(),
+        diagnostics: vec![],
+    };
+
+    collect_module(&program.expressions, vec![], None, &mut ctx);
+    let items = std::mem::take(&mut program.expressions);
+    program.expressions = flatten_module(items, 0, &mut ctx);
+
+    if ctx.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(ctx.diagnostics)
+    }
+}
+
+/// Declares the items of a module and, depth first, of the modules nested
+/// in it. Returns the id of the module.
+fn collect_module(
+    items: &[ExpressionNode],
+    path: Vec<String>,
+    parent: Option<usize>,
+    ctx: &mut Context,
+) -> usize {
+    let id = ctx.modules.len();
+    ctx.modules.push(Module {
+        path: path.clone(),
+        parent,
+        items: HashMap::new(),
+    });
+
+    for expr in items {
+        let (name, kind, public, span) = match &expr.kind {
+            ExpressionKind::FuncDef(node) => {
+                (&*node.name, ItemKind::Function, node.public, node.span)
+            }
+            ExpressionKind::StructDef(node) => {
+                (&*node.name, ItemKind::Struct, node.public, node.span)
+            }
+            ExpressionKind::EnumDef(node) => (&*node.name, ItemKind::Enum, node.public, node.span),
+            ExpressionKind::TraitDef(node) => {
+                (&*node.name, ItemKind::Trait, node.public, node.span)
+            }
+            ExpressionKind::Module(node) => {
+                if let Some(reason) = reserved_name(&node.name) {
+                    ctx.error(reserved_name_error(&node.name, reason, node.span));
+                }
+                let mut child_path = path.clone();
+                child_path.push(node.name.clone());
+                let items = node.items.as_deref().unwrap_or_default();
+                let child = collect_module(items, child_path, Some(id), ctx);
+                (&*node.name, ItemKind::Module(child), node.public, node.span)
+            }
+            ExpressionKind::Use(node) => {
+                let kind = ItemKind::Import(node.path.clone());
+                (node.name(), kind, node.public, node.span)
+            }
+            _ => continue,
+        };
+
+        let item = Item {
+            name: qualify(&path, name),
+            kind,
+            public,
+            span,
+        };
+        let items = &mut ctx.modules[id].items;
+        match items.get(name) {
+            None => {
+                items.insert(name.to_string(), item);
+            }
+            // Functions, structs, enums and traits of the same name are
+            // reported by name resolution, which sees them side by side once
+            // the modules are flattened.
+            Some(previous)
+                if matches!(item.kind, ItemKind::Module(_) | ItemKind::Import(_))
+                    || matches!(previous.kind, ItemKind::Module(_) | ItemKind::Import(_)) =>
+            {
+                let label = match previous.kind {
+                    ItemKind::Import(_) => "first imported here".to_string(),
+                    _ => format!("first defined as a {} here", previous.kind.describe()),
+                };
+                let diagnostic =
+                    Diagnostic::error(format!("the name `{}` is defined multiple times", name))
+                        .with_span(item.span)
+                        .with_label("redefined here")
+                        .with_secondary(previous.span, label);
+                ctx.error(diagnostic);
+            }
+            Some(_) => {}
+        }
+    }
+
+    id
+}
+
+/// Rewrites the items of module `id` and splices the items of its nested
+/// modules in place of their declaration.
+fn flatten_module(items: Vec<ExpressionNode>, id: usize, ctx: &mut Context) -> Vec<ExpressionNode> {
+    let outer = std::mem::replace(&mut ctx.current, id);
+    let mut flat = vec![];
+
+    for mut expr in items {
+        match expr.kind {
+            ExpressionKind::Module(node) => {
+                ctx.entered += 1;
+                let child = ctx.entered;
+                flat.extend(flatten_module(node.items.unwrap_or_default(), child, ctx));
+            }
+            // Reports imports nothing refers to as well.
+            ExpressionKind::Use(node) => {
+                resolve_import(id, node.name(), ctx);
+            }
+            _ => {
+                if id != 0 && !is_item(&expr) {
+                    let diagnostic = Diagnostic::error("expected an item, found a statement")
+                        .with_span(expr.span)
+                        .with_label(format!("not allowed in {}", ctx.module_name(id)))
+                        .with_note("modules hold functions, structs, enums, traits, impls, modules and `use` declarations");
+                    ctx.error(diagnostic);
+                    continue;
+                }
+                rewrite_expression(&mut expr, ctx);
+                flat.push(expr);
+            }
+        }
+    }
+
+    ctx.current = outer;
+    flat
+}
+
+fn is_item(expr: &ExpressionNode) -> bool {
+    matches!(
+        expr.kind,
+        ExpressionKind::FuncDef(_)
+            | ExpressionKind::StructDef(_)
+            | ExpressionKind::EnumDef(_)
+            | ExpressionKind::TraitDef(_)
+            | ExpressionKind::ImplBlock(_)
+            | ExpressionKind::CImport(_)
+    )
+}
+
+/// Item a `use` declaration of module `module` imports. Failures are only
+/// reported the first time.
+fn resolve_import(module: usize, name: &str, ctx: &mut Context) -> Option<Item> {
+    let key = (module, name.to_string());
+    if let Some(item) = ctx.imports.get(&key) {
+        return item.clone();
+    }
+
+    let import = ctx.modules[module].items.get(name)?.clone();
+    let ItemKind::Import(path) = &import.kind else {
+        return Some(import);
+    };
+    if path.len() == 1 && path[0] == name {
+        let diagnostic = Diagnostic::error(format!("unresolved import `{}`", name))
+            .with_span(import.span)
+            .with_label("imports itself")
+            .with_note(format!(
+                "give the path to the item, like `use module::{}`",
+                name
+            ));
+        ctx.error(diagnostic);
+        ctx.imports.insert(key, None);
+        return None;
+    }
+    if !ctx.resolving.insert(key.clone()) {
+        let diagnostic =
+            Diagnostic::error(format!("the import of `{}` leads back to itself", name))
+                .with_span(import.span)
+                .with_label("cyclic import");
+        ctx.error(diagnostic);
+        ctx.imports.insert(key, None);
+        return None;
+    }
+
+    let item = resolve_path(path, import.span, module, false, ctx);
+    ctx.resolving.remove(&key);
+    if ctx.imports.contains_key(&key) {
+        // Reported as part of the cycle already.
+        return None;
+    }
+    if item.is_none() && path.len() == 1 {
+        let diagnostic = Diagnostic::error(format!("unresolved import `{}`", path[0]))
+            .with_span(import.span)
+            .with_label("no item of this name in scope");
+        ctx.error(diagnostic);
+    }
+
+    ctx.imports.insert(key, item.clone());
+    item
+}
+
+/// Item named `name` in the scope of code in module `from`: items of the
+/// enclosing blocks if `in_blocks` is set, then the items of the module and
+/// of the modules it is nested in.
+fn lookup(name: &str, from: usize, in_blocks: bool, ctx: &mut Context) -> Option<Item> {
+    if in_blocks && let Some(item) = ctx.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+        return Some(item.clone());
+    }
+
+    let mut module = Some(from);
+    while let Some(id) = module {
+        if ctx.modules[id].items.contains_key(name) {
+            return item_of(id, name, ctx);
+        }
+        module = ctx.modules[id].parent;
+    }
+    None
+}
+
+/// Item declared or imported by module `module`. Imports have the
+/// visibility of the `use` declaration, so `pub use` re-exports an item.
+fn item_of(module: usize, name: &str, ctx: &mut Context) -> Option<Item> {
+    let item = ctx.modules[module].items.get(name)?.clone();
+    match item.kind {
+        ItemKind::Import(_) => resolve_import(module, name, ctx).map(|imported| Item {
+            public: item.public,
+            ..imported
+        }),
+        _ => Some(item),
+    }
+}
+
+/// Item a path like `net::http::get` leads to, starting in the scope of
+/// code in module `from`. A single name that isn't an item gives `None`
+/// without an error, it may name a variable or a C function.
+fn resolve_path(
+    path: &[String],
+    span: Span,
+    from: usize,
+    in_blocks: bool,
+    ctx: &mut Context,
+) -> Option<Item> {
+    let (first, rest) = path.split_first()?;
+    let Some(mut item) = lookup(first, from, in_blocks, ctx) else {
+        if !rest.is_empty() {
+            let diagnostic = Diagnostic::error(format!("cannot find module `{}`", first))
+                .with_span(span)
+                .with_label("not found in this scope");
+            ctx.error(diagnostic);
+        }
+        return None;
+    };
+
+    for (i, name) in rest.iter().enumerate() {
+        let ItemKind::Module(module) = item.kind else {
+            let diagnostic =
+                Diagnostic::error(format!("`{}` is not a module", path[..=i].join("::")))
+                    .with_span(span)
+                    .with_label(format!(
+                        "{} `{}` has no items",
+                        item.kind.describe(),
+                        item.name
+                    ))
+                    .with_secondary(item.span, format!("{} defined here", item.kind.describe()));
+            ctx.error(diagnostic);
+            return None;
+        };
+        item = member(module, name, span, from, ctx)?;
+    }
+
+    Some(item)
+}
+
+/// Item `name` of `module`, checking that code in module `from` may use it.
+fn member(module: usize, name: &str, span: Span, from: usize, ctx: &mut Context) -> Option<Item> {
+    let Some(item) = item_of(module, name, ctx) else {
+        // The import itself reports why it failed.
+        if !ctx.modules[module].items.contains_key(name) {
+            let module_name = ctx.module_name(module);
+            let diagnostic =
+                Diagnostic::error(format!("cannot find `{}` in {}", name, module_name))
+                    .with_span(span)
+                    .with_label(format!("not found in {}", module_name));
+            ctx.error(diagnostic);
+        }
+        return None;
+    };
+
+    if !item.public && !ctx.is_inside(from, module) {
+        let kind = item.kind.describe();
+        let diagnostic = Diagnostic::error(format!("{} `{}` is private", kind, name))
+            .with_span(span)
+            .with_label(format!("private {}", kind))
+            .with_secondary(item.span, format!("{} defined here", kind))
+            .with_note(format!(
+                "mark it `pub` to use it outside of {}",
+                ctx.module_name(module)
+            ));
+        ctx.error(diagnostic);
+    }
+
+    Some(item)
+}
+
+/// Name of the item a path in the code of the current module leads to, or
+/// the path itself if it doesn't name an item.
+fn resolve_name(name: &str, span: Span, ctx: &mut Context) -> String {
+    let path = name.split("::").map(str::to_string).collect::<Vec<_>>();
+    match resolve_path(&path, span, ctx.current, true, ctx) {
+        Some(item) if !matches!(item.kind, ItemKind::Module(_) | ItemKind::TypeParameter) => {
+            item.name
+        }
+        _ => name.to_string(),
+    }
+}
+
+fn rewrite_expression(expr: &mut ExpressionNode, ctx: &mut Context) {
+    match &mut expr.kind {
+        ExpressionKind::VarDecl(node) => {
+            rewrite_expression(&mut node.value, ctx);
+            if let Some(var_type) = &mut node.var_type {
+                rewrite_type(var_type, node.span, ctx);
+            }
+        }
+        ExpressionKind::Assignment(node) => {
+            for segment in &mut node.path {
+                if let PlaceSegment::Index(index) = segment {
+                    rewrite_expression(index, ctx);
+                }
+            }
+            rewrite_expression(&mut node.value, ctx);
+        }
+        ExpressionKind::BinaryExpr(node) => {
+            rewrite_expression(&mut node.left, ctx);
+            rewrite_expression(&mut node.right, ctx);
+        }
+        ExpressionKind::UnaryExpr(node) => rewrite_expression(&mut node.operand, ctx),
+        ExpressionKind::FuncDef(node) => {
+            node.name = ctx.qualify(&node.name);
+            rewrite_function(node, ctx);
+        }
+        ExpressionKind::ReturnExpr(node) => {
+            if let Some(expression) = &mut node.expression {
+                rewrite_expression(expression, ctx);
+            }
+        }
+        ExpressionKind::CImport(_)
+        | ExpressionKind::IntLit(_)
+        | ExpressionKind::FloatLit(_)
+        | ExpressionKind::StrLit(_)
+        | ExpressionKind::Break
+        | ExpressionKind::Continue => {}
+        ExpressionKind::FuncCall(node) => {
+            node.name = resolve_name(&node.name, node.span, ctx);
+            for generic_param in &mut node.generic_params {
+                rewrite_type(generic_param, node.span, ctx);
+            }
+            for param in &mut node.params {
+                rewrite_expression(param, ctx);
+            }
+        }
+        ExpressionKind::MethodCall(node) => {
+            rewrite_expression(&mut node.object, ctx);
+            for arg in &mut node.args {
+                rewrite_expression(arg, ctx);
+            }
+        }
+        // Structs and enums are values in static method calls, like
+        // `Point.new()`.
+        ExpressionKind::VarAccess(node) => {
+            if let Some(item) = lookup(&node.name, ctx.current, true, ctx)
+                && matches!(item.kind, ItemKind::Struct | ItemKind::Enum)
+            {
+                node.name = item.name;
+            }
+        }
+        ExpressionKind::StructDef(node) => {
+            node.name = ctx.qualify(&node.name);
+            ctx.scopes.push(type_params(&node.generic_typing));
+            rewrite_generic_typing(&mut node.generic_typing, ctx);
+            for field in &mut node.fields {
+                rewrite_type(&mut field.field_type, field.span, ctx);
+                if let Some(default) = &mut field.default {
+                    rewrite_expression(default, ctx);
+                }
+            }
+            for method in &mut node.methods {
+                rewrite_function(method, ctx);
+            }
+            ctx.scopes.pop();
+        }
+        ExpressionKind::StructInit(node) => {
+            node.struct_name = resolve_name(&node.struct_name, node.span, ctx);
+            for generic_param in &mut node.generic_params {
+                rewrite_type(generic_param, node.span, ctx);
+            }
+            for field in &mut node.fields {
+                rewrite_expression(&mut field.value, ctx);
+            }
+        }
+        ExpressionKind::StructFieldAccess(node) => rewrite_expression(&mut node.object, ctx),
+        ExpressionKind::EnumDef(node) => {
+            node.name = ctx.qualify(&node.name);
+            ctx.scopes.push(type_params(&node.generic_typing));
+            rewrite_generic_typing(&mut node.generic_typing, ctx);
+            for variant in &mut node.variants {
+                for field in &mut variant.fields {
+                    rewrite_type(&mut field.field_type, field.span, ctx);
+                }
+            }
+            ctx.scopes.pop();
+        }
+        ExpressionKind::EnumInit(node) => {
+            if let Some(kind) = rewrite_enum_init(node, ctx) {
+                expr.kind = kind;
+            }
+        }
+        ExpressionKind::TraitDef(node) => {
+            node.name = ctx.qualify(&node.name);
+            for method in &mut node.methods {
+                rewrite_function(&mut method.func, ctx);
+            }
+        }
+        ExpressionKind::ImplBlock(node) => {
+            ctx.scopes.push(type_params(&node.generic_typing));
+            rewrite_generic_typing(&mut node.generic_typing, ctx);
+            node.trait_name = resolve_name(&node.trait_name, node.span, ctx);
+            rewrite_type(&mut node.for_type, node.span, ctx);
+            for method in &mut node.methods {
+                rewrite_function(method, ctx);
+            }
+            ctx.scopes.pop();
+        }
+        // Taken out of blocks by `rewrite_block` and spliced into the
+        // surrounding module by `flatten_module`.
+        ExpressionKind::Module(_) | ExpressionKind::Use(_) => {}
+        ExpressionKind::DynCoercion(node) => {
+            rewrite_expression(&mut node.value, ctx);
+            rewrite_type(&mut node.value_type, node.span, ctx);
+            node.trait_name = resolve_name(&node.trait_name, node.span, ctx);
+        }
+        ExpressionKind::ArrayLit(node) => {
+            for element in &mut node.elements {
+                rewrite_expression(element, ctx);
+            }
+        }
+        ExpressionKind::ArrayRepeat(node) => {
+            rewrite_expression(&mut node.value, ctx);
+            rewrite_type(&mut node.length, node.span, ctx);
+        }
+        ExpressionKind::Index(node) => {
+            rewrite_expression(&mut node.object, ctx);
+            rewrite_expression(&mut node.index, ctx);
+        }
+        ExpressionKind::If(node) => {
+            rewrite_expression(&mut node.condition, ctx);
+            rewrite_block(&mut node.then_block, ctx);
+            if let Some(else_block) = &mut node.else_block {
+                rewrite_block(else_block, ctx);
+            }
+        }
+        ExpressionKind::Match(node) => {
+            rewrite_expression(&mut node.subject, ctx);
+            for arm in &mut node.arms {
+                rewrite_pattern(&mut arm.pattern, ctx);
+                if let Some(guard) = &mut arm.guard {
+                    rewrite_expression(guard, ctx);
+                }
+                rewrite_block(&mut arm.body, ctx);
+            }
+        }
+        ExpressionKind::While(node) => {
+            rewrite_expression(&mut node.condition, ctx);
+            rewrite_block(&mut node.body, ctx);
+        }
+        ExpressionKind::For(node) => {
+            rewrite_expression(&mut node.start, ctx);
+            rewrite_expression(&mut node.end, ctx);
+            rewrite_block(&mut node.body, ctx);
+        }
+    }
+}
+
+/// `math::square(2)` reads like the variant `square` of an enum `math`, so
+/// paths into modules are turned into calls of the function, or into the
+/// struct of a static method call like `geo::Point.new()`.
+fn rewrite_enum_init(node: &mut EnumInitNode, ctx: &mut Context) -> Option<ExpressionKind> {
+    for generic_param in &mut node.generic_params {
+        rewrite_type(generic_param, node.span, ctx);
+    }
+    for arg in &mut node.args {
+        rewrite_expression(arg, ctx);
+    }
+
+    let path = node
+        .enum_name
+        .split("::")
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let item = resolve_path(&path, node.span, ctx.current, true, ctx)?;
+    let ItemKind::Module(module) = item.kind else {
+        if !matches!(item.kind, ItemKind::TypeParameter) {
+            node.enum_name = item.name;
+        }
+        return None;
+    };
+
+    let item = member(module, &node.variant, node.span, ctx.current, ctx)?;
+    Some(match item.kind {
+        ItemKind::Struct | ItemKind::Enum if node.args.is_empty() => {
+            ExpressionKind::VarAccess(VarAccessNode {
+                name: item.name,
+                span: node.span,
+            })
+        }
+        _ => ExpressionKind::FuncCall(FuncCallNode {
+            name: item.name,
+            params: std::mem::take(&mut node.args),
+            generic_params: std::mem::take(&mut node.generic_params),
+            span: node.span,
+        }),
+    })
+}
+
+/// Rewrites the signature and body of a function or method, in a scope
+/// holding its type parameters.
+fn rewrite_function(node: &mut FuncDefNode, ctx: &mut Context) {
+    ctx.scopes.push(type_params(&node.generic_typing));
+    rewrite_generic_typing(&mut node.generic_typing, ctx);
+    for param in &mut node.params {
+        rewrite_type(&mut param.param_type, param.span, ctx);
+    }
+    if let Some(return_type) = &mut node.return_type {
+        rewrite_type(return_type, node.span, ctx);
+    }
+    rewrite_block(&mut node.body, ctx);
+    ctx.scopes.pop();
+}
+
+fn type_params(generic_typing: &Option<GenericTypingNode>) -> HashMap<String, Item> {
+    let params = generic_typing.iter().flat_map(|g| &g.params);
+    params
+        .map(|param| {
+            let item = Item {
+                kind: ItemKind::TypeParameter,
+                name: param.name.clone(),
+                public: true,
+                span: param.span,
+            };
+            (param.name.clone(), item)
+        })
+        .collect()
+}
+
+/// Rewrites the traits type parameters are bound by and the types of const
+/// parameters.
+fn rewrite_generic_typing(generic_typing: &mut Option<GenericTypingNode>, ctx: &mut Context) {
+    for param in generic_typing.iter_mut().flat_map(|g| &mut g.params) {
+        for bound in &mut param.bounds {
+            *bound = resolve_name(bound, param.span, ctx);
+        }
+        if let Some(const_type) = &mut param.const_type {
+            rewrite_type(const_type, param.span, ctx);
+        }
+    }
+}
+
+fn rewrite_type(value_type: &mut Type, span: Span, ctx: &mut Context) {
+    match value_type {
+        Type::Named(name) | Type::Dyn(name) => *name = resolve_name(name, span, ctx),
+        Type::Generic(name, args) => {
+            *name = resolve_name(name, span, ctx);
+            for arg in args {
+                rewrite_type(arg, span, ctx);
+            }
+        }
+        Type::Array(element, length) => {
+            rewrite_type(element, span, ctx);
+            rewrite_type(length, span, ctx);
+        }
+        _ => {}
+    }
+}
+
+fn rewrite_pattern(pattern: &mut PatternNode, ctx: &mut Context) {
+    match &mut pattern.kind {
+        PatternKind::Variant {
+            enum_name, fields, ..
+        } => {
+            if let Some(enum_name) = enum_name {
+                *enum_name = resolve_name(enum_name, pattern.span, ctx);
+            }
+            for field in fields {
+                rewrite_pattern(field, ctx);
+            }
+        }
+        PatternKind::Literal(value) | PatternKind::Comparison { value, .. } => {
+            rewrite_expression(value, ctx)
+        }
+        PatternKind::Wildcard | PatternKind::Binding { .. } => {}
+    }
+}
+
+/// Rewrites a block in a scope holding its items, which can be used before
+/// their definition like the items of a module. `use` declarations only
+/// import into the block.
+fn rewrite_block(block: &mut BlockNode, ctx: &mut Context) {
+    let mut scope = HashMap::new();
+    for expr in &block.expressions {
+        let (name, kind, span) = match &expr.kind {
+            ExpressionKind::FuncDef(node) => (&node.name, ItemKind::Function, node.span),
+            ExpressionKind::StructDef(node) => (&node.name, ItemKind::Struct, node.span),
+            ExpressionKind::EnumDef(node) => (&node.name, ItemKind::Enum, node.span),
+            ExpressionKind::TraitDef(node) => (&node.name, ItemKind::Trait, node.span),
+            _ => continue,
+        };
+        let item = Item {
+            kind,
+            name: ctx.qualify(name),
+            public: true,
+            span,
+        };
+        scope.insert(name.clone(), item);
+    }
+    ctx.scopes.push(scope);
+
+    block.expressions.retain(|expr| match &expr.kind {
+        ExpressionKind::Use(node) => {
+            if node.public {
+                block_item_error("`pub use`", node.span, ctx);
+            }
+            if let Some(item) = resolve_path(&node.path, node.span, ctx.current, true, ctx) {
+                let scope = ctx.scopes.last_mut().expect("the block's scope was pushed");
+                scope.insert(node.name().to_string(), item);
+            }
+            false
+        }
+        ExpressionKind::Module(node) => {
+            let diagnostic = Diagnostic::error("modules can't be declared inside a block")
+                .with_span(node.span)
+                .with_label("not at the top level of a file or module");
+            ctx.error(diagnostic);
+            false
+        }
+        _ => true,
+    });
+
+    for expr in &mut block.expressions {
+        let public = match &expr.kind {
+            ExpressionKind::FuncDef(node) => node.public,
+            ExpressionKind::StructDef(node) => node.public,
+            ExpressionKind::EnumDef(node) => node.public,
+            ExpressionKind::TraitDef(node) => node.public,
+            _ => false,
+        };
+        if public {
+            block_item_error("`pub` item", expr.span, ctx);
+        }
+        rewrite_expression(expr, ctx);
+    }
+
+    ctx.scopes.pop();
+}
+
+fn block_item_error(what: &str, span: Span, ctx: &mut Context) {
+    let diagnostic = Diagnostic::error(format!("{} inside a block", what))
+        .with_span(span)
+        .with_label("only visible in this block")
+        .with_note("only items of a module can be made visible to other modules");
+    ctx.error(diagnostic);
+}
@@ -0,0 +1,697 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast_nodes::{
+    block::BlockNode,
+    expression::{ExpressionKind, ExpressionNode},
+    func_call::FuncCallNode,
+    func_def::FuncDefNode,
+    match_expr::{PatternKind, PatternNode},
+    program::ProgramNode,
+    term::PlaceSegment,
+    trait_def::ImplBlockNode,
+    types::{Type, instance_name},
+};
+use crate::diagnostics::{Diagnostic, Span};
+
+/// Values of the type parameters of the instance being generated.
+type TypeArgs = HashMap<String, Type>;
+
+/// Something generated for a list of type arguments, told apart by the
+/// arguments themselves rather than by the C names made of them.
+#[derive(PartialEq, Eq, Hash)]
+enum Instance {
+    /// Generic function or struct or enum, with its type arguments.
+    Item(String, Vec<Type>),
+    /// Method of a generic struct, for the struct's type arguments.
+    Method(String, Vec<Type>, String),
+    /// Generic impl of a trait, for the type it is used with.
+    Impl(String, Type),
+}
+
+/// How deep type arguments may nest before instances are assumed to keep
+/// nesting forever, like a `List<T>` whose methods use `List<List<T>>`.
+const MAX_TYPE_DEPTH: usize = 32;
+
+#[derive(Default)]
+struct Scope {
+    /// Generic functions defined in the scope, by name.
+    generics: HashMap<String, FuncDefNode>,
+    /// Generic structs and enums defined in the scope, by name.
+    generic_types: HashMap<String, ExpressionNode>,
+    /// Generic impls defined in the scope, like `impl<T> ToString for Pair<T>`.
+    generic_impls: Vec<ImplBlockNode>,
+    /// Instances created so far.
+    instances: HashSet<Instance>,
+    /// Instances of functions and impls whose bodies still have to be
+    /// monomorphized, with the instance of the generic struct methods belong
+    /// to.
+    pending: Vec<(ExpressionNode, TypeArgs, Option<String>)>,
+    /// Monomorphized instances, which replace the generic definitions.
+    finished: Vec<ExpressionNode>,
+}
+
+struct Context {
+    scopes: Vec<Scope>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Context {
+    fn recursion_limit(&mut self, instance: &str, span: Span) {
+        // One report is enough, every deeper instance would repeat it.
+        if self.diagnostics.is_empty() {
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "reached the recursion limit while instantiating `{}`",
+                    instance
+                ))
+                .with_span(span)
+                .with_note("its instances keep nesting their type arguments deeper"),
+            );
+        }
+    }
+}
+
+/// Replaces every generic function, struct and enum by one instance per list
+/// of type arguments it is used with, and renames the calls and literals to
+/// their instance. Generic impls get an instance for every type their trait
+/// is used with. Runs after the type checker, which filled in the type
+/// arguments of every call and literal and the types inside the generic
+/// bodies, which are substituted here.
+pub fn monomorphize(program: &mut ProgramNode) -> Result<(), Vec<Diagnostic>> {
+    let mut ctx = Context {
+        scopes: vec![],
+        diagnostics: vec![],
+    };
+    monomorphize_expressions(&mut program.expressions, &TypeArgs::new(), &mut ctx);
+
+    if ctx.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(ctx.diagnostics)
+    }
+}
+
+/// Monomorphizes a sequence of expressions sharing one scope. The instances
+/// of the generic definitions in it are put at its start, where they don't
+/// change the value of a block.
+fn monomorphize_expressions(
+    expressions: &mut Vec<ExpressionNode>,
+    type_args: &TypeArgs,
+    ctx: &mut Context,
+) {
+    let mut scope = Scope::default();
+    expressions.retain(|expr| match &expr.kind {
+        ExpressionKind::FuncDef(node) if node.generic_typing.is_some() => {
+            scope.generics.insert(node.name.clone(), node.clone());
+            false
+        }
+        ExpressionKind::StructDef(node) if node.generic_typing.is_some() => {
+            scope.generic_types.insert(node.name.clone(), expr.clone());
+            false
+        }
+        ExpressionKind::EnumDef(node) if node.generic_typing.is_some() => {
+            scope.generic_types.insert(node.name.clone(), expr.clone());
+            false
+        }
+        ExpressionKind::ImplBlock(node) if node.generic_typing.is_some() => {
+            scope.generic_impls.push(node.clone());
+            false
+        }
+        _ => true,
+    });
+    ctx.scopes.push(scope);
+
+    for expr in expressions.iter_mut() {
+        monomorphize_expression(expr, type_args, ctx);
+    }
+
+    // Instances may call further generic functions, of this scope or of the
+    // ones around it.
+    while let Some((mut instance, instance_args, owner)) = current_scope(ctx).pending.pop() {
+        match &mut instance.kind {
+            ExpressionKind::FuncDef(node) => monomorphize_func_def(node, &instance_args, ctx),
+            ExpressionKind::ImplBlock(node) => {
+                for method in &mut node.methods {
+                    monomorphize_func_def(method, &instance_args, ctx);
+                }
+            }
+            _ => unreachable!("only functions and impls are pending"),
+        }
+
+        let finished = &mut current_scope(ctx).finished;
+        let (Some(owner), ExpressionKind::FuncDef(method)) = (owner, &instance.kind) else {
+            finished.push(instance);
+            continue;
+        };
+        let owner = finished.iter_mut().find_map(|expr| match &mut expr.kind {
+            ExpressionKind::StructDef(node) if node.name == owner => Some(node),
+            _ => None,
+        });
+        if let Some(owner) = owner {
+            owner.methods.push(method.clone());
+        }
+    }
+
+    let scope = ctx.scopes.pop().expect("pushed above");
+    expressions.splice(0..0, scope.finished);
+}
+
+fn current_scope(ctx: &mut Context) -> &mut Scope {
+    ctx.scopes
+        .last_mut()
+        .expect("a scope is pushed while monomorphizing")
+}
+
+fn monomorphize_block(block: &mut BlockNode, type_args: &TypeArgs, ctx: &mut Context) {
+    monomorphize_expressions(&mut block.expressions, type_args, ctx);
+}
+
+fn monomorphize_expression(expr: &mut ExpressionNode, type_args: &TypeArgs, ctx: &mut Context) {
+    // Const parameters become the number of the instance. The resolver made
+    // sure constants fit into an `i32`.
+    if let ExpressionKind::VarAccess(node) = &expr.kind
+        && let Some(Type::Const(value)) = type_args.get(&node.name)
+    {
+        expr.kind = ExpressionKind::IntLit((*value).into());
+    }
+
+    match &mut expr.kind {
+        ExpressionKind::VarDecl(node) => {
+            concrete_option(&mut node.var_type, type_args, ctx);
+            monomorphize_expression(&mut node.value, type_args, ctx);
+        }
+        ExpressionKind::Assignment(node) => {
+            for segment in &mut node.path {
+                if let PlaceSegment::Index(index) = segment {
+                    monomorphize_expression(index, type_args, ctx);
+                }
+            }
+            monomorphize_expression(&mut node.value, type_args, ctx);
+        }
+        ExpressionKind::BinaryExpr(node) => {
+            concrete_option(&mut node.result_type, type_args, ctx);
+            monomorphize_expression(&mut node.left, type_args, ctx);
+            monomorphize_expression(&mut node.right, type_args, ctx);
+        }
+        ExpressionKind::UnaryExpr(node) => {
+            monomorphize_expression(&mut node.operand, type_args, ctx)
+        }
+        ExpressionKind::FuncDef(node) => monomorphize_func_def(node, type_args, ctx),
+        ExpressionKind::ReturnExpr(node) => {
+            if let Some(expression) = &mut node.expression {
+                monomorphize_expression(expression, type_args, ctx);
+            }
+        }
+        ExpressionKind::FuncCall(node) => monomorphize_func_call(node, type_args, ctx),
+        ExpressionKind::MethodCall(node) => {
+            concrete_option(&mut node.receiver_type, type_args, ctx);
+            if let Some(receiver_type @ Type::Generic(name, args)) = &node.receiver_type {
+                match &node.trait_name {
+                    Some(trait_name) => instantiate_impl(trait_name, receiver_type, ctx),
+                    None => instantiate_method(name, args, &node.method, ctx),
+                }
+            }
+            monomorphize_expression(&mut node.object, type_args, ctx);
+            for arg in &mut node.args {
+                monomorphize_expression(arg, type_args, ctx);
+            }
+        }
+        ExpressionKind::StructDef(node) => {
+            for field in &mut node.fields {
+                field.field_type = concrete(&field.field_type, type_args, ctx);
+            }
+            for method in &mut node.methods {
+                monomorphize_func_def(method, type_args, ctx);
+            }
+        }
+        ExpressionKind::StructInit(node) => {
+            node.struct_name =
+                instance_type(&node.struct_name, &mut node.generic_params, type_args, ctx);
+            for field in &mut node.fields {
+                monomorphize_expression(&mut field.value, type_args, ctx);
+            }
+        }
+        ExpressionKind::StructFieldAccess(node) => {
+            monomorphize_expression(&mut node.object, type_args, ctx)
+        }
+        ExpressionKind::ArrayLit(node) => {
+            concrete_option(&mut node.array_type, type_args, ctx);
+            for element in &mut node.elements {
+                monomorphize_expression(element, type_args, ctx);
+            }
+        }
+        ExpressionKind::ArrayRepeat(node) => {
+            concrete_option(&mut node.array_type, type_args, ctx);
+            node.length = substitute(&node.length, type_args);
+            monomorphize_expression(&mut node.value, type_args, ctx);
+        }
+        ExpressionKind::Index(node) => {
+            monomorphize_expression(&mut node.object, type_args, ctx);
+            monomorphize_expression(&mut node.index, type_args, ctx);
+        }
+        ExpressionKind::EnumDef(node) => {
+            for field in node.variants.iter_mut().flat_map(|v| &mut v.fields) {
+                field.field_type = concrete(&field.field_type, type_args, ctx);
+            }
+        }
+        ExpressionKind::EnumInit(node) => {
+            node.enum_name =
+                instance_type(&node.enum_name, &mut node.generic_params, type_args, ctx);
+            for arg in &mut node.args {
+                monomorphize_expression(arg, type_args, ctx);
+            }
+        }
+        // Default methods of traits are generated as part of the impls, only
+        // the types in the vtable are needed.
+        ExpressionKind::TraitDef(node) => {
+            for method in &mut node.methods {
+                for param in &mut method.func.params {
+                    param.param_type = concrete(&param.param_type, type_args, ctx);
+                }
+                concrete_option(&mut method.func.return_type, type_args, ctx);
+            }
+        }
+        ExpressionKind::ImplBlock(node) => {
+            node.for_type = concrete(&node.for_type, type_args, ctx);
+            for method in &mut node.methods {
+                monomorphize_func_def(method, type_args, ctx);
+            }
+        }
+        ExpressionKind::Module(_) | ExpressionKind::Use(_) => {
+            unreachable!("modules are flattened before name resolution")
+        }
+        ExpressionKind::DynCoercion(node) => {
+            node.value_type = concrete(&node.value_type, type_args, ctx);
+            instantiate_impl(&node.trait_name, &node.value_type, ctx);
+            monomorphize_expression(&mut node.value, type_args, ctx);
+        }
+        ExpressionKind::If(node) => {
+            concrete_option(&mut node.value_type, type_args, ctx);
+            monomorphize_expression(&mut node.condition, type_args, ctx);
+            monomorphize_block(&mut node.then_block, type_args, ctx);
+            if let Some(else_block) = &mut node.else_block {
+                monomorphize_block(else_block, type_args, ctx);
+            }
+        }
+        ExpressionKind::Match(node) => {
+            concrete_option(&mut node.subject_type, type_args, ctx);
+            concrete_option(&mut node.value_type, type_args, ctx);
+            monomorphize_expression(&mut node.subject, type_args, ctx);
+            for arm in &mut node.arms {
+                monomorphize_pattern(&mut arm.pattern, type_args, ctx);
+                if let Some(guard) = &mut arm.guard {
+                    monomorphize_expression(guard, type_args, ctx);
+                }
+                monomorphize_block(&mut arm.body, type_args, ctx);
+            }
+        }
+        ExpressionKind::While(node) => {
+            monomorphize_expression(&mut node.condition, type_args, ctx);
+            monomorphize_block(&mut node.body, type_args, ctx);
+        }
+        ExpressionKind::For(node) => {
+            concrete_option(&mut node.var_type, type_args, ctx);
+            monomorphize_expression(&mut node.start, type_args, ctx);
+            monomorphize_expression(&mut node.end, type_args, ctx);
+            monomorphize_block(&mut node.body, type_args, ctx);
+        }
+        ExpressionKind::CImport(_)
+        | ExpressionKind::IntLit(_)
+        | ExpressionKind::FloatLit(_)
+        | ExpressionKind::StrLit(_)
+        | ExpressionKind::VarAccess(_)
+        | ExpressionKind::Break
+        | ExpressionKind::Continue => {}
+    }
+}
+
+fn monomorphize_pattern(pattern: &mut PatternNode, type_args: &TypeArgs, ctx: &mut Context) {
+    match &mut pattern.kind {
+        PatternKind::Binding { value_type, .. } => concrete_option(value_type, type_args, ctx),
+        PatternKind::Literal(value) | PatternKind::Comparison { value, .. } => {
+            monomorphize_expression(value, type_args, ctx)
+        }
+        PatternKind::Variant {
+            enum_type, fields, ..
+        } => {
+            concrete_option(enum_type, type_args, ctx);
+            for field in fields {
+                monomorphize_pattern(field, type_args, ctx);
+            }
+        }
+        PatternKind::Wildcard => {}
+    }
+}
+
+fn monomorphize_func_def(node: &mut FuncDefNode, type_args: &TypeArgs, ctx: &mut Context) {
+    for param in &mut node.params {
+        param.param_type = concrete(&param.param_type, type_args, ctx);
+    }
+    concrete_option(&mut node.return_type, type_args, ctx);
+    monomorphize_block(&mut node.body, type_args, ctx);
+}
+
+/// Renames a call of a generic function to the instance for its type
+/// arguments, creating the instance on first use.
+fn monomorphize_func_call(node: &mut FuncCallNode, type_args: &TypeArgs, ctx: &mut Context) {
+    for param in &mut node.params {
+        monomorphize_expression(param, type_args, ctx);
+    }
+
+    let call_args: Vec<Type> = node
+        .generic_params
+        .iter()
+        .map(|t| concrete(t, type_args, ctx))
+        .collect();
+
+    let Some(scope) = ctx
+        .scopes
+        .iter_mut()
+        .rev()
+        .find(|scope| scope.generics.contains_key(&node.name))
+    else {
+        return;
+    };
+
+    let name = instance_name(&node.name, &call_args);
+    if call_args.iter().any(|t| type_depth(t) > MAX_TYPE_DEPTH) {
+        let span = scope.generics[&node.name].span;
+        return ctx.recursion_limit(&node.name, span);
+    }
+
+    let key = Instance::Item(node.name.clone(), call_args.clone());
+    if scope.instances.insert(key) {
+        let mut instance = scope.generics[&node.name].clone();
+        let type_params = instance
+            .generic_typing
+            .take()
+            .map(|g| g.names())
+            .unwrap_or_default();
+        instance.name = name.clone();
+        scope.pending.push((
+            ExpressionNode {
+                span: instance.span,
+                kind: ExpressionKind::FuncDef(instance),
+            },
+            type_params.into_iter().zip(call_args).collect(),
+            None,
+        ));
+    }
+
+    node.name = name;
+    node.generic_params.clear();
+}
+
+/// Name of the struct or enum a literal creates a value of, which is the
+/// instance for its type arguments in case of a generic type.
+fn instance_type(
+    name: &str,
+    generic_params: &mut Vec<Type>,
+    type_args: &TypeArgs,
+    ctx: &mut Context,
+) -> String {
+    if generic_params.is_empty() {
+        return name.to_string();
+    }
+
+    let literal_args = std::mem::take(generic_params);
+    let literal_type = Type::Generic(name.to_string(), literal_args);
+    concrete(&literal_type, type_args, ctx).c_name()
+}
+
+/// Substitutes the type parameters in a type and creates the instances of
+/// the generic types it names.
+fn concrete(value_type: &Type, type_args: &TypeArgs, ctx: &mut Context) -> Type {
+    let value_type = substitute(value_type, type_args);
+    instantiate_type(&value_type, ctx);
+    value_type
+}
+
+fn concrete_option(value_type: &mut Option<Type>, type_args: &TypeArgs, ctx: &mut Context) {
+    if let Some(value_type) = value_type {
+        *value_type = concrete(value_type, type_args, ctx);
+    }
+}
+
+/// Creates the instance of a generic struct or enum for the given type
+/// arguments on first use. Instances are finished before the ones using
+/// them, as C needs the types of fields to be complete.
+fn instantiate_type(value_type: &Type, ctx: &mut Context) {
+    let (name, args) = match value_type {
+        Type::Generic(name, args) => (name, args),
+        Type::Array(element, _) => return instantiate_type(element, ctx),
+        _ => return,
+    };
+    for arg in args {
+        instantiate_type(arg, ctx);
+    }
+
+    let instance_name = value_type.c_name();
+    let Some(scope_index) = ctx
+        .scopes
+        .iter()
+        .rposition(|scope| scope.generic_types.contains_key(name))
+    else {
+        return;
+    };
+    let scope = &mut ctx.scopes[scope_index];
+    let mut instance = scope.generic_types[name].clone();
+
+    if type_depth(value_type) > MAX_TYPE_DEPTH {
+        return ctx.recursion_limit(name, instance.span);
+    }
+    if !scope
+        .instances
+        .insert(Instance::Item(name.clone(), args.clone()))
+    {
+        return;
+    }
+
+    match &mut instance.kind {
+        ExpressionKind::StructDef(node) => {
+            let type_params = node.generic_typing.take().map(|g| g.names());
+            let type_args = type_params.unwrap_or_default().into_iter();
+            let type_args: TypeArgs = type_args.zip(args.iter().cloned()).collect();
+
+            node.name = instance_name;
+            for field in &mut node.fields {
+                field.field_type = concrete(&field.field_type, &type_args, ctx);
+            }
+            // Like generic functions, methods are only instantiated when
+            // called, as they may not work for every type argument.
+            node.methods.clear();
+        }
+        ExpressionKind::EnumDef(node) => {
+            let type_params = node.generic_typing.take().map(|g| g.names());
+            let type_args = type_params.unwrap_or_default().into_iter();
+            let type_args: TypeArgs = type_args.zip(args.iter().cloned()).collect();
+
+            node.name = instance_name;
+            for field in node.variants.iter_mut().flat_map(|v| &mut v.fields) {
+                field.field_type = concrete(&field.field_type, &type_args, ctx);
+            }
+        }
+        _ => unreachable!("only structs and enums are generic types"),
+    }
+
+    ctx.scopes[scope_index].finished.push(instance);
+}
+
+/// Creates the instance of a method of a generic struct for the struct's type
+/// arguments on its first call.
+fn instantiate_method(name: &str, args: &[Type], method: &str, ctx: &mut Context) {
+    let owner = Type::Generic(name.to_string(), args.to_vec());
+    // The struct itself hit the recursion limit, which was reported.
+    if type_depth(&owner) > MAX_TYPE_DEPTH {
+        return;
+    }
+
+    let Some(scope) = ctx
+        .scopes
+        .iter_mut()
+        .rev()
+        .find(|scope| scope.generic_types.contains_key(name))
+    else {
+        return;
+    };
+    let ExpressionKind::StructDef(generic) = &scope.generic_types[name].kind else {
+        return;
+    };
+
+    let key = Instance::Method(name.to_string(), args.to_vec(), method.to_string());
+    if !scope.instances.insert(key) {
+        return;
+    }
+    let owner = owner.c_name();
+    let Some(instance) = generic.methods.iter().find(|m| m.name == method) else {
+        return;
+    };
+
+    let type_params = generic.generic_typing.iter().flat_map(|g| g.names());
+    let type_args = type_params.zip(args.iter().cloned()).collect();
+    let instance = ExpressionNode {
+        span: instance.span,
+        kind: ExpressionKind::FuncDef(instance.clone()),
+    };
+    scope.pending.push((instance, type_args, Some(owner)));
+}
+
+/// Creates the instance of the generic impl of a trait applying to an
+/// instance of a generic type on first use, with all methods, as its vtable
+/// needs them.
+fn instantiate_impl(trait_name: &str, for_type: &Type, ctx: &mut Context) {
+    if type_depth(for_type) > MAX_TYPE_DEPTH {
+        return;
+    }
+
+    // Impls for single instances aren't generic, so at most one applies.
+    let found = ctx.scopes.iter_mut().rev().find_map(|scope| {
+        let generic = scope.generic_impls.iter().find_map(|generic| {
+            let type_params = generic.generic_typing.as_ref()?.names();
+            let mut type_args = TypeArgs::new();
+            let applies = generic.trait_name == trait_name
+                && match_type(&generic.for_type, for_type, &type_params, &mut type_args);
+            applies.then(|| (generic.clone(), type_args))
+        })?;
+        Some((scope, generic))
+    });
+    let Some((scope, (mut instance, type_args))) = found else {
+        return;
+    };
+
+    if !scope
+        .instances
+        .insert(Instance::Impl(trait_name.to_string(), for_type.clone()))
+    {
+        return;
+    }
+    instance.generic_typing = None;
+    instance.for_type = for_type.clone();
+    let instance = ExpressionNode {
+        span: instance.span,
+        kind: ExpressionKind::ImplBlock(instance),
+    };
+    scope.pending.push((instance, type_args, None));
+}
+
+/// Matches the type a generic impl is for against a type, collecting the
+/// values of the impl's type parameters.
+fn match_type(
+    pattern: &Type,
+    matched: &Type,
+    type_params: &[String],
+    type_args: &mut TypeArgs,
+) -> bool {
+    match (pattern, matched) {
+        (Type::Named(name), _) if type_params.contains(name) => {
+            match type_args.insert(name.clone(), matched.clone()) {
+                Some(previous) => previous == *matched,
+                None => true,
+            }
+        }
+        (Type::Generic(name, args), Type::Generic(matched_name, matched_args)) => {
+            name == matched_name
+                && args.len() == matched_args.len()
+                && args
+                    .iter()
+                    .zip(matched_args)
+                    .all(|(arg, matched)| match_type(arg, matched, type_params, type_args))
+        }
+        (Type::Array(element, length), Type::Array(matched_element, matched_length)) => {
+            match_type(element, matched_element, type_params, type_args)
+                && match_type(length, matched_length, type_params, type_args)
+        }
+        _ => pattern == matched,
+    }
+}
+
+/// How deeply type arguments are nested in a type, `Option<Pair<i32>>` has
+/// depth 2.
+fn type_depth(value_type: &Type) -> usize {
+    match value_type {
+        Type::Generic(_, args) => 1 + args.iter().map(type_depth).max().unwrap_or(0),
+        Type::Array(element, _) => type_depth(element),
+        _ => 0,
+    }
+}
+
+fn substitute(value_type: &Type, type_args: &TypeArgs) -> Type {
+    match value_type {
+        Type::Named(name) => type_args
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| value_type.clone()),
+        Type::Generic(name, args) => Type::Generic(
+            name.clone(),
+            args.iter().map(|arg| substitute(arg, type_args)).collect(),
+        ),
+        Type::Array(element, length) => Type::Array(
+            Box::new(substitute(element, type_args)),
+            Box::new(substitute(length, type_args)),
+        ),
+        _ => value_type.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_builder::build_ast_from_pairs;
+    use crate::modules::resolve_modules;
+    use crate::parser::parse_program;
+    use crate::resolver::resolve_names;
+    use crate::type_checker::check_types;
+
+    /// Names of the functions, structs and enums of `source` once it is
+    /// monomorphized.
+    fn item_names(source: &str) -> Vec<String> {
+        let pair = parse_program(source, 0).expect("source parses");
+        let mut program = build_ast_from_pairs(pair, 0).expect("source builds");
+        resolve_modules(&mut program).expect("modules resolve");
+        resolve_names(&program).expect("names resolve");
+        check_types(&mut program).expect("types check");
+        monomorphize(&mut program).expect("instances are created");
+
+        let mut names: Vec<_> = program
+            .expressions
+            .iter()
+            .filter_map(|expr| match &expr.kind {
+                ExpressionKind::FuncDef(node) => Some(node.name.clone()),
+                ExpressionKind::StructDef(node) => Some(node.name.clone()),
+                ExpressionKind::EnumDef(node) => Some(node.name.clone()),
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn instance_names_tell_type_arguments_apart() {
+        let source = "struct A = { v: i32 }\nstruct B_C = { v: i32 }\nstruct A_B = { v: i32 }\nstruct C = { v: i32 }\nfn f<T, U>(x: T, y: U) {}\nf(A{ v: 1 }, B_C{ v: 2 })\nf(A_B{ v: 3 }, C{ v: 4 })\n";
+        assert_eq!(
+            item_names(source),
+            ["A", "A_B", "B_C", "C", "f__1_A__3_B_C", "f__3_A_B__1_C"]
+        );
+    }
+
+    #[test]
+    fn instance_names_differ_from_declared_names() {
+        let source = "fn id<T>(x: T) T { return x }\nfn id_i32(x: i32) i32 { return x }\na := id(1)\nb := id_i32(1)\n";
+        assert_eq!(item_names(source), ["id__3_i32", "id_i32"]);
+    }
+
+    #[test]
+    fn instance_names_tell_nested_type_arguments_apart() {
+        let source = "struct Pair<T> = { a: T }\nstruct Pair_i32 = { a: i32 }\nenum Option<T> = {\n    Some(T),\n    None\n}\nx := Pair<i32>{ a: 1 }\no := Option::Some(Pair_i32{ a: 2 })\np := Option::Some(x)\n";
+        assert_eq!(
+            item_names(source),
+            [
+                "Option__11_Pair__3_i32",
+                "Option__8_Pair_i32",
+                "Pair__3_i32",
+                "Pair_i32"
+            ]
+        );
+    }
+}
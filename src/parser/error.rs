@@ -0,0 +1,272 @@
+use super::{FusionParser, Rule};
+use crate::diagnostics::{Diagnostic, Span};
+use pest::Parser;
+use pest::error::{Error, ErrorVariant, InputLocation};
+
+/// Human readable name of a grammar rule, used in error messages.
+pub fn rule_name(rule: Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "end of file",
+        Rule::program => "program",
+        Rule::block => "block",
+        Rule::ident | Rule::field_ident | Rule::path | Rule::enum_path => "identifier",
+        Rule::expression => "expression",
+        Rule::operation | Rule::condition => "expression",
+        Rule::var_access => "variable",
+        Rule::return_expr => "return expression",
+        Rule::negate => "`-`",
+        Rule::not => "`!`",
+        Rule::add => "`+`",
+        Rule::subtract => "`-`",
+        Rule::power => "`**`",
+        Rule::multiply => "`*`",
+        Rule::divide => "`/`",
+        Rule::modulo => "`%`",
+        Rule::and => "`&&`",
+        Rule::or => "`||`",
+        Rule::equal => "`==`",
+        Rule::greater_than => "`>`",
+        Rule::less_than => "`<`",
+        Rule::unequal => "`!=`",
+        Rule::greater_or_equal => "`>=`",
+        Rule::less_or_equal => "`<=`",
+        Rule::int_lit => "integer literal",
+        Rule::str_lit | Rule::inner_string => "string literal",
+        Rule::float_lit => "float literal",
+        Rule::func_def => "function definition",
+        Rule::generic_typing => "generic parameter list",
+        Rule::type_param => "type parameter",
+        Rule::const_param => "const parameter",
+        Rule::const_kw => "`const`",
+        Rule::return_type => "return type",
+        Rule::param_def_list => "parameter list",
+        Rule::self_param => "`&self`",
+        Rule::self_kw => "`self`",
+        Rule::func_call => "function call",
+        Rule::generic_params => "generic argument list",
+        Rule::param_list => "argument list",
+        Rule::parameter_seperator => "`,`",
+        Rule::struct_def => "struct definition",
+        Rule::struct_def_content => "struct fields",
+        Rule::field_def | Rule::struct_field_def => "field definition",
+        Rule::struct_init => "struct literal",
+        Rule::struct_field_init => "field initializer",
+        Rule::field_access => "field access",
+        Rule::method_call => "method call",
+        Rule::index => "index",
+        Rule::array_lit | Rule::array_repeat => "array",
+        Rule::array_type => "array type",
+        Rule::enum_def => "enum definition",
+        Rule::enum_def_content => "enum variants",
+        Rule::enum_variant => "enum variant",
+        Rule::variant_fields | Rule::variant_types => "variant fields",
+        Rule::enum_init => "enum variant",
+        Rule::type_alias => "type alias",
+        Rule::var_decl => "variable declaration",
+        Rule::let_kw => "`let`",
+        Rule::mut_kw => "`mut`",
+        Rule::var_type => "type annotation",
+        Rule::as_kw => "`as`",
+        Rule::assignment => "assignment",
+        Rule::if_expr => "`if` expression",
+        Rule::while_expr => "`while` loop",
+        Rule::for_expr => "`for` loop",
+        Rule::break_expr => "`break`",
+        Rule::return_kw => "`return`",
+        Rule::continue_expr => "`continue`",
+        Rule::if_kw => "`if`",
+        Rule::else_kw => "`else`",
+        Rule::while_kw => "`while`",
+        Rule::for_kw => "`for`",
+        Rule::in_kw => "`in`",
+        Rule::match_expr => "`match` expression",
+        Rule::match_arm => "match arm",
+        Rule::match_guard => "match guard",
+        Rule::pattern
+        | Rule::comparison_pattern
+        | Rule::wildcard_pattern
+        | Rule::literal_pattern
+        | Rule::variant_pattern
+        | Rule::binding_pattern => "pattern",
+        Rule::pattern_list => "variant patterns",
+        Rule::match_kw => "`match`",
+        Rule::dyn_type => "type",
+        Rule::generic_type => "type",
+        Rule::dyn_kw => "`dyn`",
+        Rule::trait_def => "trait definition",
+        Rule::trait_method => "trait method",
+        Rule::impl_block => "`impl` block",
+        Rule::trait_kw => "`trait`",
+        Rule::impl_kw => "`impl`",
+        Rule::mod_decl => "module declaration",
+        Rule::use_decl => "`use` declaration",
+        Rule::pub_kw => "`pub`",
+        Rule::mod_kw => "`mod`",
+        Rule::use_kw => "`use`",
+        Rule::c_import => "`_c_import`",
+        _ => "token",
+    }
+}
+
+/// Literal token pest expected, `None` for ones not worth mentioning.
+fn token_name(token: String) -> Option<String> {
+    match token.as_str() {
+        // Implicit whitespace and comments are allowed everywhere.
+        " " | "\t" | "//" | "\r\n" | "BUILTIN_RULE" => None,
+        // `_` continuing an identifier. A lone `_` is a pattern, which the
+        // rules expecting one already say.
+        "_" => None,
+        "\n" => Some("newline".to_string()),
+        // Character ranges like `a..z` from identifiers and digits.
+        range if range.chars().count() == 4 && range.contains("..") => None,
+        token => Some(format!("`{}`", token)),
+    }
+}
+
+/// Name of a rule in the list of what a parse expected. Binary operators
+/// are summed up, there are too many to list.
+fn expected_name(rule: Rule) -> &'static str {
+    match rule {
+        Rule::add
+        | Rule::subtract
+        | Rule::power
+        | Rule::multiply
+        | Rule::divide
+        | Rule::modulo
+        | Rule::and
+        | Rule::or
+        | Rule::equal
+        | Rule::greater_than
+        | Rule::less_than
+        | Rule::unequal
+        | Rule::greater_or_equal
+        | Rule::less_or_equal => "operator",
+        rule => rule_name(rule),
+    }
+}
+
+/// Tokens `rule` may start with, those a parse of it expects on an empty
+/// input. Empty for rules matching nothing.
+fn first_tokens(rule: Rule) -> Vec<String> {
+    let Err(error) = FusionParser::parse(rule, "") else {
+        return vec![];
+    };
+    let attempts = error.parse_attempts();
+    let tokens = attempts
+        .iter()
+        .flat_map(|attempts| attempts.expected_tokens());
+    tokens.map(|token| token.to_string()).collect()
+}
+
+/// Whether every token `inner` may start with may start `outer` too.
+fn starts(outer: Rule, inner: Rule) -> bool {
+    let outer = first_tokens(outer);
+    let inner = first_tokens(inner);
+    !inner.is_empty() && inner.iter().all(|token| outer.contains(token))
+}
+
+/// Turns a pest error of a parse that started at byte `offset` of `source`
+/// into a diagnostic, e.g. "expected `)` in parameter list".
+pub fn to_diagnostic(error: &Error<Rule>, source: &str, offset: usize) -> Diagnostic {
+    let attempts = error.parse_attempts();
+
+    // The furthest position pest tried to match a token at is where the
+    // input actually stopped making sense.
+    let start = match (&attempts, &error.location) {
+        (Some(attempts), _) => attempts.max_position,
+        (None, InputLocation::Pos(pos)) => *pos,
+        (None, InputLocation::Span((start, _))) => *start,
+    } + offset;
+
+    let found = match source[start..].chars().next() {
+        None => "end of file".to_string(),
+        Some('\n') | Some('\r') => "end of line".to_string(),
+        Some(c) => format!("`{}`", c),
+    };
+    let end = start + source[start..].chars().next().map_or(0, char::len_utf8);
+
+    let positives = match &error.variant {
+        ErrorVariant::ParsingError { positives, .. } => positives.clone(),
+        ErrorVariant::CustomError { .. } => vec![],
+    };
+    let mut rules: Vec<Rule> = positives
+        .iter()
+        .copied()
+        .filter(|rule| !matches!(rule, Rule::EOI))
+        .collect();
+    let mut tokens = vec![];
+    let mut context = None;
+
+    if let Some(attempts) = attempts {
+        tokens = attempts
+            .expected_tokens()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        context = attempts
+            .call_stacks()
+            .iter()
+            .find_map(|stack| stack.parent)
+            .filter(|rule| !matches!(rule, Rule::program | Rule::expression));
+
+        // Pest reports a rule with many failed alternatives, like an
+        // expression, as a whole. It was expected here if everything tried
+        // here could start it.
+        for stack in attempts.call_stacks() {
+            if let Some(&rule) = stack.deepest.get_rule()
+                && !rules.contains(&rule)
+                && !rules.is_empty()
+                && rules.iter().all(|p| starts(rule, *p))
+            {
+                rules.push(rule);
+            }
+        }
+    }
+
+    // Only the highest-level rules are named, not the rules and tokens they
+    // start with, so `b := a +` expects an expression instead of every token
+    // an expression may start with.
+    let firsts: Vec<_> = rules.iter().map(|rule| first_tokens(*rule)).collect();
+    let is_covered = |first: &Vec<String>| {
+        !first.is_empty()
+            && firsts
+                .iter()
+                .any(|other| other.len() > first.len() && first.iter().all(|t| other.contains(t)))
+    };
+    let mut expected: Vec<String> = tokens
+        .into_iter()
+        .filter(|token| !firsts.iter().any(|first| first.contains(token)))
+        .filter_map(token_name)
+        .collect();
+    expected.extend(
+        rules
+            .iter()
+            .zip(&firsts)
+            .filter(|(_, first)| !is_covered(first))
+            .map(|(rule, _)| expected_name(*rule).to_string()),
+    );
+
+    expected.dedup();
+    let mut seen = vec![];
+    expected.retain(|e| {
+        let new = !seen.contains(e);
+        seen.push(e.clone());
+        new
+    });
+
+    let mut message = match expected.as_slice() {
+        [] => format!("unexpected {}", found),
+        [only] => format!("expected {}", only),
+        [init @ .., last] => format!("expected {} or {}", init.join(", "), last),
+    };
+    if let Some(context) = context {
+        message += &format!(" in {}", rule_name(context));
+    }
+
+    Diagnostic::error(message)
+        .with_span(Span {
+            start,
+            end: end.max(start),
+        })
+        .with_label(format!("found {}", found))
+}
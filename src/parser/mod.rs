@@ -1,5 +1,204 @@
+mod error;
+
+use crate::diagnostics::{Diagnostic, Span};
+use pest::Parser;
 use pest_derive::Parser;
 
+pub use error::rule_name;
+
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct FusionParser;
+
+pub type Pair<'a> = pest::iterators::Pair<'a, Rule>;
+
+/// Parses a whole source file into its `program` pair. The spans of errors
+/// are moved by `base`, where the file starts in the source map.
+///
+/// On a syntax error parsing resumes at the next expression separator on the
+/// top level, so every broken statement of a file is reported in one run.
+pub fn parse_program(source: &str, base: usize) -> Result<Pair<'_>, Vec<Diagnostic>> {
+    // Tracking what was expected where slows parsing down a lot, so it is
+    // only done to describe the errors of a file that failed to parse.
+    pest::set_error_detail(false);
+    if let Ok(mut pairs) = FusionParser::parse(Rule::program, source) {
+        return Ok(pairs.next().expect("program always produces a pair"));
+    }
+
+    pest::set_error_detail(true);
+    let first_error = match FusionParser::parse(Rule::program, source) {
+        Ok(mut pairs) => return Ok(pairs.next().expect("program always produces a pair")),
+        Err(e) => e,
+    };
+
+    let resume_points = top_level_separators(source);
+    let mut diagnostics = vec![];
+    let mut error = first_error;
+    let mut offset = 0;
+
+    loop {
+        let mut diagnostic = error::to_diagnostic(&error, source, offset);
+        let failed_at = diagnostic.span.map(|s| s.start).unwrap_or(source.len());
+        diagnostic.span = diagnostic.span.map(|span| Span {
+            start: base + span.start,
+            end: base + span.end,
+        });
+        diagnostics.push(diagnostic);
+
+        let Some(&resume) = resume_points.iter().find(|&&p| p > failed_at) else {
+            break;
+        };
+
+        match FusionParser::parse(Rule::program, &source[resume..]) {
+            Ok(_) => break,
+            Err(e) => {
+                error = e;
+                offset = resume;
+            }
+        }
+    }
+
+    Err(diagnostics)
+}
+
+/// Byte offsets directly after every newline or `;` that isn't nested in
+/// brackets, a string or a comment. Lines starting in the first column are
+/// treated as top level too, so an unclosed bracket doesn't swallow the rest
+/// of the file.
+fn top_level_separators(source: &str) -> Vec<usize> {
+    let mut points = vec![];
+    let mut depth: i32 = 0;
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => depth -= 1,
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            }
+            '\n' | ';' if depth <= 0 => points.push(i + 1),
+            '\n' if chars
+                .peek()
+                .is_some_and(|&(_, c)| !c.is_whitespace() && !"})]".contains(c)) =>
+            {
+                points.push(i + 1)
+            }
+            _ => {}
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        let diagnostics = parse_program(source, 0).expect_err("source has syntax errors");
+        diagnostics.into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn separators_skip_nested_brackets_strings_and_comments() {
+        let source = "a := f(1,\n    2)\nb := \"x;\ny\" // c;\nc := 1; d := 2";
+        let points = top_level_separators(source);
+        let starts: Vec<_> = points.iter().map(|&p| &source[p..]).collect();
+
+        assert_eq!(
+            starts,
+            [
+                "b := \"x;\ny\" // c;\nc := 1; d := 2",
+                "c := 1; d := 2",
+                " d := 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn separators_resume_at_first_column_inside_unclosed_bracket() {
+        let source = "fn f() {\n    a := (1\n}\nb := 2";
+        let points = top_level_separators(source);
+
+        assert!(points.contains(&source.find("b :=").unwrap()));
+        assert!(!points.contains(&source.find("a :=").unwrap()));
+    }
+
+    #[test]
+    fn recovery_reports_every_broken_statement() {
+        let source = "a := 1 +\nb := 2\nc := (3\nd := 4\n";
+        let diagnostics = parse_program(source, 0).unwrap_err();
+        let starts: Vec<_> = diagnostics
+            .iter()
+            .map(|d| d.span.expect("syntax errors have a span").start)
+            .collect();
+
+        let ends_of_broken_lines = [
+            source.find(" +\n").unwrap() + 2,
+            source.find("(3").unwrap() + 2,
+        ];
+        assert_eq!(starts, ends_of_broken_lines);
+    }
+
+    #[test]
+    fn missing_operand_expects_an_expression() {
+        assert_eq!(messages("b := a +\n"), ["expected expression"]);
+    }
+
+    #[test]
+    fn unclosed_call_lists_separators_and_operators() {
+        assert_eq!(
+            messages("z := f(1 2\n"),
+            ["expected `)`, `,`, operator, field access, method call or index"]
+        );
+    }
+
+    #[test]
+    fn identifier_only_characters_are_not_listed() {
+        for source in ["enum E = { }\n", "a: i32\n"] {
+            let [message] = messages(source).try_into().unwrap();
+            assert!(!message.contains('*'), "{}", message);
+            assert!(!message.contains("`_`"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn operators_need_no_spaces_around_them() {
+        let pair = parse_program("x := a*2 + a**2\n", 0).expect("source parses");
+        let rules: Vec<_> = pair.into_inner().flatten().map(|p| p.as_rule()).collect();
+        assert!(rules.contains(&Rule::multiply), "{:?}", rules);
+        assert!(rules.contains(&Rule::power), "{:?}", rules);
+    }
+
+    #[test]
+    fn error_spans_point_into_the_source_map() {
+        let source = "x := 1\ny := (\nz := 2 +\n";
+        let spans = |base| {
+            let diagnostics = parse_program(source, base).unwrap_err();
+            diagnostics
+                .iter()
+                .map(|d| d.span.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let moved: Vec<_> = spans(0)
+            .into_iter()
+            .map(|s| Span {
+                start: s.start + 40,
+                end: s.end + 40,
+            })
+            .collect();
+        assert_eq!(spans(40), moved);
+    }
+}
@@ -0,0 +1,381 @@
+use crate::ast_nodes::{
+    expression::{ExpressionKind, ExpressionNode},
+    module::ModuleNode,
+    program::ProgramNode,
+};
+use crate::codegen::Target;
+use crate::diagnostics::{Diagnostic, SourceMap, Span};
+use crate::modules::{load_items, parse_source};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+pub const MANIFEST_NAME: &str = "fusion.toml";
+
+/// Contents of a `fusion.toml`, e.g.
+///
+/// ```toml
+/// [project]
+/// name = "server"
+/// entry = "src/main.fu"
+/// kind = "executable"
+/// source_dirs = ["src"]
+///
+/// [c]
+/// libraries = ["curl"]
+/// include_paths = ["vendor/include"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub project: ProjectSection,
+    #[serde(default)]
+    pub c: CSection,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSection {
+    pub name: String,
+    /// File with the top-level statements of the program, relative to the
+    /// project root.
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    #[serde(default)]
+    pub kind: Target,
+    /// Directories every `.fu` file of is part of the project.
+    #[serde(default = "default_source_dirs")]
+    pub source_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CSection {
+    /// Libraries linked with `-l`.
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// Directories searched for the headers of `_c_import` with `-I`.
+    #[serde(default)]
+    pub include_paths: Vec<PathBuf>,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("src/main.fu")
+}
+
+fn default_source_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+/// A project, with its paths relative to the directory of its manifest.
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Loads the manifest in `dir` or the closest directory above it.
+    pub fn find(dir: &Path, sources: &mut SourceMap) -> Result<Project, Vec<Diagnostic>> {
+        match dir
+            .ancestors()
+            .find(|dir| dir.join(MANIFEST_NAME).is_file())
+        {
+            Some(root) => Project::load(&root.join(MANIFEST_NAME), sources),
+            None => Err(vec![
+                Diagnostic::error(format!(
+                    "could not find `{}` in `{}` or any parent directory",
+                    MANIFEST_NAME,
+                    dir.display()
+                ))
+                .with_note("pass the manifest with `--manifest-path`"),
+            ]),
+        }
+    }
+
+    pub fn load(path: &Path, sources: &mut SourceMap) -> Result<Project, Vec<Diagnostic>> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            vec![Diagnostic::error(format!(
+                "couldn't read `{}`: {}",
+                path.display(),
+                e
+            ))]
+        })?;
+        let base = sources.add(path.display().to_string(), content.as_str());
+
+        let manifest: Manifest = toml::from_str(&content).map_err(|e| {
+            let mut diagnostic = Diagnostic::error("invalid project manifest");
+            if let Some(range) = e.span() {
+                diagnostic = diagnostic.with_span(Span {
+                    start: base + range.start,
+                    end: base + range.end,
+                });
+            }
+            vec![diagnostic.with_label(e.message())]
+        })?;
+
+        let root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(Project { root, manifest })
+    }
+
+    /// File the project is built to, `build/<name>` for an executable and
+    /// `build/lib<name>.a` for a library.
+    pub fn output(&self) -> PathBuf {
+        let name = &self.manifest.project.name;
+        self.root
+            .join("build")
+            .join(match self.manifest.project.kind {
+                Target::Executable => name.clone(),
+                Target::Library => format!("lib{}.a", name),
+            })
+    }
+
+    pub fn include_paths(&self) -> Vec<PathBuf> {
+        let paths = self.manifest.c.include_paths.iter();
+        paths.map(|path| self.root.join(path)).collect()
+    }
+
+    /// Parses the entry file with the modules it declares, then every other
+    /// file of the source directories as a public module named after its
+    /// path, so `src/net/http.fu` is `net::http` even if no file declares
+    /// it.
+    pub fn load_program(&self, sources: &mut SourceMap) -> Result<ProgramNode, Vec<Diagnostic>> {
+        let entry = self.root.join(&self.manifest.project.entry);
+        info!(project = %self.manifest.project.name, entry = %entry.display(), "loading project");
+        let mut expressions = read_file(&entry, sources)?;
+
+        let mut diagnostics = vec![];
+        let dir = entry.parent().unwrap_or(Path::new("."));
+        load_items(&mut expressions, dir, sources, &mut diagnostics);
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        for (path, file) in self.module_files()? {
+            if is_loaded(&file, sources) {
+                continue;
+            }
+            debug!(module = %path.join("::"), path = %file.display(), "discovered module");
+            let mut items = read_file(&file, sources)?;
+            let span = last_file_span(sources);
+
+            let dir = match file.file_stem() {
+                Some(stem) if stem == "mod" => {
+                    file.parent().unwrap_or(Path::new(".")).to_path_buf()
+                }
+                _ => file.with_extension(""),
+            };
+            load_items(&mut items, &dir, sources, &mut diagnostics);
+            if !diagnostics.is_empty() {
+                return Err(diagnostics);
+            }
+
+            let (name, parents) = path.split_last().expect("a module path is never empty");
+            module_items(&mut expressions, parents, span).push(module(name, items, span));
+        }
+
+        Ok(ProgramNode { expressions })
+    }
+
+    /// `.fu` files of the source directories with the path of the module
+    /// they are, parents first.
+    fn module_files(&self) -> Result<Vec<(Vec<String>, PathBuf)>, Vec<Diagnostic>> {
+        let mut files = vec![];
+        let mut diagnostics = vec![];
+
+        for source_dir in &self.manifest.project.source_dirs {
+            let source_dir = self.root.join(source_dir);
+            if !source_dir.is_dir() {
+                diagnostics.push(Diagnostic::error(format!(
+                    "source directory `{}` does not exist",
+                    source_dir.display()
+                )));
+                continue;
+            }
+
+            for file in fu_files(&source_dir) {
+                let relative = file.strip_prefix(&source_dir).unwrap_or(&file);
+                let mut path: Vec<String> = relative
+                    .with_extension("")
+                    .iter()
+                    .map(|segment| segment.to_string_lossy().into_owned())
+                    .collect();
+                if path.last().is_some_and(|name| name == "mod") {
+                    path.pop();
+                }
+
+                match path.iter().find(|segment| !is_identifier(segment)) {
+                    Some(segment) => diagnostics.push(
+                        Diagnostic::error(format!("`{}` is not a valid module name", segment))
+                            .with_note(format!(
+                                "the path of `{}` is used as the path of its module",
+                                file.display()
+                            )),
+                    ),
+                    None if path.is_empty() => {}
+                    None => files.push((path, file)),
+                }
+            }
+        }
+
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+        files.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        Ok(files)
+    }
+}
+
+fn read_file(path: &Path, sources: &mut SourceMap) -> Result<Vec<ExpressionNode>, Vec<Diagnostic>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        vec![Diagnostic::error(format!(
+            "couldn't read `{}`: {}",
+            path.display(),
+            e
+        ))]
+    })?;
+    parse_source(path, content, sources)
+}
+
+/// Span of the whole file added to the source map last.
+fn last_file_span(sources: &SourceMap) -> Span {
+    let file = sources.files.last().expect("a file was just added");
+    Span {
+        start: file.base,
+        end: file.base + file.content.len(),
+    }
+}
+
+fn is_loaded(path: &Path, sources: &SourceMap) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    let mut names = sources
+        .files
+        .iter()
+        .map(|file| fs::canonicalize(&file.name));
+    names.any(|name| name.is_ok_and(|name| name == path))
+}
+
+/// All `.fu` files in `dir` and its subdirectories.
+fn fu_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(fu_files(&path));
+        } else if path.extension().is_some_and(|extension| extension == "fu") {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn module(name: &str, items: Vec<ExpressionNode>, span: Span) -> ExpressionNode {
+    ExpressionNode {
+        kind: ExpressionKind::Module(ModuleNode {
+            name: name.to_string(),
+            public: true,
+            items: Some(items),
+            span,
+        }),
+        span,
+    }
+}
+
+/// Items of the module at `path` below `items`, creating the modules that
+/// have no file of their own, like `net` for `src/net/http.fu`.
+fn module_items<'a>(
+    mut items: &'a mut Vec<ExpressionNode>,
+    path: &[String],
+    span: Span,
+) -> &'a mut Vec<ExpressionNode> {
+    for name in path {
+        let position = items.iter().position(
+            |item| matches!(&item.kind, ExpressionKind::Module(module) if &module.name == name),
+        );
+        let index = position.unwrap_or_else(|| {
+            items.push(module(name, vec![], span));
+            items.len() - 1
+        });
+        let ExpressionKind::Module(module) = &mut items[index].kind else {
+            unreachable!("the item was found as a module");
+        };
+        items = module.items.get_or_insert_with(Vec::new);
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::TempDir;
+
+    /// Project in a temporary directory holding `files`, all empty.
+    fn project(files: &[&str]) -> (TempDir, Project) {
+        let dir = TempDir::new().expect("directory is created");
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().expect("files are in a directory"))
+                .expect("directory is created");
+            fs::write(&path, "").expect("file is written");
+        }
+
+        let manifest = toml::from_str("[project]\nname = \"test\"\n").expect("manifest parses");
+        let root = dir.path().to_path_buf();
+        (dir, Project { root, manifest })
+    }
+
+    fn module_paths(project: &Project) -> Vec<String> {
+        let files = project.module_files().expect("module names are valid");
+        files.iter().map(|(path, _)| path.join("::")).collect()
+    }
+
+    #[test]
+    fn files_are_modules_named_after_their_path() {
+        let (_dir, project) = project(&[
+            "src/main.fu",
+            "src/util.fu",
+            "src/net/http.fu",
+            "src/net/tcp/mod.fu",
+            "src/notes.txt",
+            "test/other.fu",
+        ]);
+        assert_eq!(
+            module_paths(&project),
+            ["main", "util", "net::http", "net::tcp"]
+        );
+    }
+
+    #[test]
+    fn mod_file_of_a_source_directory_is_not_a_module() {
+        let (_dir, project) = project(&["src/mod.fu", "src/a.fu"]);
+        assert_eq!(module_paths(&project), ["a"]);
+    }
+
+    #[test]
+    fn reports_paths_that_are_no_module_names() {
+        let (_dir, project) = project(&["src/my-lib/a.fu", "src/2d.fu"]);
+        let errors = project.module_files().expect_err("paths are invalid");
+        let mut messages: Vec<_> = errors.into_iter().map(|d| d.message).collect();
+        messages.sort();
+        assert_eq!(
+            messages,
+            [
+                "`2d` is not a valid module name",
+                "`my-lib` is not a valid module name"
+            ]
+        );
+    }
+}
@@ -0,0 +1,954 @@
+mod scope;
+
+use scope::{ScopeKind, ScopeStack, Symbol, SymbolKind};
+
+use crate::ast_nodes::{
+    block::BlockNode,
+    control_flow::ForExprNode,
+    enum_def::{EnumDefNode, EnumInitNode},
+    expression::{ExpressionKind, ExpressionNode},
+    func_call::{FuncCallNode, MethodCallNode},
+    func_def::{FuncDefNode, GenericTypingNode, TypeParamNode},
+    match_expr::{MatchExprNode, PatternKind, PatternNode},
+    program::ProgramNode,
+    struct_def::{StructDefNode, StructFieldNode},
+    term::{PlaceSegment, StructInitNode},
+    trait_def::{ImplBlockNode, TraitDefNode},
+    types::{Type, reserved_name},
+};
+use crate::diagnostics::{Diagnostic, Span};
+use std::collections::{HashMap, HashSet};
+
+struct Context {
+    scopes: ScopeStack,
+    diagnostics: Vec<Diagnostic>,
+    /// Functions declared in `_c_import`ed headers are invisible to us, so
+    /// unknown function names can't be reported once a header is imported.
+    has_c_imports: bool,
+    /// Variants holding no values, which patterns may name without their
+    /// enum, like `None`.
+    unit_variants: HashSet<String>,
+    /// Fields of every struct, which methods in `impl` blocks see by their
+    /// bare names.
+    struct_fields: HashMap<String, Vec<StructFieldNode>>,
+    /// Type parameters of every generic struct and enum.
+    type_params: HashMap<String, Vec<TypeParamNode>>,
+    /// Set while resolving the default of a struct field, which can't use
+    /// the struct's const parameters.
+    in_field_default: bool,
+}
+
+impl Context {
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn declare(&mut self, name: &str, kind: SymbolKind, span: Span) {
+        // Items of modules are declared by their path, whose modules were
+        // checked when the modules were collected.
+        let own_name = name.rsplit("::").next().unwrap_or(name);
+        if let Some(reason) = reserved_name(own_name) {
+            self.error(reserved_name_error(own_name, reason, span));
+            return;
+        }
+
+        if let Some(previous) = self.scopes.current(name) {
+            let diagnostic =
+                Diagnostic::error(format!("`{}` is already defined in this scope", name))
+                    .with_span(span)
+                    .with_label("redefined here")
+                    .with_secondary(
+                        previous.span,
+                        format!("first defined as a {} here", previous.kind.describe()),
+                    );
+            self.error(diagnostic);
+            return;
+        }
+
+        // Variables share C's ordinary identifier namespace with functions
+        // and typedef'd structs, so they may not shadow those.
+        if kind.is_value()
+            && let Some(outer) = self.scopes.lookup(name).filter(|s| !s.kind.is_value())
+        {
+            let diagnostic = Diagnostic::error(format!(
+                "{} `{}` shadows the {} `{}`",
+                kind.describe(),
+                name,
+                outer.kind.describe(),
+                name
+            ))
+            .with_span(span)
+            .with_secondary(
+                outer.span,
+                format!("{} defined here", outer.kind.describe()),
+            )
+            .with_note("rename the variable");
+            self.error(diagnostic);
+            return;
+        }
+
+        self.scopes.declare(name, Symbol { kind, span });
+    }
+}
+
+/// Error for a name the generated C code keeps for itself.
+pub fn reserved_name_error(name: &str, reason: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(format!("`{}` can't be used as a name", name))
+        .with_span(span)
+        .with_label(reason)
+        .with_note("names containing `__` or ending with `_` are kept for the generated C code")
+}
+
+pub fn resolve_names(program: &ProgramNode) -> Result<(), Vec<Diagnostic>> {
+    let mut ctx = Context {
+        scopes: ScopeStack::new(),
+        diagnostics: vec![],
+        has_c_imports: program
+            .expressions
+            .iter()
+            .any(|e| matches!(e.kind, ExpressionKind::CImport(_))),
+        unit_variants: HashSet::new(),
+        struct_fields: HashMap::new(),
+        type_params: HashMap::new(),
+        in_field_default: false,
+    };
+
+    resolve_expressions(&program.expressions, &mut ctx);
+
+    if ctx.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(ctx.diagnostics)
+    }
+}
+
+/// Resolves a sequence of expressions sharing the current scope. Functions
+/// and structs are declared up front so they can be used before their
+/// definition.
+fn resolve_expressions(expressions: &[ExpressionNode], ctx: &mut Context) {
+    for expr in expressions {
+        match &expr.kind {
+            ExpressionKind::FuncDef(node) => {
+                ctx.declare(&node.name, SymbolKind::Function, node.span)
+            }
+            ExpressionKind::StructDef(node) => {
+                ctx.declare(&node.name, SymbolKind::Struct, node.span);
+                ctx.struct_fields
+                    .insert(node.name.clone(), node.fields.clone());
+                if let Some(generic_typing) = &node.generic_typing {
+                    ctx.type_params
+                        .insert(node.name.clone(), generic_typing.params.clone());
+                }
+            }
+            ExpressionKind::TraitDef(node) => ctx.declare(&node.name, SymbolKind::Trait, node.span),
+            ExpressionKind::EnumDef(node) => {
+                ctx.declare(&node.name, SymbolKind::Enum, node.span);
+                let unit_variants = node.variants.iter().filter(|v| v.fields.is_empty());
+                ctx.unit_variants
+                    .extend(unit_variants.map(|v| v.name.clone()));
+                if let Some(generic_typing) = &node.generic_typing {
+                    ctx.type_params
+                        .insert(node.name.clone(), generic_typing.params.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for expr in expressions {
+        resolve_expression(expr, ctx);
+    }
+}
+
+fn resolve_expression(expr: &ExpressionNode, ctx: &mut Context) {
+    match &expr.kind {
+        ExpressionKind::VarDecl(node) => {
+            resolve_expression(&node.value, ctx);
+            if let Some(var_type) = &node.var_type {
+                resolve_type(var_type, node.span, ctx);
+            }
+            ctx.declare(
+                &node.name,
+                SymbolKind::Variable {
+                    mutable: node.mutable,
+                },
+                node.span,
+            );
+        }
+        ExpressionKind::Assignment(node) => {
+            resolve_variable(&node.name, node.span, ctx);
+            for segment in &node.path {
+                if let PlaceSegment::Index(index) = segment {
+                    resolve_expression(index, ctx);
+                }
+            }
+            resolve_expression(&node.value, ctx);
+
+            if let Some(symbol) = ctx.scopes.lookup(&node.name)
+                && symbol.kind.is_value()
+                && !symbol.kind.is_mutable()
+            {
+                // Fields of immutable values are immutable as well.
+                let message = if let SymbolKind::Field { .. } = symbol.kind {
+                    format!(
+                        "cannot assign to `{}`, as `self` is immutable in this method",
+                        node.target()
+                    )
+                } else if node.path.is_empty() {
+                    format!(
+                        "cannot assign twice to immutable {} `{}`",
+                        symbol.kind.describe(),
+                        node.name
+                    )
+                } else {
+                    format!(
+                        "cannot assign to `{}`, as `{}` is an immutable {}",
+                        node.target(),
+                        node.name,
+                        symbol.kind.describe()
+                    )
+                };
+                let mut diagnostic = Diagnostic::error(message)
+                    .with_span(node.span)
+                    .with_secondary(symbol.span, "declared here");
+                match symbol.kind {
+                    SymbolKind::Variable { .. } => {
+                        diagnostic = diagnostic
+                            .with_note(format!("declare it with `mut {} := ...`", node.name));
+                    }
+                    SymbolKind::SelfParameter { .. } | SymbolKind::Field { .. } => {
+                        diagnostic = diagnostic.with_note("declare the method with `&mut self`");
+                    }
+                    _ => {}
+                }
+                ctx.error(diagnostic);
+            }
+        }
+        ExpressionKind::BinaryExpr(node) => {
+            resolve_expression(&node.left, ctx);
+            resolve_expression(&node.right, ctx);
+        }
+        ExpressionKind::UnaryExpr(node) => resolve_expression(&node.operand, ctx),
+        ExpressionKind::VarAccess(node) => resolve_variable(&node.name, node.span, ctx),
+        ExpressionKind::FuncDef(node) => resolve_func_def(node, ctx),
+        ExpressionKind::ReturnExpr(node) => {
+            if let Some(expression) = &node.expression {
+                resolve_expression(expression, ctx);
+            }
+        }
+        ExpressionKind::FuncCall(node) => resolve_func_call(node, ctx),
+        ExpressionKind::StructDef(node) => resolve_struct_def(node, ctx),
+        ExpressionKind::EnumDef(node) => resolve_enum_def(node, ctx),
+        ExpressionKind::TraitDef(node) => resolve_trait_def(node, ctx),
+        ExpressionKind::ImplBlock(node) => resolve_impl_block(node, ctx),
+        ExpressionKind::Module(_) | ExpressionKind::Use(_) => {
+            unreachable!("modules are flattened before name resolution")
+        }
+        ExpressionKind::DynCoercion(node) => resolve_expression(&node.value, ctx),
+        ExpressionKind::EnumInit(node) => resolve_enum_init(node, ctx),
+        ExpressionKind::StructInit(node) => resolve_struct_init(node, ctx),
+        ExpressionKind::StructFieldAccess(node) => resolve_expression(&node.object, ctx),
+        ExpressionKind::MethodCall(node) => resolve_method_call(node, ctx),
+        ExpressionKind::ArrayLit(node) => {
+            for element in &node.elements {
+                resolve_expression(element, ctx);
+            }
+        }
+        ExpressionKind::ArrayRepeat(node) => {
+            resolve_expression(&node.value, ctx);
+            resolve_array_length(&node.length, node.span, ctx);
+        }
+        ExpressionKind::Index(node) => {
+            resolve_expression(&node.object, ctx);
+            resolve_expression(&node.index, ctx);
+        }
+        ExpressionKind::If(node) => {
+            resolve_expression(&node.condition, ctx);
+            resolve_block(&node.then_block, ScopeKind::Block, ctx);
+            if let Some(else_block) = &node.else_block {
+                resolve_block(else_block, ScopeKind::Block, ctx);
+            }
+        }
+        ExpressionKind::Match(node) => resolve_match_expr(node, ctx),
+        ExpressionKind::While(node) => {
+            resolve_expression(&node.condition, ctx);
+            resolve_block(&node.body, ScopeKind::Loop, ctx);
+        }
+        ExpressionKind::For(node) => resolve_for_expr(node, ctx),
+        ExpressionKind::Break | ExpressionKind::Continue => {
+            if !ctx.scopes.in_loop() {
+                let keyword = match expr.kind {
+                    ExpressionKind::Break => "break",
+                    _ => "continue",
+                };
+                ctx.error(
+                    Diagnostic::error(format!("`{}` outside of a loop", keyword))
+                        .with_span(expr.span)
+                        .with_label(format!("cannot `{}` outside of a loop", keyword)),
+                );
+            }
+        }
+        ExpressionKind::CImport(_)
+        | ExpressionKind::IntLit(_)
+        | ExpressionKind::FloatLit(_)
+        | ExpressionKind::StrLit(_) => {}
+    }
+}
+
+fn resolve_block(block: &BlockNode, kind: ScopeKind, ctx: &mut Context) {
+    ctx.scopes.push(kind);
+    resolve_expressions(&block.expressions, ctx);
+    ctx.scopes.pop();
+}
+
+fn resolve_for_expr(node: &ForExprNode, ctx: &mut Context) {
+    resolve_expression(&node.start, ctx);
+    resolve_expression(&node.end, ctx);
+
+    // The body shares its scope with the loop variable.
+    ctx.scopes.push(ScopeKind::Loop);
+    ctx.declare(&node.variable, SymbolKind::LoopVariable, node.variable_span);
+    resolve_expressions(&node.body.expressions, ctx);
+    ctx.scopes.pop();
+}
+
+fn resolve_match_expr(node: &MatchExprNode, ctx: &mut Context) {
+    resolve_expression(&node.subject, ctx);
+
+    // Each arm gets a scope for its bindings, shared with the guard and body.
+    for arm in &node.arms {
+        ctx.scopes.push(ScopeKind::Block);
+        resolve_pattern(&arm.pattern, ctx);
+        if let Some(guard) = &arm.guard {
+            resolve_expression(guard, ctx);
+        }
+        resolve_expressions(&arm.body.expressions, ctx);
+        ctx.scopes.pop();
+    }
+}
+
+fn resolve_pattern(pattern: &PatternNode, ctx: &mut Context) {
+    match &pattern.kind {
+        PatternKind::Wildcard => {}
+        PatternKind::Literal(value) | PatternKind::Comparison { value, .. } => {
+            resolve_expression(value, ctx)
+        }
+        // Unit variants are turned into variant patterns by the type checker.
+        PatternKind::Binding { name, .. } if ctx.unit_variants.contains(name) => {}
+        PatternKind::Binding { name, .. } => ctx.declare(name, SymbolKind::Binding, pattern.span),
+        PatternKind::Variant {
+            enum_name, fields, ..
+        } => {
+            if let Some(enum_name) = enum_name {
+                resolve_enum_name(enum_name, pattern.span, ctx);
+            }
+            for field in fields {
+                resolve_pattern(field, ctx);
+            }
+        }
+    }
+}
+
+fn resolve_variable(name: &str, span: Span, ctx: &mut Context) {
+    match ctx.scopes.lookup(name) {
+        Some(symbol) if symbol.kind == SymbolKind::ConstParameter && ctx.in_field_default => ctx
+            .error(
+                Diagnostic::error(format!(
+                    "const parameter `{}` can't be used in a default value",
+                    name
+                ))
+                .with_span(span)
+                .with_note("defaults are evaluated where the struct is created"),
+            ),
+        Some(symbol) if symbol.kind.is_value() => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "expected a variable, found the {} `{}`",
+                symbol.kind.describe(),
+                name
+            ))
+            .with_span(span);
+            ctx.error(diagnostic);
+        }
+        None => ctx.error(
+            Diagnostic::error(format!("cannot find variable `{}` in this scope", name))
+                .with_span(span)
+                .with_label("not found"),
+        ),
+    }
+}
+
+fn resolve_type(resolved_type: &Type, span: Span, ctx: &mut Context) {
+    let (name, args) = match resolved_type {
+        Type::Named(name) => (name, &[][..]),
+        Type::Generic(name, args) => (name, &args[..]),
+        Type::Dyn(trait_name) => return resolve_trait_name(trait_name, span, ctx),
+        Type::Array(element, length) => {
+            resolve_type(element, span, ctx);
+            return resolve_array_length(length, span, ctx);
+        }
+        _ => return,
+    };
+
+    for arg in args {
+        resolve_type_arg(arg, span, ctx);
+    }
+
+    match ctx.scopes.lookup(name) {
+        Some(symbol) if symbol.kind.is_type() => check_type_args(name, args, span, ctx),
+        Some(symbol) if symbol.kind == SymbolKind::ConstParameter => ctx.error(
+            Diagnostic::error(format!("const parameter `{}` used as a type", name))
+                .with_span(span)
+                .with_note("const parameters are numbers, like the length of an array"),
+        ),
+        Some(symbol) if symbol.kind == SymbolKind::Trait => ctx.error(
+            Diagnostic::error(format!("trait `{}` used as a type", name))
+                .with_span(span)
+                .with_note(format!(
+                    "use `dyn {}` for values of any type implementing it",
+                    name
+                )),
+        ),
+        _ => ctx.error(
+            Diagnostic::error(format!("cannot find type `{}` in this scope", name))
+                .with_span(span)
+                .with_label("unknown type"),
+        ),
+    }
+}
+
+/// Type arguments may also be the numbers const parameters take.
+fn resolve_type_arg(arg: &Type, span: Span, ctx: &mut Context) {
+    if let Type::Const(value) = arg {
+        check_constant(*value, span, ctx);
+    } else if !is_const_arg(arg, ctx) {
+        resolve_type(arg, span, ctx);
+    }
+}
+
+fn is_const_arg(arg: &Type, ctx: &Context) -> bool {
+    match arg {
+        Type::Const(_) => true,
+        Type::Named(name) => ctx
+            .scopes
+            .lookup(name)
+            .is_some_and(|symbol| symbol.kind == SymbolKind::ConstParameter),
+        _ => false,
+    }
+}
+
+/// The length of an array is a number or a const parameter.
+fn resolve_array_length(length: &Type, span: Span, ctx: &mut Context) {
+    let name = match length {
+        Type::Named(name) => name,
+        Type::Const(value) => return check_constant(*value, span, ctx),
+        _ => return,
+    };
+
+    match ctx.scopes.lookup(name) {
+        Some(symbol) if symbol.kind == SymbolKind::ConstParameter => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "expected a const parameter as the length, found the {} `{}`",
+                symbol.kind.describe(),
+                name
+            ))
+            .with_span(span)
+            .with_note("the length of an array has to be known at compile time");
+            ctx.error(diagnostic);
+        }
+        None => ctx.error(
+            Diagnostic::error(format!(
+                "cannot find const parameter `{}` in this scope",
+                name
+            ))
+            .with_span(span)
+            .with_label("not found"),
+        ),
+    }
+}
+
+/// Constants end up as `int`s in C.
+fn check_constant(value: i64, span: Span, ctx: &mut Context) {
+    if i32::try_from(value).is_err() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "the constant `{}` doesn't fit into an `i32`",
+                value
+            ))
+            .with_span(span),
+        );
+    }
+}
+
+/// Generic structs and enums are only types together with their arguments,
+/// other types take none. Const parameters take numbers, the others types.
+fn check_type_args(name: &str, args: &[Type], span: Span, ctx: &mut Context) {
+    let type_params = ctx.type_params.get(name).cloned().unwrap_or_default();
+    let expected = type_params.len();
+    let supplied = args.len();
+    if supplied == expected {
+        for (param, arg) in type_params.iter().zip(args) {
+            check_type_arg_kind(name, param, arg, span, ctx);
+        }
+        return;
+    }
+
+    let diagnostic = if supplied == 0 {
+        let type_params: Vec<_> = type_params
+            .iter()
+            .map(|p| format!("`{}`", p.name))
+            .collect();
+        Diagnostic::error(format!("missing type arguments for `{}`", name))
+            .with_span(span)
+            .with_note(format!(
+                "`{}` is generic over {}, write the types to use for them: `{}<...>`",
+                name,
+                type_params.join(", "),
+                name
+            ))
+    } else {
+        Diagnostic::error(format!(
+            "`{}` takes {} type argument(s) but {} were supplied",
+            name, expected, supplied
+        ))
+        .with_span(span)
+        .with_label(format!("expected {} type argument(s)", expected))
+    };
+    ctx.error(diagnostic);
+}
+
+fn check_type_arg_kind(
+    name: &str,
+    param: &TypeParamNode,
+    arg: &Type,
+    span: Span,
+    ctx: &mut Context,
+) {
+    let message = match (&param.const_type, is_const_arg(arg, ctx)) {
+        (Some(_), false) => format!(
+            "expected a number for the const parameter `{}` of `{}`, found the type `{}`",
+            param.name, name, arg
+        ),
+        (None, true) => format!(
+            "expected a type for the type parameter `{}` of `{}`, found `{}`",
+            param.name, name, arg
+        ),
+        _ => return,
+    };
+    ctx.error(
+        Diagnostic::error(message)
+            .with_span(span)
+            .with_secondary(param.span, "declared here"),
+    );
+}
+
+fn resolve_func_call(node: &FuncCallNode, ctx: &mut Context) {
+    match ctx.scopes.lookup(&node.name) {
+        Some(symbol) if symbol.kind == SymbolKind::Function => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` is a {}, not a function",
+                node.name,
+                symbol.kind.describe()
+            ))
+            .with_span(node.span);
+            ctx.error(diagnostic);
+        }
+        None if ctx.has_c_imports => {}
+        None => ctx.error(
+            Diagnostic::error(format!(
+                "cannot find function `{}` in this scope",
+                node.name
+            ))
+            .with_span(node.span)
+            .with_label("not found"),
+        ),
+    }
+
+    for generic_param in &node.generic_params {
+        resolve_type_arg(generic_param, node.span, ctx);
+    }
+
+    for param in &node.params {
+        resolve_expression(param, ctx);
+    }
+}
+
+fn resolve_func_def(node: &FuncDefNode, ctx: &mut Context) {
+    if let Some(self_param) = node.self_param {
+        ctx.error(
+            Diagnostic::error("`self` is only allowed in methods")
+                .with_span(self_param.span)
+                .with_label("not inside a struct")
+                .with_note("define the function inside a struct to make it a method"),
+        );
+    }
+
+    resolve_function(node, None, ctx);
+}
+
+/// Resolves a function or, given the fields of the type it is defined for, a
+/// method. Methods taking `self` see the fields by their bare names,
+/// parameters and variables may shadow them.
+fn resolve_function(node: &FuncDefNode, owner: Option<&[StructFieldNode]>, ctx: &mut Context) {
+    ctx.scopes.push(ScopeKind::Function);
+
+    if let (Some(fields), Some(self_param)) = (owner, node.self_param) {
+        let mutable = self_param.mutable;
+        ctx.declare(
+            "self",
+            SymbolKind::SelfParameter { mutable },
+            self_param.span,
+        );
+        // Fields are reached through `self` in C, so unlike variables they
+        // may share the name of a function.
+        for field in fields {
+            let kind = SymbolKind::Field { mutable };
+            ctx.scopes.declare(
+                &field.name,
+                Symbol {
+                    kind,
+                    span: field.span,
+                },
+            );
+        }
+        ctx.scopes.push(ScopeKind::Block);
+    }
+
+    declare_type_params(node.generic_typing.as_ref(), ctx);
+
+    for param in &node.params {
+        resolve_type(&param.param_type, param.span, ctx);
+        ctx.declare(&param.name, SymbolKind::Parameter, param.span);
+    }
+
+    if let Some(return_type) = &node.return_type {
+        resolve_type(return_type, node.span, ctx);
+    }
+
+    // The body shares its scope with the parameters, like in C.
+    resolve_expressions(&node.body.expressions, ctx);
+
+    if owner.is_some() && node.self_param.is_some() {
+        ctx.scopes.pop();
+    }
+    ctx.scopes.pop();
+}
+
+fn declare_type_params(generic_typing: Option<&GenericTypingNode>, ctx: &mut Context) {
+    for param in generic_typing.iter().flat_map(|g| &g.params) {
+        for bound in &param.bounds {
+            resolve_trait_name(bound, param.span, ctx);
+        }
+
+        let Some(const_type) = &param.const_type else {
+            ctx.declare(&param.name, SymbolKind::TypeParameter, param.span);
+            continue;
+        };
+        if !const_type.is_integer() {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "const parameter `{}` can't have type `{}`",
+                    param.name, const_type
+                ))
+                .with_span(param.span)
+                .with_note("const parameters are integers, like `const N: i32`"),
+            );
+        }
+        ctx.declare(&param.name, SymbolKind::ConstParameter, param.span);
+    }
+}
+
+/// The type parameters of a generic struct or enum are visible in its fields
+/// and methods.
+fn resolve_struct_def(node: &StructDefNode, ctx: &mut Context) {
+    ctx.scopes.push(ScopeKind::Block);
+    declare_type_params(node.generic_typing.as_ref(), ctx);
+
+    for (i, method) in node.methods.iter().enumerate() {
+        let previous = node.fields.iter().map(|f| (f.span, "field"));
+        let previous = previous.chain(node.methods[..i].iter().map(|m| (m.span, "method")));
+        let names = node.fields.iter().map(|f| &f.name);
+        let names = names.chain(node.methods[..i].iter().map(|m| &m.name));
+
+        if let Some(((span, kind), _)) = previous.zip(names).find(|(_, n)| **n == method.name) {
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` is defined twice in struct `{}`",
+                method.name, node.name
+            ))
+            .with_span(method.span)
+            .with_label("redefined here")
+            .with_secondary(span, format!("first defined as a {} here", kind));
+            ctx.error(diagnostic);
+        }
+
+        resolve_function(method, Some(&node.fields), ctx);
+    }
+
+    for field in &node.fields {
+        resolve_type(&field.field_type, field.span, ctx);
+
+        // Defaults are evaluated at every struct literal, where the
+        // variables around the definition don't exist.
+        if let Some(default) = &field.default {
+            ctx.scopes.push(ScopeKind::Function);
+            ctx.in_field_default = true;
+            resolve_expression(default, ctx);
+            ctx.in_field_default = false;
+            ctx.scopes.pop();
+        }
+    }
+
+    ctx.scopes.pop();
+}
+
+fn resolve_trait_def(node: &TraitDefNode, ctx: &mut Context) {
+    let methods: Vec<_> = node.methods.iter().map(|m| &m.func).collect();
+    check_duplicate_methods(&methods, &format!("trait `{}`", node.name), ctx);
+
+    // Default bodies only know `self` implements the trait, not its fields.
+    for method in methods {
+        resolve_function(method, Some(&[]), ctx);
+    }
+}
+
+/// The type parameters of a generic impl are visible in its methods and
+/// have to appear in the type it is for, which is where their values come
+/// from.
+fn resolve_impl_block(node: &ImplBlockNode, ctx: &mut Context) {
+    resolve_trait_name(&node.trait_name, node.span, ctx);
+    ctx.scopes.push(ScopeKind::Block);
+    declare_type_params(node.generic_typing.as_ref(), ctx);
+    resolve_type(&node.for_type, node.span, ctx);
+
+    for param in node.generic_typing.iter().flat_map(|g| &g.params) {
+        if !mentions(&node.for_type, &param.name) {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "type parameter `{}` is not used in `{}`",
+                    param.name, node.for_type
+                ))
+                .with_span(param.span)
+                .with_label("unused type parameter")
+                .with_note(
+                    "the values of the type parameters of an impl come from the type it is for",
+                ),
+            );
+        }
+    }
+
+    let methods: Vec<_> = node.methods.iter().collect();
+    let owner = format!("impl of `{}` for `{}`", node.trait_name, node.for_type);
+    check_duplicate_methods(&methods, &owner, ctx);
+
+    let fields = node
+        .for_type
+        .base_name()
+        .and_then(|name| ctx.struct_fields.get(name))
+        .cloned()
+        .unwrap_or_default();
+    for method in &node.methods {
+        resolve_function(method, Some(&fields), ctx);
+    }
+    ctx.scopes.pop();
+}
+
+/// Whether a type names the given type, also among its type arguments.
+fn mentions(value_type: &Type, name: &str) -> bool {
+    match value_type {
+        Type::Named(type_name) => type_name == name,
+        Type::Generic(_, args) => args.iter().any(|arg| mentions(arg, name)),
+        Type::Array(element, length) => mentions(element, name) || mentions(length, name),
+        _ => false,
+    }
+}
+
+fn resolve_trait_name(name: &str, span: Span, ctx: &mut Context) {
+    match ctx.scopes.lookup(name) {
+        Some(symbol) if symbol.kind == SymbolKind::Trait => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` is a {}, not a trait",
+                name,
+                symbol.kind.describe()
+            ))
+            .with_span(span);
+            ctx.error(diagnostic);
+        }
+        None => ctx.error(
+            Diagnostic::error(format!("cannot find trait `{}` in this scope", name))
+                .with_span(span)
+                .with_label("not found"),
+        ),
+    }
+}
+
+fn check_duplicate_methods(methods: &[&FuncDefNode], owner: &str, ctx: &mut Context) {
+    for (i, method) in methods.iter().enumerate() {
+        if let Some(previous) = methods[..i].iter().find(|m| m.name == method.name) {
+            let diagnostic =
+                Diagnostic::error(format!("`{}` is defined twice in {}", method.name, owner))
+                    .with_span(method.span)
+                    .with_label("redefined here")
+                    .with_secondary(previous.span, "first defined here");
+            ctx.error(diagnostic);
+        }
+    }
+}
+
+fn resolve_struct_init(node: &StructInitNode, ctx: &mut Context) {
+    match ctx.scopes.lookup(&node.struct_name) {
+        Some(symbol) if symbol.kind == SymbolKind::Struct => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` is a {}, not a struct",
+                node.struct_name,
+                symbol.kind.describe()
+            ))
+            .with_span(node.span);
+            ctx.error(diagnostic);
+        }
+        None => ctx.error(
+            Diagnostic::error(format!(
+                "cannot find struct `{}` in this scope",
+                node.struct_name
+            ))
+            .with_span(node.span)
+            .with_label("not found"),
+        ),
+    }
+
+    // Omitted type arguments are inferred by the type checker.
+    for generic_param in &node.generic_params {
+        resolve_type_arg(generic_param, node.span, ctx);
+    }
+    if !node.generic_params.is_empty() {
+        check_type_args(&node.struct_name, &node.generic_params, node.span, ctx);
+    }
+
+    for field in &node.fields {
+        resolve_expression(&field.value, ctx);
+    }
+}
+
+fn resolve_method_call(node: &MethodCallNode, ctx: &mut Context) {
+    // `X.add_two(5)` calls a method without `self` on the struct `X`.
+    let names_struct = match &node.object.kind {
+        ExpressionKind::VarAccess(object) => ctx
+            .scopes
+            .lookup(&object.name)
+            .is_some_and(|symbol| symbol.kind == SymbolKind::Struct),
+        _ => false,
+    };
+    if !names_struct {
+        resolve_expression(&node.object, ctx);
+    }
+
+    for arg in &node.args {
+        resolve_expression(arg, ctx);
+    }
+}
+
+fn resolve_enum_def(node: &EnumDefNode, ctx: &mut Context) {
+    ctx.scopes.push(ScopeKind::Block);
+    declare_type_params(node.generic_typing.as_ref(), ctx);
+
+    for (i, variant) in node.variants.iter().enumerate() {
+        if let Some(first) = node.variants[..i].iter().find(|v| v.name == variant.name) {
+            let diagnostic = Diagnostic::error(format!(
+                "variant `{}` is defined twice in enum `{}`",
+                variant.name, node.name
+            ))
+            .with_span(variant.span)
+            .with_label("redefined here")
+            .with_secondary(first.span, "first defined here");
+            ctx.error(diagnostic);
+        }
+
+        for field in &variant.fields {
+            resolve_type(&field.field_type, field.span, ctx);
+        }
+    }
+
+    ctx.scopes.pop();
+}
+
+fn resolve_enum_init(node: &EnumInitNode, ctx: &mut Context) {
+    resolve_enum_name(&node.enum_name, node.span, ctx);
+
+    for generic_param in &node.generic_params {
+        resolve_type_arg(generic_param, node.span, ctx);
+    }
+    if !node.generic_params.is_empty() {
+        check_type_args(&node.enum_name, &node.generic_params, node.span, ctx);
+    }
+
+    for arg in &node.args {
+        resolve_expression(arg, ctx);
+    }
+}
+
+fn resolve_enum_name(name: &str, span: Span, ctx: &mut Context) {
+    match ctx.scopes.lookup(name) {
+        Some(symbol) if symbol.kind == SymbolKind::Enum => {}
+        Some(symbol) => {
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` is a {}, not an enum",
+                name,
+                symbol.kind.describe()
+            ))
+            .with_span(span);
+            ctx.error(diagnostic);
+        }
+        None => ctx.error(
+            Diagnostic::error(format!("cannot find enum `{}` in this scope", name))
+                .with_span(span)
+                .with_label("not found"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_builder::build_ast_from_pairs;
+    use crate::parser::parse_program;
+
+    fn errors(source: &str) -> Vec<String> {
+        let pair = parse_program(source, 0).expect("source parses");
+        let program = build_ast_from_pairs(pair, 0).expect("source builds");
+        match resolve_names(&program) {
+            Ok(()) => vec![],
+            Err(diagnostics) => diagnostics.into_iter().map(|d| d.message).collect(),
+        }
+    }
+
+    #[test]
+    fn fields_may_share_the_name_of_a_function() {
+        let source = "fn size() i32 { return 7 }\nstruct Buf = {\n    size: i32\n    fn get(&self) i32 { return size }\n}\n";
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn variables_may_not_shadow_a_function() {
+        let source = "fn size() i32 { return 7 }\nfn f() { size := 1 }\n";
+        assert_eq!(
+            errors(source),
+            ["variable `size` shadows the function `size`"]
+        );
+    }
+
+    #[test]
+    fn names_left_to_the_generated_code_are_rejected() {
+        let source = "fn a__f() {}\nfn g_() {}\nfn h() { x__y := 1 }\nfn main() {}\n";
+        assert_eq!(
+            errors(source),
+            [
+                "`a__f` can't be used as a name",
+                "`g_` can't be used as a name",
+                "`x__y` can't be used as a name"
+            ]
+        );
+    }
+}
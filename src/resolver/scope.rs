@@ -0,0 +1,173 @@
+use crate::diagnostics::Span;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable {
+        mutable: bool,
+    },
+    LoopVariable,
+    /// Name bound by a `match` pattern.
+    Binding,
+    Parameter,
+    /// `self` inside a method.
+    SelfParameter {
+        mutable: bool,
+    },
+    /// Field of `self`, usable by its bare name inside methods.
+    Field {
+        mutable: bool,
+    },
+    Function,
+    Struct,
+    Enum,
+    Trait,
+    TypeParameter,
+    /// `const N: i32`, usable as a value and as the length of arrays.
+    ConstParameter,
+}
+
+impl SymbolKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SymbolKind::Variable { .. } => "variable",
+            SymbolKind::LoopVariable => "loop variable",
+            SymbolKind::Binding => "binding",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::SelfParameter { .. } => "self parameter",
+            SymbolKind::Field { .. } => "field",
+            SymbolKind::Function => "function",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Trait => "trait",
+            SymbolKind::TypeParameter => "type parameter",
+            SymbolKind::ConstParameter => "const parameter",
+        }
+    }
+
+    pub fn is_value(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Variable { .. }
+                | SymbolKind::LoopVariable
+                | SymbolKind::Binding
+                | SymbolKind::Parameter
+                | SymbolKind::SelfParameter { .. }
+                | SymbolKind::Field { .. }
+                | SymbolKind::ConstParameter
+        )
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Variable { mutable: true }
+                | SymbolKind::SelfParameter { mutable: true }
+                | SymbolKind::Field { mutable: true }
+        )
+    }
+
+    pub fn is_type(&self) -> bool {
+        matches!(
+            self,
+            SymbolKind::Struct | SymbolKind::Enum | SymbolKind::TypeParameter
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: SymbolKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Program,
+    Function,
+    /// Branches of an `if` and arms of a `match`.
+    Block,
+    /// Body of a `while` or `for` loop, which `break` and `continue` target.
+    Loop,
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub symbols: HashMap<String, Symbol>,
+}
+
+/// Stack of nested scopes, innermost last.
+#[derive(Debug)]
+pub struct ScopeStack {
+    scopes: Vec<Scope>,
+}
+
+impl ScopeStack {
+    pub fn new() -> Self {
+        ScopeStack {
+            scopes: vec![Scope {
+                kind: ScopeKind::Program,
+                symbols: HashMap::new(),
+            }],
+        }
+    }
+
+    pub fn push(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope {
+            kind,
+            symbols: HashMap::new(),
+        });
+    }
+
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Adds a symbol to the innermost scope, returning the symbol it replaced
+    /// if the name was already taken there.
+    pub fn declare(&mut self, name: &str, symbol: Symbol) -> Option<Symbol> {
+        self.scopes
+            .last_mut()
+            .expect("the program scope is never popped")
+            .symbols
+            .insert(name.to_string(), symbol)
+    }
+
+    pub fn current(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.last()?.symbols.get(name)
+    }
+
+    /// Whether a loop encloses the innermost scope without a function
+    /// boundary in between.
+    pub fn in_loop(&self) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .take_while(|scope| scope.kind != ScopeKind::Function)
+            .any(|scope| scope.kind == ScopeKind::Loop)
+    }
+
+    /// Looks a name up from the innermost scope outwards.
+    ///
+    /// Function bodies are compiled to separate C functions, so variables of
+    /// the scopes around a function are not visible inside of it; functions,
+    /// structs and type parameters are, and const parameters, which become
+    /// numbers.
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        let mut crossed_function = false;
+
+        for scope in self.scopes.iter().rev() {
+            if let Some(symbol) = scope.symbols.get(name)
+                && !(crossed_function
+                    && symbol.kind.is_value()
+                    && symbol.kind != SymbolKind::ConstParameter)
+            {
+                return Some(symbol);
+            }
+            crossed_function |= scope.kind == ScopeKind::Function;
+        }
+
+        None
+    }
+}
@@ -0,0 +1,263 @@
+use crate::codegen::Target;
+use crate::diagnostics::{Diagnostic, SourceMap};
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, thread};
+use tracing::{debug, info};
+
+/// The C compiler the generated code is compiled with, and its flags.
+#[derive(Debug)]
+pub struct Toolchain {
+    pub cc: String,
+    /// Passed on as `-O<level>`.
+    pub opt_level: Option<String>,
+    pub cflags: Vec<String>,
+    /// Flags only used when linking an executable.
+    pub ldflags: Vec<String>,
+    pub include_paths: Vec<PathBuf>,
+    /// Libraries linked with `-l`, besides the math library every program
+    /// is linked with.
+    pub libraries: Vec<String>,
+}
+
+impl Toolchain {
+    /// Toolchain using the compiler in `$CC` or else `cc`.
+    pub fn from_env() -> Toolchain {
+        let cc = env::var("CC").ok().filter(|cc| !cc.is_empty());
+        Toolchain {
+            cc: cc.unwrap_or_else(|| String::from("cc")),
+            opt_level: None,
+            cflags: vec![],
+            ldflags: vec![],
+            include_paths: vec![],
+            libraries: vec![],
+        }
+    }
+
+    /// Compiles `c_file` to an executable or, using `$AR` or `ar`, to a
+    /// static library at `output`. The errors of the C compiler are
+    /// reported at the Fusion lines the `#line` directives of the code
+    /// point to.
+    pub fn compile(
+        &self,
+        c_file: &Path,
+        output: &Path,
+        target: Target,
+        sources: &SourceMap,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let mut cc = Command::new(&self.cc);
+        if let Some(level) = &self.opt_level {
+            cc.arg(format!("-O{}", level));
+        }
+        cc.args(&self.cflags);
+        for path in &self.include_paths {
+            cc.arg("-I").arg(path);
+        }
+
+        match target {
+            Target::Executable => {
+                cc.arg(c_file).arg("-o").arg(output).args(&self.ldflags);
+                for library in &self.libraries {
+                    cc.arg(format!("-l{}", library));
+                }
+                run_tool(cc.arg("-lm"), sources)
+            }
+            Target::Library => {
+                let object = c_file.with_extension("o");
+                run_tool(cc.arg("-c").arg(c_file).arg("-o").arg(&object), sources)?;
+
+                let ar = env::var("AR").ok().filter(|ar| !ar.is_empty());
+                let mut ar = Command::new(ar.as_deref().unwrap_or("ar"));
+                let _ = fs::remove_file(output);
+                run_tool(ar.arg("rcs").arg(output).arg(&object), sources)
+            }
+        }
+    }
+}
+
+/// Runs the C compiler or archiver, passing its warnings on.
+fn run_tool(command: &mut Command, sources: &SourceMap) -> Result<(), Vec<Diagnostic>> {
+    info!("running {:?}", command);
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().map_err(|e| {
+        vec![Diagnostic::error(format!(
+            "couldn't run `{}`: {}",
+            program, e
+        ))]
+    })?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() {
+        eprint!("{}", stderr);
+        return Ok(());
+    }
+
+    let mut diagnostics = c_errors(&stderr, sources);
+    if diagnostics.is_empty() {
+        // Errors without a location, like the ones of the linker.
+        eprint!("{}", stderr);
+        diagnostics.push(Diagnostic::error(format!(
+            "`{}` failed with {}",
+            program, output.status
+        )));
+    } else {
+        debug!("output of `{}`:\n{}", program, stderr);
+    }
+    Err(diagnostics)
+}
+
+/// Errors in the output of a C compiler, like
+/// `main.fu:3:5: error: implicit declaration of function 'foo'`. The ones
+/// in a Fusion file are reported at their line, the column is one of the
+/// generated C.
+fn c_errors(stderr: &str, sources: &SourceMap) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for line in stderr.lines() {
+        let Some((location, message)) = line
+            .split_once(": error: ")
+            .or_else(|| line.split_once(": fatal error: "))
+        else {
+            continue;
+        };
+        let mut parts = location.rsplitn(3, ':');
+        let (Some(_column), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+
+        let diagnostic = Diagnostic::error(format!("C compiler: {}", message));
+        let source = sources.files.iter().find(|source| source.name == file);
+        let diagnostic = match (source, line.parse()) {
+            (Some(source), Ok(line)) => diagnostic
+                .with_span(source.line_span(line))
+                .with_label("in the C code generated for this line"),
+            _ => diagnostic.with_note(format!("at {}", location)),
+        };
+
+        // The prototype and the definition of a function are attributed to
+        // the same line, so they often cause the same error twice.
+        let reported = diagnostics.iter().any(|other: &Diagnostic| {
+            other.message == diagnostic.message && other.span == diagnostic.span
+        });
+        if !reported {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    diagnostics
+}
+
+/// The code formatted by clang-format, or as it is if clang-format isn't
+/// installed.
+pub fn format_c(code: &str) -> String {
+    let child = Command::new("clang-format")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            debug!("not formatting the C code, can't run `clang-format`: {}", e);
+            return code.to_string();
+        }
+    };
+
+    // Written from another thread, so clang-format never blocks on a full
+    // stdout while the input is still being written.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = code.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output();
+    let written = writer.join().is_ok_and(|result| result.is_ok());
+
+    match output {
+        Ok(output) if written && output.status.success() => {
+            String::from_utf8(output.stdout).unwrap_or_else(|_| code.to_string())
+        }
+        _ => code.to_string(),
+    }
+}
+
+/// Directory for intermediate files, removed again when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a directory only the current user can access. Its name can't
+    /// be guessed and it must not exist yet, so no one else can have created
+    /// it beforehand to read or replace the files put into it.
+    pub fn new() -> io::Result<TempDir> {
+        const ATTEMPTS: usize = 16;
+
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        for _ in 0..ATTEMPTS {
+            let path = env::temp_dir().join(format!("fusion-{:016x}", random_number()));
+            match builder.create(&path) {
+                Ok(()) => return Ok(TempDir { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "every name tried was taken already",
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A number no other process can predict. The keys of `RandomState` come
+/// from the operating system's random number generator.
+fn random_number() -> u64 {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_usize(COUNT.fetch_add(1, Ordering::Relaxed));
+    if let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(time.as_nanos());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_dirs_are_new_private_and_removed() {
+        let first = TempDir::new().expect("directory is created");
+        let second = TempDir::new().expect("directory is created");
+        assert_ne!(first.path(), second.path());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(first.path()).expect("directory exists");
+            assert_eq!(metadata.permissions().mode() & 0o077, 0);
+        }
+
+        let path = first.path().to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
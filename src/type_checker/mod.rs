@@ -0,0 +1,3276 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::ast_nodes::{
+    array::{ArrayLitNode, ArrayRepeatNode},
+    block::BlockNode,
+    control_flow::{ForExprNode, IfExprNode},
+    enum_def::{EnumDefNode, EnumInitNode},
+    expression::{
+        BinaryExprNode, BinaryOp, ExpressionKind, ExpressionNode, UnaryExprNode, UnaryOp,
+    },
+    func_call::{FuncCallNode, MethodCallNode},
+    func_def::{FuncDefNode, GenericTypingNode, SelfParam, TypeParamNode},
+    match_expr::{MatchArmNode, MatchExprNode, PatternKind, PatternNode},
+    program::ProgramNode,
+    struct_def::{StructDefNode, StructFieldAccessNode, StructFieldNode},
+    term::{AssignmentNode, PlaceSegment, StructFieldInitNode, StructInitNode, VarDeclNode},
+    trait_def::{DynCoercionNode, ImplBlockNode, TraitDefNode},
+    types::Type,
+    var_access::VarAccessNode,
+};
+use crate::diagnostics::{Diagnostic, Span};
+
+#[derive(Debug, Clone)]
+struct FunctionSignature {
+    params: Vec<Type>,
+    return_type: Type,
+    generic_params: Vec<TypeParamNode>,
+}
+
+#[derive(Debug, Clone)]
+struct Method {
+    name: String,
+    self_param: Option<SelfParam>,
+    signature: FunctionSignature,
+}
+
+#[derive(Debug, Clone)]
+struct TraitMethod {
+    method: Method,
+    /// Unchecked default body, checked again as a method of every type
+    /// using it.
+    default: Option<FuncDefNode>,
+    span: Span,
+}
+
+/// `impl Trait for Type`, with the signatures of all trait methods. Generic
+/// impls apply to every type `for_type` matches, with values for the type
+/// parameters that meet their bounds.
+#[derive(Debug, Clone)]
+struct Impl {
+    trait_name: String,
+    type_params: Vec<TypeParamNode>,
+    for_type: Type,
+    methods: Vec<Method>,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+enum Binding {
+    Variable {
+        var_type: Type,
+        mutable: bool,
+    },
+    /// Field of `self` inside a method, usable by its bare name.
+    Field(Type),
+    Function(FunctionSignature),
+    /// Function whose return type is inferred from its body, which is checked
+    /// when the function is first called. Indexes `Context::inferred`.
+    InferredFunction(usize),
+    /// Structs and enums name the type parameters their fields and methods
+    /// are written in terms of, empty unless they are generic.
+    Struct {
+        type_params: Vec<TypeParamNode>,
+        fields: Vec<StructFieldNode>,
+        methods: Vec<Method>,
+    },
+    /// Variants with the types of the values they hold.
+    Enum {
+        type_params: Vec<TypeParamNode>,
+        variants: Vec<(String, Vec<Type>)>,
+    },
+    Trait(Vec<TraitMethod>),
+}
+
+struct FunctionContext {
+    name: String,
+    return_type: Option<Type>,
+    /// Type unified from the `return`s seen so far when the return type is
+    /// omitted, with the span of the `return` that introduced it.
+    inferred: Option<(Type, Span)>,
+}
+
+/// Function with an omitted return type, waiting to be checked where it is
+/// defined.
+struct InferredFunction {
+    node: FuncDefNode,
+    /// Number of scopes visible at the definition.
+    depth: usize,
+    type_params: Vec<TypeParamNode>,
+    checked: bool,
+    /// Whether a call of the function was reached while checking it,
+    /// possibly through other functions.
+    recursive: bool,
+    /// Position in `Context::inferring` of the first function of the cycles
+    /// this one is part of.
+    cycle_start: Option<usize>,
+}
+
+struct Context {
+    scopes: Vec<HashMap<String, Binding>>,
+    inferred: Vec<InferredFunction>,
+    /// Inferred functions being checked, each called by the one before.
+    inferring: Vec<usize>,
+    functions: Vec<FunctionContext>,
+    /// Type parameters of the generic functions being checked. Their values
+    /// are only known per instantiation, so they match every type, but only
+    /// implement the traits they are bounded by.
+    type_params: Vec<TypeParamNode>,
+    /// Trait implementations, which apply everywhere regardless of the
+    /// scope they are written in.
+    impls: Vec<Impl>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Context {
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn declare(&mut self, name: &str, binding: Binding) {
+        self.scopes
+            .last_mut()
+            .expect("the program scope is never popped")
+            .insert(name.to_string(), binding);
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn variable(&self, name: &str) -> Type {
+        match self.lookup(name) {
+            Some(Binding::Variable { var_type, .. } | Binding::Field(var_type)) => var_type.clone(),
+            // Name resolution already reported anything else.
+            _ => Type::Unknown,
+        }
+    }
+
+    fn type_param(&self, checked_type: &Type) -> Option<&TypeParamNode> {
+        let Type::Named(name) = checked_type else {
+            return None;
+        };
+        self.type_params.iter().rev().find(|p| p.name == *name)
+    }
+
+    fn is_type_param(&self, checked_type: &Type) -> bool {
+        self.type_param(checked_type).is_some()
+    }
+
+    /// Whether two types may be the same once the type parameters are known.
+    /// The arguments of generic types have to match exactly, as every list
+    /// of arguments is a different C struct.
+    fn same_type(&self, left: &Type, right: &Type) -> bool {
+        if left == right
+            || *left == Type::Unknown
+            || *right == Type::Unknown
+            || self.is_type_param(left)
+            || self.is_type_param(right)
+        {
+            return true;
+        }
+
+        match (left, right) {
+            (Type::Generic(left, left_args), Type::Generic(right, right_args)) => {
+                left == right
+                    && left_args.len() == right_args.len()
+                    && left_args
+                        .iter()
+                        .zip(right_args)
+                        .all(|(l, r)| self.same_type(l, r))
+            }
+            (Type::Array(left, left_length), Type::Array(right, right_length)) => {
+                self.same_type(left, right) && self.same_type(left_length, right_length)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a value of type `actual` may be used where `expected` is
+    /// required. Numbers widen implicitly to types holding all their values.
+    fn is_assignable(&self, expected: &Type, actual: &Type) -> bool {
+        self.same_type(expected, actual) || actual.widens_to(expected)
+    }
+
+    /// Reports a mismatch unless `expr`, which has type `actual`, fits into
+    /// `expected`. Number literals fit into any type that can hold them.
+    fn expect_type(&mut self, expected: &Type, actual: &Type, expr: &ExpressionNode) {
+        let fits_literal = match literal_type(expr) {
+            Some(Type::F32) => expected.is_float(),
+            Some(_) => expected.is_numeric(),
+            None => false,
+        };
+
+        if let ExpressionKind::IntLit(value) = expr.kind
+            && let Some((min, max)) = expected.int_range()
+            && !(min..=max).contains(&value)
+        {
+            self.error(
+                Diagnostic::error(format!("literal out of range for `{}`", expected))
+                    .with_span(expr.span)
+                    .with_label(format!("`{}` holds {} to {}", expected, min, max)),
+            );
+            return;
+        }
+
+        if !fits_literal && !self.is_assignable(expected, actual) {
+            self.error(mismatch(expected, actual, expr.span));
+        }
+    }
+
+    /// Like `expect_type`, but where a `dyn Trait` is expected a value of a
+    /// type implementing the trait is turned into a trait object. Arrays of
+    /// number literals are checked again with the expected element type, as
+    /// C can't convert between array types like between numbers.
+    fn coerce(&mut self, expected: &Type, actual: &Type, expr: &mut ExpressionNode) {
+        if let Type::Array(..) = expected
+            && !self.same_type(expected, actual)
+            && is_literal_array(expr)
+        {
+            let actual = check_expected(expr, expected, self);
+            return self.expect_type(expected, &actual, expr);
+        }
+
+        let Type::Dyn(trait_name) = expected else {
+            return self.expect_type(expected, actual, expr);
+        };
+        if matches!(actual, Type::Dyn(_) | Type::Unknown) {
+            return self.expect_type(expected, actual, expr);
+        }
+
+        if !self.implements(actual, trait_name) {
+            let diagnostic = Diagnostic::error(format!(
+                "type `{}` does not implement `{}`",
+                actual, trait_name
+            ))
+            .with_span(expr.span)
+            .with_label(format!("expected `{}`", expected));
+            self.error(self.implement_hint(diagnostic, actual, trait_name));
+            return;
+        }
+
+        let span = expr.span;
+        let value = std::mem::replace(&mut expr.kind, ExpressionKind::Continue);
+        expr.kind = ExpressionKind::DynCoercion(DynCoercionNode {
+            value: Box::new(ExpressionNode { kind: value, span }),
+            value_type: actual.clone(),
+            trait_name: trait_name.clone(),
+            span,
+        });
+    }
+
+    /// Type parameters implement the traits they are bounded by, trait
+    /// objects their own trait and every other type the traits of the impls
+    /// applying to it.
+    fn implements(&self, implementor: &Type, trait_name: &str) -> bool {
+        if let Some(type_param) = self.type_param(implementor) {
+            return type_param.bounds.iter().any(|b| b == trait_name);
+        }
+        if let Type::Dyn(name) = implementor {
+            return name == trait_name;
+        }
+
+        self.impls
+            .iter()
+            .filter(|i| i.trait_name == trait_name)
+            .any(|i| self.impl_applies(i, implementor))
+    }
+
+    /// Whether an impl applies to `implementor`, with type arguments that
+    /// meet the bounds of its type parameters.
+    fn impl_applies(&self, implementation: &Impl, implementor: &Type) -> bool {
+        let type_params = &implementation.type_params;
+        let mut type_args = vec![None; type_params.len()];
+        if !match_type(
+            &implementation.for_type,
+            implementor,
+            type_params,
+            &mut type_args,
+        ) {
+            return false;
+        }
+
+        type_params.iter().zip(type_args).all(|(param, arg)| {
+            let arg = arg.unwrap_or(Type::Unknown);
+            param
+                .bounds
+                .iter()
+                .all(|bound| self.implements(&arg, bound))
+        })
+    }
+
+    /// How to make a type implement a trait, added to errors about it not
+    /// doing so.
+    fn implement_hint(
+        &self,
+        diagnostic: Diagnostic,
+        actual: &Type,
+        trait_name: &str,
+    ) -> Diagnostic {
+        match self.type_param(actual) {
+            Some(type_param) => diagnostic.with_note(format!(
+                "add a bound to the type parameter: `{} impl {}`",
+                type_param.name, trait_name
+            )),
+            None => diagnostic.with_note(format!("add `impl {} for {}`", trait_name, actual)),
+        }
+    }
+
+    /// Reports the type arguments of a generic function, struct or enum that
+    /// don't implement the traits their type parameter is bounded by, and
+    /// types given for const parameters or numbers for type parameters,
+    /// which are replaced by `Unknown` to not report their uses as well.
+    fn check_bounds(&mut self, type_params: &[TypeParamNode], type_args: &mut [Type], span: Span) {
+        for (param, arg) in type_params.iter().zip(type_args) {
+            if *arg == Type::Unknown {
+                continue;
+            }
+
+            let is_const = matches!(arg, Type::Const(_))
+                || self.type_param(arg).is_some_and(|p| p.const_type.is_some());
+            if is_const != param.const_type.is_some() {
+                let message = match is_const {
+                    false => format!(
+                        "expected a number for the const parameter `{}`, found the type `{}`",
+                        param.name, arg
+                    ),
+                    true => format!(
+                        "expected a type for the type parameter `{}`, found `{}`",
+                        param.name, arg
+                    ),
+                };
+                self.error(
+                    Diagnostic::error(message)
+                        .with_span(span)
+                        .with_secondary(param.span, "declared here"),
+                );
+                *arg = Type::Unknown;
+                continue;
+            }
+
+            for bound in &param.bounds {
+                if self.implements(arg, bound) {
+                    continue;
+                }
+                let mut diagnostic =
+                    Diagnostic::error(format!("type `{}` does not implement `{}`", arg, bound))
+                        .with_span(span);
+                if arg.to_string() != param.name {
+                    diagnostic =
+                        diagnostic.with_label(format!("`{}` is `{}` here", param.name, arg));
+                }
+                let diagnostic = diagnostic.with_secondary(param.span, "required by this bound");
+                let diagnostic = self.implement_hint(diagnostic, arg, bound);
+                self.error(diagnostic);
+            }
+        }
+    }
+
+    /// Fields of a struct type, with the type arguments of generic structs
+    /// substituted.
+    fn struct_fields(&self, struct_type: &Type) -> Option<Vec<StructFieldNode>> {
+        let (
+            Some(Binding::Struct {
+                type_params,
+                fields,
+                ..
+            }),
+            args,
+        ) = self.type_binding(struct_type)
+        else {
+            return None;
+        };
+
+        let fields = fields.iter().map(|field| StructFieldNode {
+            field_type: substitute(&field.field_type, type_params, args),
+            ..field.clone()
+        });
+        Some(fields.collect())
+    }
+
+    /// Variants of an enum type, with the type arguments of generic enums
+    /// substituted.
+    fn enum_variants(&self, enum_type: &Type) -> Option<Vec<(String, Vec<Type>)>> {
+        let (
+            Some(Binding::Enum {
+                type_params,
+                variants,
+            }),
+            args,
+        ) = self.type_binding(enum_type)
+        else {
+            return None;
+        };
+
+        let variants = variants.iter().map(|(name, field_types)| {
+            let field_types = field_types
+                .iter()
+                .map(|t| substitute(t, type_params, args))
+                .collect();
+            (name.clone(), field_types)
+        });
+        Some(variants.collect())
+    }
+
+    /// Binding of the struct or enum a type names, with the type arguments.
+    fn type_binding<'a>(&'a self, named_type: &'a Type) -> (Option<&'a Binding>, &'a [Type]) {
+        match named_type {
+            Type::Named(name) if !self.is_type_param(named_type) => (self.lookup(name), &[]),
+            Type::Generic(name, args) => (self.lookup(name), args),
+            _ => (None, &[]),
+        }
+    }
+}
+
+/// Replaces the type parameters in a type by their values.
+fn substitute(value_type: &Type, type_params: &[TypeParamNode], type_args: &[Type]) -> Type {
+    match value_type {
+        Type::Named(name) => match type_params.iter().position(|p| p.name == *name) {
+            Some(i) => type_args.get(i).cloned().unwrap_or(Type::Unknown),
+            None => value_type.clone(),
+        },
+        Type::Generic(name, args) => Type::Generic(
+            name.clone(),
+            args.iter()
+                .map(|arg| substitute(arg, type_params, type_args))
+                .collect(),
+        ),
+        Type::Array(element, length) => Type::Array(
+            Box::new(substitute(element, type_params, type_args)),
+            Box::new(substitute(length, type_params, type_args)),
+        ),
+        _ => value_type.clone(),
+    }
+}
+
+/// Matches a type written in terms of type parameters against a type,
+/// collecting the values of the parameters. Fails if a parameter would need
+/// two different values.
+fn match_type(
+    pattern: &Type,
+    matched: &Type,
+    type_params: &[TypeParamNode],
+    type_args: &mut [Option<Type>],
+) -> bool {
+    if let Type::Named(name) = pattern
+        && let Some(i) = type_params.iter().position(|p| p.name == *name)
+    {
+        return match &type_args[i] {
+            Some(previous) => previous == matched,
+            None => {
+                type_args[i] = Some(matched.clone());
+                true
+            }
+        };
+    }
+
+    match (pattern, matched) {
+        (Type::Generic(name, args), Type::Generic(matched_name, matched_args)) => {
+            name == matched_name
+                && args.len() == matched_args.len()
+                && args
+                    .iter()
+                    .zip(matched_args)
+                    .all(|(arg, matched)| match_type(arg, matched, type_params, type_args))
+        }
+        (Type::Array(element, length), Type::Array(matched_element, matched_length)) => {
+            match_type(element, matched_element, type_params, type_args)
+                && match_type(length, matched_length, type_params, type_args)
+        }
+        _ => pattern == matched,
+    }
+}
+
+/// Whether an expression is an array made only of number literals, like
+/// `[1, 2]` or `[0.0; N]`.
+fn is_literal_array(expr: &ExpressionNode) -> bool {
+    let is_literal = |e: &ExpressionNode| literal_type(e).is_some() || is_literal_array(e);
+    match &expr.kind {
+        ExpressionKind::ArrayLit(node) => {
+            !node.elements.is_empty() && node.elements.iter().all(is_literal)
+        }
+        ExpressionKind::ArrayRepeat(node) => is_literal(&node.value),
+        _ => false,
+    }
+}
+
+fn mismatch(expected: &Type, actual: &Type, span: Span) -> Diagnostic {
+    Diagnostic::error(format!(
+        "mismatched types: expected `{}`, found `{}`",
+        expected, actual
+    ))
+    .with_span(span)
+    .with_label(format!("expected `{}`", expected))
+}
+
+/// Type of an expression made only of number literals, like `-1` or `2 * 3`,
+/// which can still adapt to the type it is used as.
+fn literal_type(expr: &ExpressionNode) -> Option<Type> {
+    match &expr.kind {
+        ExpressionKind::IntLit(value) => Some(int_literal_type(*value)),
+        ExpressionKind::FloatLit(_) => Some(Type::F32),
+        ExpressionKind::UnaryExpr(node) if node.op == UnaryOp::Negate => {
+            literal_type(&node.operand)
+        }
+        ExpressionKind::BinaryExpr(node) if node.op.is_arithmetic() => {
+            let left = literal_type(&node.left)?;
+            let right = literal_type(&node.right)?;
+            Some(widest_literal(left, right))
+        }
+        _ => None,
+    }
+}
+
+/// Type of an integer literal used as is, the first of `i32`, `i64` and
+/// `u64` holding it.
+fn int_literal_type(value: i128) -> Type {
+    match value {
+        value if i32::try_from(value).is_ok() => Type::I32,
+        value if value < 0 || i64::try_from(value).is_ok() => Type::I64,
+        _ => Type::U64,
+    }
+}
+
+/// Checks the program and records the inferred type of every variable
+/// declaration in its `var_type` and of every function without a declared
+/// return type in its `return_type`, for code generation.
+pub fn check_types(program: &mut ProgramNode) -> Result<(), Vec<Diagnostic>> {
+    let mut ctx = Context {
+        scopes: vec![HashMap::new()],
+        inferred: vec![],
+        inferring: vec![],
+        functions: vec![],
+        type_params: vec![],
+        impls: vec![],
+        diagnostics: vec![],
+    };
+
+    check_expressions(&mut program.expressions, &mut ctx);
+
+    if ctx.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(ctx.diagnostics)
+    }
+}
+
+/// Checks a sequence of expressions sharing one scope and returns the type
+/// of the last one, which is the value of a block. Functions, structs and
+/// traits are declared first, as they can be used before their definition,
+/// followed by the impls of the traits. Function bodies are checked before
+/// everything else, functions with an omitted return type possibly earlier
+/// by a call needing it.
+fn check_expressions(expressions: &mut [ExpressionNode], ctx: &mut Context) -> Type {
+    let mut inferred = HashMap::new();
+    for (i, expr) in expressions.iter().enumerate() {
+        match &expr.kind {
+            ExpressionKind::FuncDef(node) if node.return_type.is_none() => {
+                inferred.insert(i, declare_inferred_func_def(node, ctx));
+            }
+            ExpressionKind::FuncDef(node) => declare_func_def(node, ctx),
+            ExpressionKind::StructDef(node) => declare_struct_def(node, ctx),
+            ExpressionKind::EnumDef(node) => declare_enum_def(node, ctx),
+            ExpressionKind::TraitDef(node) => declare_trait_def(node, ctx),
+            _ => {}
+        }
+    }
+
+    for expr in expressions.iter() {
+        if let ExpressionKind::ImplBlock(node) = &expr.kind {
+            declare_impl_block(node, ctx);
+        }
+    }
+
+    for (i, expr) in expressions.iter_mut().enumerate() {
+        match &mut expr.kind {
+            ExpressionKind::FuncDef(node) => match inferred.get(&i) {
+                Some(&id) => {
+                    if !ctx.inferred[id].checked {
+                        check_inferred_func_def(id, ctx);
+                    }
+                    *node = ctx.inferred[id].node.clone();
+                }
+                None => check_func_def(node, None, ctx),
+            },
+            ExpressionKind::StructDef(node) => check_methods(node, ctx),
+            ExpressionKind::TraitDef(node) => check_trait_def(node, ctx),
+            ExpressionKind::ImplBlock(node) => check_impl_block(node, ctx),
+            _ => {}
+        }
+    }
+
+    let mut value_type = Type::Void;
+    for expr in expressions.iter_mut() {
+        value_type = match expr.kind {
+            ExpressionKind::FuncDef(_)
+            | ExpressionKind::TraitDef(_)
+            | ExpressionKind::ImplBlock(_) => Type::Void,
+            _ => check_expression(expr, ctx),
+        };
+    }
+    value_type
+}
+
+fn check_block(block: &mut BlockNode, ctx: &mut Context) -> Type {
+    ctx.scopes.push(HashMap::new());
+    let value_type = check_expressions(&mut block.expressions, ctx);
+    ctx.scopes.pop();
+    value_type
+}
+
+fn check_expression(expr: &mut ExpressionNode, ctx: &mut Context) -> Type {
+    // Bare field names inside methods are accesses of `self`.
+    if let ExpressionKind::VarAccess(node) = &expr.kind
+        && let Some(Binding::Field(_)) = ctx.lookup(&node.name)
+    {
+        expr.kind = ExpressionKind::StructFieldAccess(self_field(&node.name, node.span));
+    }
+
+    match &mut expr.kind {
+        ExpressionKind::VarDecl(node) => {
+            check_var_decl(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::Assignment(node) => {
+            check_assignment(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::BinaryExpr(node) => check_binary_expr(node, ctx),
+        ExpressionKind::UnaryExpr(node) => check_unary_expr(node, ctx),
+        ExpressionKind::FuncDef(node) => {
+            check_func_def(node, None, ctx);
+            Type::Void
+        }
+        ExpressionKind::ReturnExpr(node) => {
+            let return_type = ctx.functions.last().and_then(|f| f.return_type.clone());
+            let value_type = match (&mut node.expression, return_type) {
+                (None, _) => Type::Void,
+                (Some(value), Some(return_type)) => check_expected(value, &return_type, ctx),
+                (Some(value), None) => check_expression(value, ctx),
+            };
+            check_return(node.expression.as_deref_mut(), value_type, node.span, ctx);
+            Type::Void
+        }
+        ExpressionKind::FuncCall(node) => check_func_call(node, ctx),
+        ExpressionKind::IntLit(value) => {
+            let value_type = int_literal_type(*value);
+            if *value < 0 {
+                // Reports values below `i64::MIN`, nothing can hold them.
+                ctx.expect_type(&value_type, &value_type, expr);
+            }
+            value_type
+        }
+        ExpressionKind::FloatLit(_) => Type::F32,
+        ExpressionKind::StrLit(_) => Type::String,
+        ExpressionKind::VarAccess(node) => ctx.variable(&node.name),
+        ExpressionKind::StructDef(node) => {
+            check_struct_def(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::StructInit(node) => check_struct_init(node, None, ctx),
+        ExpressionKind::ArrayLit(node) => check_array_lit(node, None, ctx),
+        ExpressionKind::ArrayRepeat(node) => check_array_repeat(node, None, ctx),
+        ExpressionKind::Index(node) => {
+            let array_type = check_expression(&mut node.object, ctx);
+            element_type(&array_type, &mut node.index, ctx)
+        }
+        ExpressionKind::StructFieldAccess(node) => check_struct_field_access(node, ctx),
+        ExpressionKind::MethodCall(node) => check_method_call(node, ctx),
+        ExpressionKind::EnumDef(node) => {
+            check_enum_def(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::EnumInit(node) => check_enum_init(node, None, ctx),
+        ExpressionKind::TraitDef(node) => {
+            check_trait_def(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::ImplBlock(node) => {
+            check_impl_block(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::Module(_) | ExpressionKind::Use(_) => {
+            unreachable!("modules are flattened before name resolution")
+        }
+        // Only inserted around values that were checked already.
+        ExpressionKind::DynCoercion(node) => Type::Dyn(node.trait_name.clone()),
+        ExpressionKind::If(node) => check_if_expr(node, ctx),
+        ExpressionKind::Match(node) => check_match_expr(node, ctx),
+        ExpressionKind::While(node) => {
+            check_condition(&mut node.condition, ctx);
+            check_block(&mut node.body, ctx);
+            Type::Void
+        }
+        ExpressionKind::For(node) => {
+            check_for_expr(node, ctx);
+            Type::Void
+        }
+        ExpressionKind::CImport(_) | ExpressionKind::Break | ExpressionKind::Continue => Type::Void,
+    }
+}
+
+/// Checks a value whose type is required by its context, which lets struct
+/// and enum literals omit type arguments their values say nothing about,
+/// like in `return Option::None`.
+fn check_expected(expr: &mut ExpressionNode, expected: &Type, ctx: &mut Context) -> Type {
+    match &mut expr.kind {
+        ExpressionKind::StructInit(node) => check_struct_init(node, Some(expected), ctx),
+        ExpressionKind::EnumInit(node) => check_enum_init(node, Some(expected), ctx),
+        ExpressionKind::ArrayLit(node) => check_array_lit(node, Some(expected), ctx),
+        ExpressionKind::ArrayRepeat(node) => check_array_repeat(node, Some(expected), ctx),
+        _ => check_expression(expr, ctx),
+    }
+}
+
+fn check_condition(condition: &mut ExpressionNode, ctx: &mut Context) {
+    let condition_type = check_expression(condition, ctx);
+    ctx.expect_type(&Type::Bool, &condition_type, condition);
+}
+
+/// Checks both branches and returns the type of the value of the `if`. An
+/// `if` without `else`, or with a branch not ending in a value, is `void`.
+fn check_if_expr(node: &mut IfExprNode, ctx: &mut Context) -> Type {
+    check_condition(&mut node.condition, ctx);
+    let then_type = check_block(&mut node.then_block, ctx);
+
+    let value_type = match &mut node.else_block {
+        None => Type::Void,
+        Some(else_block) => {
+            let else_type = check_block(else_block, ctx);
+
+            match (then_type, else_type) {
+                (Type::Void, _) | (_, Type::Void) => Type::Void,
+                (Type::Unknown, other) | (other, Type::Unknown) => other,
+                (then_type, else_type) if ctx.is_assignable(&then_type, &else_type) => then_type,
+                (then_type, else_type) if ctx.is_assignable(&else_type, &then_type) => else_type,
+                (then_type, else_type) => {
+                    let value_span =
+                        |block: &BlockNode| block.expressions.last().map_or(block.span, |e| e.span);
+                    ctx.error(
+                        Diagnostic::error("`if` and `else` have incompatible types")
+                            .with_span(value_span(else_block))
+                            .with_label(format!("expected `{}`, found `{}`", then_type, else_type))
+                            .with_secondary(
+                                value_span(&node.then_block),
+                                format!("this is a `{}`", then_type),
+                            ),
+                    );
+                    Type::Unknown
+                }
+            }
+        }
+    };
+
+    let then_value = node.then_block.expressions.last();
+    let error = Diagnostic::error(format!("`if` can't have a value of type `{}`", value_type))
+        .with_span(then_value.map_or(node.then_block.span, |e| e.span))
+        .with_label("the value of a branch may point into it");
+    reject_escaping_dyn(&value_type, error, ctx);
+
+    node.value_type = Some(value_type.clone());
+    value_type
+}
+
+/// Checks the patterns and arms and returns the type of the value of the
+/// `match`, which is `void` unless every arm ends in a value.
+fn check_match_expr(node: &mut MatchExprNode, ctx: &mut Context) -> Type {
+    let errors_before = ctx.diagnostics.len();
+    let subject_type = check_expression(&mut node.subject, ctx);
+
+    // The matched value is stored in a C variable, which needs a type.
+    if subject_type == Type::Unknown && ctx.diagnostics.len() == errors_before {
+        ctx.error(
+            Diagnostic::error("cannot infer the type of the matched value")
+                .with_span(node.subject.span)
+                .with_note("store it in a variable with an explicit type first"),
+        );
+    }
+
+    let mut value_type: Option<(Type, Span)> = None;
+    for arm in &mut node.arms {
+        // Each arm gets a scope for its bindings, shared with the guard.
+        ctx.scopes.push(HashMap::new());
+        check_pattern(&mut arm.pattern, &subject_type, ctx);
+        if let Some(guard) = &mut arm.guard {
+            check_condition(guard, ctx);
+        }
+        let arm_type = check_expressions(&mut arm.body.expressions, ctx);
+        ctx.scopes.pop();
+
+        let arm_span = arm
+            .body
+            .expressions
+            .last()
+            .map_or(arm.body.span, |e| e.span);
+        value_type = match (value_type, arm_type) {
+            (None, arm_type) => Some((arm_type, arm_span)),
+            (Some((Type::Void, span)), _) | (Some((_, span)), Type::Void) => {
+                Some((Type::Void, span))
+            }
+            (Some((current, span)), Type::Unknown) => Some((current, span)),
+            (Some((Type::Unknown, _)), arm_type) => Some((arm_type, arm_span)),
+            (Some((current, span)), arm_type) if ctx.is_assignable(&current, &arm_type) => {
+                Some((current, span))
+            }
+            (Some((current, _)), arm_type) if ctx.is_assignable(&arm_type, &current) => {
+                Some((arm_type, arm_span))
+            }
+            (Some((current, span)), arm_type) => {
+                ctx.error(
+                    Diagnostic::error("`match` arms have incompatible types")
+                        .with_span(arm_span)
+                        .with_label(format!("expected `{}`, found `{}`", current, arm_type))
+                        .with_secondary(span, format!("this is a `{}`", current)),
+                );
+                Some((Type::Unknown, span))
+            }
+        };
+    }
+
+    if subject_type != Type::Unknown {
+        check_coverage(&node.arms, &subject_type, node.span, ctx);
+    }
+
+    let value_type = value_type.map_or(Type::Void, |(value_type, _)| value_type);
+    let error = Diagnostic::error(format!(
+        "`match` can't have a value of type `{}`",
+        value_type
+    ))
+    .with_span(node.span)
+    .with_label("the value of an arm may point into it");
+    reject_escaping_dyn(&value_type, error, ctx);
+
+    node.subject_type = Some(subject_type);
+    node.value_type = Some(value_type.clone());
+    value_type
+}
+
+/// Checks a pattern against the type of the value it matches and declares
+/// its bindings. Bindings naming a variant without values, like `None`, are
+/// turned into variant patterns.
+fn check_pattern(pattern: &mut PatternNode, expected: &Type, ctx: &mut Context) {
+    if let PatternKind::Binding { name, .. } = &pattern.kind
+        && let Some(enum_name) = unit_variant_enum(name, expected, ctx)
+    {
+        pattern.kind = PatternKind::Variant {
+            enum_name: Some(enum_name),
+            enum_type: None,
+            variant: name.clone(),
+            fields: vec![],
+        };
+    }
+
+    match &mut pattern.kind {
+        PatternKind::Wildcard => {}
+        PatternKind::Literal(value) => {
+            let value_type = check_expression(value, ctx);
+            ctx.expect_type(expected, &value_type, value);
+        }
+        PatternKind::Comparison { op, value } => {
+            let value_type = check_expression(value, ctx);
+            let (accepts, description): (fn(&Type) -> bool, _) = match op.is_equality() {
+                true => (|t| t.is_numeric() || *t == Type::Bool, "a number or `bool`"),
+                false => (Type::is_numeric, "a number"),
+            };
+
+            if *expected != Type::Unknown && !ctx.is_type_param(expected) && !accepts(expected) {
+                ctx.error(
+                    Diagnostic::error(format!("cannot apply `{}` to a `{}`", op, expected))
+                        .with_span(pattern.span)
+                        .with_label(format!("the matched value is not {}", description)),
+                );
+            } else if check_operand(*op, value, &value_type, accepts, description, ctx)
+                && !is_comparable(expected, &value_type, literal_type(value).is_some(), ctx)
+            {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "cannot compare a `{}` with a `{}`",
+                        expected, value_type
+                    ))
+                    .with_span(pattern.span),
+                );
+            }
+        }
+        PatternKind::Binding { name, value_type } => {
+            *value_type = Some(expected.clone());
+            let binding = Binding::Variable {
+                var_type: expected.clone(),
+                mutable: false,
+            };
+            ctx.declare(name, binding);
+        }
+        PatternKind::Variant {
+            enum_name,
+            enum_type,
+            variant,
+            fields,
+        } => {
+            let field_types = variant_field_types(
+                enum_name,
+                variant,
+                fields.len(),
+                expected,
+                pattern.span,
+                ctx,
+            );
+            *enum_type = enum_name.as_ref().map(|name| match expected.base_name() {
+                Some(expected_name) if expected_name == name => expected.clone(),
+                _ => Type::Named(name.clone()),
+            });
+
+            for (i, field) in fields.iter_mut().enumerate() {
+                let field_type = field_types
+                    .as_ref()
+                    .map_or(Type::Unknown, |types| types[i].clone());
+                check_pattern(field, &field_type, ctx);
+            }
+        }
+    }
+}
+
+/// Enum of `expected`, or of any enum in scope otherwise, with a variant
+/// named `name` that holds no values.
+fn unit_variant_enum(name: &str, expected: &Type, ctx: &Context) -> Option<String> {
+    let is_unit_variant = |variants: &Vec<(String, Vec<Type>)>| {
+        variants
+            .iter()
+            .any(|(variant, fields)| variant == name && fields.is_empty())
+    };
+
+    if let Some(enum_name) = expected.base_name()
+        && let (Some(Binding::Enum { variants, .. }), _) = ctx.type_binding(expected)
+        && is_unit_variant(variants)
+    {
+        return Some(enum_name.to_string());
+    }
+
+    ctx.scopes.iter().rev().find_map(|scope| {
+        scope.iter().find_map(|(enum_name, binding)| match binding {
+            Binding::Enum { variants, .. } if is_unit_variant(variants) => Some(enum_name.clone()),
+            _ => None,
+        })
+    })
+}
+
+/// Types of the values held by the variant a pattern matches, filling in
+/// the enum of the pattern when omitted. Reports patterns that can't match
+/// a value of type `expected`.
+fn variant_field_types(
+    enum_name: &mut Option<String>,
+    variant: &str,
+    field_count: usize,
+    expected: &Type,
+    span: Span,
+    ctx: &mut Context,
+) -> Option<Vec<Type>> {
+    let expected_enum = match ctx.type_binding(expected) {
+        (Some(Binding::Enum { .. }), _) => expected.base_name(),
+        _ => None,
+    };
+
+    let name = match (&enum_name, expected_enum) {
+        (Some(name), Some(expected_name)) if name != expected_name => {
+            ctx.error(mismatch(expected, &Type::Named(name.clone()), span));
+            return None;
+        }
+        (Some(name), _) => name.clone(),
+        (None, Some(expected_name)) => expected_name.to_string(),
+        (None, None) => {
+            if *expected != Type::Unknown {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "mismatched types: expected `{}`, found enum variant `{}`",
+                        expected, variant
+                    ))
+                    .with_span(span)
+                    .with_label(format!("expected `{}`", expected)),
+                );
+            }
+            return None;
+        }
+    };
+    *enum_name = Some(name.clone());
+
+    if expected_enum.is_none() && *expected != Type::Unknown {
+        ctx.error(mismatch(expected, &Type::Named(name), span));
+        return None;
+    }
+
+    // Name resolution already reported unknown enums.
+    let enum_type = match expected {
+        Type::Unknown => Type::Named(name.clone()),
+        expected => expected.clone(),
+    };
+    let variants = ctx.enum_variants(&enum_type)?;
+
+    let Some((_, field_types)) = variants.iter().find(|(v, _)| v == variant) else {
+        ctx.error(
+            Diagnostic::error(format!("no variant `{}` on enum `{}`", variant, name))
+                .with_span(span)
+                .with_label("unknown variant"),
+        );
+        return None;
+    };
+
+    if field_types.len() != field_count {
+        ctx.error(
+            Diagnostic::error(format!(
+                "variant `{}::{}` holds {} value(s) but the pattern has {}",
+                name,
+                variant,
+                field_types.len(),
+                field_count
+            ))
+            .with_span(span),
+        );
+        return None;
+    }
+
+    Some(field_types.clone())
+}
+
+static WILDCARD: PatternKind = PatternKind::Wildcard;
+
+/// Reports arms that can never be reached and values no arm matches.
+/// Guarded arms may always fail, so they don't cover anything.
+fn check_coverage(arms: &[MatchArmNode], subject_type: &Type, span: Span, ctx: &mut Context) {
+    let mut covering: Vec<&MatchArmNode> = vec![];
+
+    for arm in arms {
+        let covered_by = covering
+            .iter()
+            .find(|previous| subsumes(&previous.pattern.kind, &arm.pattern.kind));
+        let rows = covering.iter().map(|a| vec![&a.pattern.kind]).collect();
+
+        if let Some(previous) = covered_by {
+            ctx.error(
+                Diagnostic::error("unreachable pattern")
+                    .with_span(arm.pattern.span)
+                    .with_label("this arm is never reached")
+                    .with_secondary(previous.pattern.span, "already matched here"),
+            );
+        } else if !covering.is_empty()
+            && missing_values(rows, std::slice::from_ref(subject_type), ctx).is_none()
+        {
+            ctx.error(
+                Diagnostic::error("unreachable pattern")
+                    .with_span(arm.pattern.span)
+                    .with_label("this arm is never reached")
+                    .with_note("the arms above already match every value"),
+            );
+        }
+
+        if arm.guard.is_none() {
+            covering.push(arm);
+        }
+    }
+
+    let rows = covering.iter().map(|a| vec![&a.pattern.kind]).collect();
+    let missing = uncovered_values(rows, subject_type, ctx);
+    if !missing.is_empty() {
+        let list = quoted_list(&missing);
+        let note = match (missing[0].as_str(), missing.len()) {
+            ("_", _) => String::from("add a `_` arm matching every other value"),
+            (_, 1) => format!(
+                "add an arm matching {}, or a `_` arm matching everything else",
+                list
+            ),
+            _ => format!(
+                "add arms matching {}, or a `_` arm matching everything else",
+                list
+            ),
+        };
+        ctx.error(
+            Diagnostic::error(format!("non-exhaustive patterns: {} not covered", list))
+                .with_span(span)
+                .with_label(format!("{} not covered", list))
+                .with_note(note),
+        );
+    }
+}
+
+/// Values of the subject no arm matches, one for every variant of an enum
+/// that isn't fully covered.
+fn uncovered_values(
+    rows: Vec<Vec<&PatternKind>>,
+    subject_type: &Type,
+    ctx: &Context,
+) -> Vec<String> {
+    let Some((enum_name, variants)) = subject_type
+        .base_name()
+        .zip(ctx.enum_variants(subject_type))
+    else {
+        let missing = missing_values(rows, std::slice::from_ref(subject_type), ctx);
+        return missing.into_iter().flatten().collect();
+    };
+
+    variants
+        .iter()
+        .filter_map(|(variant, field_types)| {
+            missing_in_variant(&rows, enum_name, variant, field_types, &[], ctx)
+        })
+        .flatten()
+        .collect()
+}
+
+/// `a`, `a` and `b`, or `a`, `b` and `c`.
+fn quoted_list(values: &[String]) -> String {
+    let quoted: Vec<_> = values.iter().map(|v| format!("`{}`", v)).collect();
+    match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => quoted.concat(),
+    }
+}
+
+/// Whether every value `pattern` matches is already matched by `previous`.
+fn subsumes(previous: &PatternKind, pattern: &PatternKind) -> bool {
+    match (previous, pattern) {
+        (previous, _) if previous.is_irrefutable() => true,
+        (PatternKind::Literal(previous), PatternKind::Literal(pattern)) => {
+            literal_key(previous).is_some() && literal_key(previous) == literal_key(pattern)
+        }
+        (
+            PatternKind::Variant {
+                variant: previous,
+                fields: previous_fields,
+                ..
+            },
+            PatternKind::Variant {
+                variant, fields, ..
+            },
+        ) => {
+            previous == variant
+                && previous_fields
+                    .iter()
+                    .zip(fields)
+                    .all(|(p, f)| subsumes(&p.kind, &f.kind))
+        }
+        _ => false,
+    }
+}
+
+fn literal_key(expr: &ExpressionNode) -> Option<String> {
+    match &expr.kind {
+        ExpressionKind::IntLit(value) => Some(value.to_string()),
+        ExpressionKind::FloatLit(value) => Some(format!("{:?}", value)),
+        ExpressionKind::StrLit(value) => Some(value.clone()),
+        ExpressionKind::UnaryExpr(node) if node.op == UnaryOp::Negate => {
+            literal_key(&node.operand).map(|key| format!("-{}", key))
+        }
+        _ => None,
+    }
+}
+
+/// Values of the given types no row of patterns matches, one per column, or
+/// `None` if the rows match everything. Enums are split by variant, any
+/// other type is only covered by wildcards and bindings.
+fn missing_values(
+    rows: Vec<Vec<&PatternKind>>,
+    types: &[Type],
+    ctx: &Context,
+) -> Option<Vec<String>> {
+    let Some((first_type, rest_types)) = types.split_first() else {
+        return rows.is_empty().then(Vec::new);
+    };
+
+    let variants = first_type.base_name().zip(ctx.enum_variants(first_type));
+
+    let Some((enum_name, variants)) = variants else {
+        let rows = rows
+            .into_iter()
+            .filter(|row| row[0].is_irrefutable())
+            .map(|row| row[1..].to_vec())
+            .collect();
+        let mut missing = missing_values(rows, rest_types, ctx)?;
+        missing.insert(0, "_".to_string());
+        return Some(missing);
+    };
+
+    variants.iter().find_map(|(variant, field_types)| {
+        missing_in_variant(&rows, enum_name, variant, field_types, rest_types, ctx)
+    })
+}
+
+/// Values, one per column, no row matches whose first column holds the given
+/// variant, or `None` if the rows match all of them.
+fn missing_in_variant(
+    rows: &[Vec<&PatternKind>],
+    enum_name: &str,
+    variant: &str,
+    field_types: &[Type],
+    rest_types: &[Type],
+    ctx: &Context,
+) -> Option<Vec<String>> {
+    let rows = rows
+        .iter()
+        .filter_map(|row| {
+            let fields: Vec<&PatternKind> = match row[0] {
+                PatternKind::Variant {
+                    variant: v, fields, ..
+                } if v == variant => fields.iter().map(|f| &f.kind).collect(),
+                kind if kind.is_irrefutable() => vec![&WILDCARD; field_types.len()],
+                _ => return None,
+            };
+            Some([fields, row[1..].to_vec()].concat())
+        })
+        .collect();
+    let types = [field_types, rest_types].concat();
+
+    let mut missing = missing_values(rows, &types, ctx)?;
+    let fields = missing.drain(..field_types.len()).collect::<Vec<_>>();
+    let value = match fields.is_empty() {
+        true => format!("{}::{}", enum_name, variant),
+        false => format!("{}::{}({})", enum_name, variant, fields.join(", ")),
+    };
+    missing.insert(0, value);
+    Some(missing)
+}
+
+fn check_for_expr(node: &mut ForExprNode, ctx: &mut Context) {
+    let start_type = check_expression(&mut node.start, ctx);
+    let end_type = check_expression(&mut node.end, ctx);
+
+    let mut bounds_ok = true;
+    for (bound, bound_type) in [(&node.start, &start_type), (&node.end, &end_type)] {
+        if *bound_type != Type::Unknown && !bound_type.is_integer() {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "range bounds must be integers, found `{}`",
+                    bound_type
+                ))
+                .with_span(bound.span)
+                .with_label("expected an integer"),
+            );
+            bounds_ok = false;
+        }
+    }
+
+    let var_type = match arithmetic_type((&node.start, start_type), (&node.end, end_type), ctx) {
+        var_type if bounds_ok && var_type.is_integer() => var_type,
+        _ => Type::I32,
+    };
+
+    // The body shares its scope with the loop variable.
+    ctx.scopes.push(HashMap::new());
+    let binding = Binding::Variable {
+        var_type: var_type.clone(),
+        mutable: false,
+    };
+    ctx.declare(&node.variable, binding);
+    check_expressions(&mut node.body.expressions, ctx);
+    ctx.scopes.pop();
+
+    node.var_type = Some(var_type);
+}
+
+fn check_var_decl(node: &mut VarDeclNode, ctx: &mut Context) {
+    let errors_before = ctx.diagnostics.len();
+    let value_type = match &node.var_type {
+        Some(var_type) => check_expected(&mut node.value, var_type, ctx),
+        None => check_expression(&mut node.value, ctx),
+    };
+    // An unknown type caused by an error in the value was reported already.
+    let value_failed = ctx.diagnostics.len() > errors_before;
+
+    let var_type = match &node.var_type {
+        Some(var_type) => {
+            ctx.coerce(var_type, &value_type, &mut node.value);
+            var_type.clone()
+        }
+        None => match value_type {
+            Type::Void => {
+                ctx.error(
+                    Diagnostic::error(format!("`{}` can't be assigned a `void` value", node.name))
+                        .with_span(node.value.span)
+                        .with_label("this has no value"),
+                );
+                Type::Unknown
+            }
+            Type::Unknown if value_failed => Type::Unknown,
+            Type::Unknown => {
+                ctx.error(
+                    Diagnostic::error(format!("cannot infer the type of `{}`", node.name))
+                        .with_span(node.value.span)
+                        .with_note(format!(
+                            "give it an explicit type: `{} := ... as <type>`",
+                            node.name
+                        )),
+                );
+                Type::Unknown
+            }
+            value_type => value_type,
+        },
+    };
+
+    node.var_type = Some(var_type.clone());
+    let binding = Binding::Variable {
+        var_type,
+        mutable: node.mutable,
+    };
+    ctx.declare(&node.name, binding);
+}
+
+fn check_assignment(node: &mut AssignmentNode, ctx: &mut Context) {
+    if let Some(Binding::Field(_)) = ctx.lookup(&node.name) {
+        let field = std::mem::replace(&mut node.name, String::from("self"));
+        node.path.insert(0, PlaceSegment::Field(field));
+    }
+
+    let value_type = check_expression(&mut node.value, ctx);
+    let mut target_type = ctx.variable(&node.name);
+    for segment in &mut node.path {
+        target_type = match segment {
+            PlaceSegment::Field(field) => field_type(&target_type, field, node.span, ctx),
+            PlaceSegment::Index(index) => element_type(&target_type, index, ctx),
+        };
+    }
+
+    let error = Diagnostic::error(format!("cannot assign to a `{}`", target_type))
+        .with_span(node.span)
+        .with_label("declare a new variable instead");
+    reject_escaping_dyn(&target_type, error, ctx);
+
+    ctx.coerce(&target_type, &value_type, &mut node.value);
+}
+
+fn check_binary_expr(node: &mut BinaryExprNode, ctx: &mut Context) -> Type {
+    let left_type = check_expression(&mut node.left, ctx);
+    let right_type = check_expression(&mut node.right, ctx);
+
+    let (accepts, expected): (fn(&Type) -> bool, _) = match node.op {
+        BinaryOp::Modulo => (Type::is_integer, "an integer"),
+        BinaryOp::Equal | BinaryOp::NotEqual => {
+            (|t| t.is_numeric() || *t == Type::Bool, "a number or `bool`")
+        }
+        BinaryOp::And | BinaryOp::Or => (|t| *t == Type::Bool, "a `bool`"),
+        _ => (Type::is_numeric, "a number"),
+    };
+
+    let left_ok = check_operand(node.op, &node.left, &left_type, accepts, expected, ctx);
+    // `s + s` is reported once, not for each operand.
+    let right_ok = if !left_ok && right_type == left_type {
+        false
+    } else {
+        check_operand(node.op, &node.right, &right_type, accepts, expected, ctx)
+    };
+
+    let result_type = if !node.op.is_arithmetic() {
+        let literal = literal_type(&node.left).is_some() || literal_type(&node.right).is_some();
+        if !node.op.is_logical()
+            && left_ok
+            && right_ok
+            && !is_comparable(&left_type, &right_type, literal, ctx)
+        {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "cannot compare a `{}` with a `{}`",
+                    left_type, right_type
+                ))
+                .with_span(node.span),
+            );
+        }
+        Type::Bool
+    } else if left_ok && right_ok {
+        let result_type = arithmetic_type((&node.left, left_type), (&node.right, right_type), ctx);
+        if node.op == BinaryOp::Power
+            && result_type.is_integer()
+            && let ExpressionKind::IntLit(exponent) = node.right.kind
+            && exponent < 0
+        {
+            ctx.error(
+                Diagnostic::error("integers can't be raised to a negative power")
+                    .with_span(node.right.span)
+                    .with_label(format!("the result type `{}` is an integer", result_type)),
+            );
+        }
+        result_type
+    } else {
+        Type::Unknown
+    };
+
+    node.result_type = Some(result_type.clone());
+    result_type
+}
+
+fn check_unary_expr(node: &mut UnaryExprNode, ctx: &mut Context) -> Type {
+    let operand_type = check_expression(&mut node.operand, ctx);
+
+    match node.op {
+        UnaryOp::Negate => {
+            if check_operand(
+                node.op,
+                &node.operand,
+                &operand_type,
+                |t| t.is_numeric() && !t.is_unsigned(),
+                "a signed number",
+                ctx,
+            ) {
+                operand_type
+            } else {
+                Type::Unknown
+            }
+        }
+        UnaryOp::Not => {
+            check_operand(
+                node.op,
+                &node.operand,
+                &operand_type,
+                |t| *t == Type::Bool,
+                "a `bool`",
+                ctx,
+            );
+            Type::Bool
+        }
+    }
+}
+
+/// Reports an operand `op` can't be applied to. Values from C and generic
+/// values are accepted, they are checked by the C compiler or per instance.
+fn check_operand(
+    op: impl Display,
+    operand: &ExpressionNode,
+    operand_type: &Type,
+    accepts: fn(&Type) -> bool,
+    expected: &str,
+    ctx: &mut Context,
+) -> bool {
+    if *operand_type == Type::Unknown || ctx.is_type_param(operand_type) || accepts(operand_type) {
+        return true;
+    }
+
+    ctx.error(
+        Diagnostic::error(format!("cannot apply `{}` to a `{}`", op, operand_type))
+            .with_span(operand.span)
+            .with_label(format!("expected {}", expected)),
+    );
+    false
+}
+
+/// Whether two values can be compared. Numbers are compared after widening
+/// one to the type of the other, literals adapt to the other side.
+fn is_comparable(left: &Type, right: &Type, literal: bool, ctx: &Context) -> bool {
+    left == right
+        || (left.is_numeric()
+            && right.is_numeric()
+            && (literal || left.widens_to(right) || right.widens_to(left)))
+        || [left, right]
+            .iter()
+            .any(|t| **t == Type::Unknown || ctx.is_type_param(t))
+}
+
+/// Result type of arithmetic on two numbers. Literals adapt to the other
+/// operand, `x + 1` keeps the type of `x`. Other operands widen to the type
+/// of the other one if it holds all their values.
+fn arithmetic_type(
+    (left, left_type): (&ExpressionNode, Type),
+    (right, right_type): (&ExpressionNode, Type),
+    ctx: &mut Context,
+) -> Type {
+    for operand_type in [&left_type, &right_type] {
+        if ctx.is_type_param(operand_type) {
+            return operand_type.clone();
+        }
+    }
+
+    let adapt = |operand: Type, literal: Type| {
+        if operand.is_integer() && literal.is_float() {
+            [Type::F32, Type::F64]
+                .into_iter()
+                .find(|float| operand.widens_to(float))
+        } else if literal != Type::I32 && operand.widens_to(&literal) {
+            // Literals too large for an `i32` don't fit smaller types either.
+            Some(literal)
+        } else {
+            Some(operand)
+        }
+    };
+
+    let (left_type, right_type) = match (left_type, right_type) {
+        (Type::Unknown, other) | (other, Type::Unknown) => return other,
+        types => types,
+    };
+    let result_type = match (literal_type(left).is_some(), literal_type(right).is_some()) {
+        (true, false) => adapt(right_type.clone(), left_type.clone()),
+        (false, true) => adapt(left_type.clone(), right_type.clone()),
+        (true, true) => Some(widest_literal(left_type.clone(), right_type.clone())),
+        (false, false) if right_type.widens_to(&left_type) => Some(left_type.clone()),
+        (false, false) if left_type.widens_to(&right_type) => Some(right_type.clone()),
+        (false, false) => None,
+    };
+
+    result_type.unwrap_or_else(|| {
+        ctx.error(
+            mismatch(&left_type, &right_type, right.span)
+                .with_note("convert one of the operands with `as`"),
+        );
+        Type::Unknown
+    })
+}
+
+/// The wider of two literal types, which are `i32`, `i64`, `u64` or `f32`.
+fn widest_literal(left: Type, right: Type) -> Type {
+    let rank = |t: &Type| {
+        [Type::I32, Type::I64, Type::U64, Type::F32]
+            .iter()
+            .position(|l| l == t)
+    };
+    if rank(&left) >= rank(&right) {
+        left
+    } else {
+        right
+    }
+}
+
+fn check_return(
+    value: Option<&mut ExpressionNode>,
+    value_type: Type,
+    span: Span,
+    ctx: &mut Context,
+) {
+    let Some(function) = ctx.functions.last() else {
+        ctx.error(Diagnostic::error("`return` outside of a function").with_span(span));
+        return;
+    };
+
+    if let Some(return_type) = function.return_type.clone() {
+        match value {
+            Some(value) => ctx.coerce(&return_type, &value_type, value),
+            None if return_type != Type::Void => ctx.error(
+                Diagnostic::error(format!(
+                    "function `{}` has to return a value",
+                    function.name
+                ))
+                .with_span(span)
+                .with_label(format!("expected a `{}`", return_type)),
+            ),
+            None => {}
+        }
+        return;
+    }
+
+    // Values from C say nothing about the return type.
+    if value_type == Type::Unknown {
+        return;
+    }
+
+    let inferred = match function.inferred.clone() {
+        None => value_type,
+        Some((current, _)) if ctx.is_assignable(&current, &value_type) => current,
+        Some((current, _)) if ctx.is_assignable(&value_type, &current) => value_type,
+        Some((current, first_span)) => {
+            let diagnostic = Diagnostic::error(format!(
+                "function `{}` returns both `{}` and `{}`",
+                function.name, current, value_type
+            ))
+            .with_span(span)
+            .with_label(format!("returns `{}`", value_type))
+            .with_secondary(first_span, format!("first returns `{}` here", current))
+            .with_note(format!(
+                "declare the return type: `fn {}(...) <type>`",
+                function.name
+            ));
+            ctx.error(diagnostic);
+            return;
+        }
+    };
+
+    let function = ctx.functions.last_mut().expect("checked above");
+    let first_span = function.inferred.as_ref().map_or(span, |(_, s)| *s);
+    function.inferred = Some((inferred, first_span));
+}
+
+fn check_func_call(node: &mut FuncCallNode, ctx: &mut Context) -> Type {
+    let arg_types = node
+        .params
+        .iter_mut()
+        .map(|param| check_expression(param, ctx))
+        .collect::<Vec<_>>();
+
+    let signature = match ctx.lookup(&node.name) {
+        Some(Binding::Function(signature)) => signature.clone(),
+        Some(&Binding::InferredFunction(id)) => inferred_signature(id, ctx),
+        // Functions from C headers can't be checked.
+        _ => return Type::Unknown,
+    };
+
+    if signature.params.len() != node.params.len() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "function `{}` takes {} argument(s) but {} were supplied",
+                node.name,
+                signature.params.len(),
+                node.params.len()
+            ))
+            .with_span(node.span),
+        );
+        return signature.return_type;
+    }
+
+    if !signature.generic_params.is_empty() {
+        infer_type_args(node, &signature, &arg_types, ctx);
+        ctx.check_bounds(
+            &signature.generic_params,
+            &mut node.generic_params,
+            node.span,
+        );
+    }
+
+    // The type arguments replace the type parameters, anything still generic
+    // is checked once the function is instantiated.
+    let type_params = &signature.generic_params;
+    for ((param_type, arg_type), arg) in signature
+        .params
+        .iter()
+        .zip(&arg_types)
+        .zip(&mut node.params)
+    {
+        let param_type = substitute(param_type, type_params, &node.generic_params);
+        ctx.coerce(&param_type, arg_type, arg);
+    }
+
+    substitute(&signature.return_type, type_params, &node.generic_params)
+}
+
+/// Fills in the type arguments of a call of a generic function. Unless they
+/// are given explicitly, like in `swap<i32>(a, b)`, they are inferred from the
+/// types of the arguments.
+fn infer_type_args(
+    node: &mut FuncCallNode,
+    signature: &FunctionSignature,
+    arg_types: &[Type],
+    ctx: &mut Context,
+) {
+    let type_params = &signature.generic_params;
+    if !node.generic_params.is_empty() {
+        if node.generic_params.len() != type_params.len() {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "function `{}` takes {} type argument(s) but {} were supplied",
+                    node.name,
+                    type_params.len(),
+                    node.generic_params.len()
+                ))
+                .with_span(node.span),
+            );
+            node.generic_params.resize(type_params.len(), Type::Unknown);
+        }
+        return;
+    }
+
+    let args: Vec<_> = node.params.iter().zip(arg_types).collect();
+    let inferred = unify_type_args(type_params, &signature.params, &args, ctx);
+
+    let mut type_args = vec![];
+    for (type_param, inferred) in type_params.iter().zip(inferred) {
+        let type_arg = inferred.unwrap_or_else(|| {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "cannot infer the type of `{}` in this call of `{}`",
+                    type_param.name, node.name
+                ))
+                .with_span(node.span)
+                .with_note(format!(
+                    "give the type arguments explicitly: `{}<...>(...)`",
+                    node.name
+                )),
+            );
+            Type::Unknown
+        });
+        type_args.push(type_arg);
+    }
+    node.generic_params = type_args;
+}
+
+/// Infers type parameters from values passed where `param_types` are
+/// expected, looking into the arguments of generic types like `Pair<T>`.
+/// Returns `None` for the type parameters the values say nothing about.
+fn unify_type_args(
+    type_params: &[TypeParamNode],
+    param_types: &[Type],
+    args: &[(&ExpressionNode, &Type)],
+    ctx: &Context,
+) -> Vec<Option<Type>> {
+    let mut inferred = vec![None; type_params.len()];
+    for (param_type, (arg, arg_type)) in param_types.iter().zip(args) {
+        let literal = literal_type(arg).is_some();
+        unify(
+            param_type,
+            arg_type,
+            literal,
+            type_params,
+            &mut inferred,
+            ctx,
+        );
+    }
+    inferred
+}
+
+fn unify(
+    param_type: &Type,
+    arg_type: &Type,
+    literal: bool,
+    type_params: &[TypeParamNode],
+    inferred: &mut [Option<Type>],
+    ctx: &Context,
+) {
+    match (param_type, arg_type) {
+        (_, Type::Unknown) => {}
+        (Type::Named(name), _) => {
+            let Some(i) = type_params.iter().position(|p| p.name == *name) else {
+                return;
+            };
+
+            // Number literals adapt to the type the other arguments agree on,
+            // otherwise the widest type wins.
+            inferred[i] = match inferred[i].take() {
+                None => Some(arg_type.clone()),
+                Some(current) if literal => Some(current),
+                Some(current) if ctx.is_assignable(arg_type, &current) => Some(arg_type.clone()),
+                // Anything else is reported as a mismatch of the argument.
+                Some(current) => Some(current),
+            };
+        }
+        (Type::Generic(name, params), Type::Generic(arg_name, args)) if name == arg_name => {
+            for (param, arg) in params.iter().zip(args) {
+                unify(param, arg, false, type_params, inferred, ctx);
+            }
+        }
+        (Type::Array(element, length), Type::Array(arg_element, arg_length)) => {
+            unify(element, arg_element, false, type_params, inferred, ctx);
+            unify(length, arg_length, false, type_params, inferred, ctx);
+        }
+        _ => {}
+    }
+}
+
+/// Type arguments of a literal of a generic struct or enum. Unless they are
+/// given explicitly, they are taken from the type the literal is expected to
+/// have, like in `x := Option::None as Option<i32>`, or else inferred from
+/// its values. `literal` shows the literal with
+/// explicit type arguments, for diagnostics.
+fn literal_type_args(
+    (name, type_params): (&str, &[TypeParamNode]),
+    explicit: &[Type],
+    inferred: Vec<Option<Type>>,
+    expected: Option<&Type>,
+    (literal, span): (String, Span),
+    ctx: &mut Context,
+) -> Vec<Type> {
+    if !explicit.is_empty() {
+        // Name resolution reported a wrong number of arguments.
+        let mut type_args = explicit.to_vec();
+        type_args.resize(type_params.len(), Type::Unknown);
+        return type_args;
+    }
+
+    let expected_args = match expected {
+        Some(Type::Generic(expected_name, args)) if expected_name == name => &args[..],
+        _ => &[],
+    };
+
+    let mut type_args = vec![];
+    for (i, (type_param, inferred)) in type_params.iter().zip(inferred).enumerate() {
+        let type_arg = expected_args
+            .get(i)
+            .cloned()
+            .or(inferred)
+            .unwrap_or_else(|| {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "cannot infer the type of `{}` in this `{}`",
+                        type_param.name, name
+                    ))
+                    .with_span(span)
+                    .with_note(format!("give the type arguments explicitly: `{}`", literal)),
+                );
+                Type::Unknown
+            });
+        type_args.push(type_arg);
+    }
+    type_args
+}
+
+fn check_struct_field_access(node: &mut StructFieldAccessNode, ctx: &mut Context) -> Type {
+    let struct_type = check_expression(&mut node.object, ctx);
+    field_type(&struct_type, &node.field_name, node.span, ctx)
+}
+
+/// Type of the field `field_name` of a value of type `struct_type`.
+fn field_type(struct_type: &Type, field_name: &str, span: Span, ctx: &mut Context) -> Type {
+    let fields = match (struct_type, ctx.struct_fields(struct_type)) {
+        (_, Some(fields)) => fields,
+        (Type::Unknown, _) => return Type::Unknown,
+        _ if ctx.is_type_param(struct_type) => return Type::Unknown,
+        _ => {
+            ctx.error(
+                Diagnostic::error(format!("type `{}` has no fields", struct_type))
+                    .with_span(span)
+                    .with_label(format!("no field `{}`", field_name)),
+            );
+            return Type::Unknown;
+        }
+    };
+
+    match fields.iter().find(|field| field.name == field_name) {
+        Some(field) => field.field_type.clone(),
+        None => {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "no field `{}` on type `{}`",
+                    field_name, struct_type
+                ))
+                .with_span(span)
+                .with_label("unknown field"),
+            );
+            Type::Unknown
+        }
+    }
+}
+
+/// Checks an array literal. Its values have the element type `expected`
+/// asks for, or else the type of the first value that isn't a number
+/// literal.
+fn check_array_lit(node: &mut ArrayLitNode, expected: Option<&Type>, ctx: &mut Context) -> Type {
+    let expected_element = match expected {
+        Some(Type::Array(element, _)) => Some(element.as_ref()),
+        _ => None,
+    };
+
+    let value_types: Vec<_> = node
+        .elements
+        .iter_mut()
+        .map(|element| match expected_element {
+            Some(expected) => check_expected(element, expected, ctx),
+            None => check_expression(element, ctx),
+        })
+        .collect();
+
+    let element_type = match expected_element {
+        Some(expected) => expected.clone(),
+        None => {
+            let typed = node.elements.iter().zip(&value_types);
+            let literals = typed.clone().filter(|(e, _)| literal_type(e).is_some());
+            match typed.clone().find(|(e, _)| literal_type(e).is_none()) {
+                Some((_, value_type)) => value_type.clone(),
+                None if value_types.is_empty() => {
+                    ctx.error(
+                        Diagnostic::error("cannot infer the type of an empty array")
+                            .with_span(node.span)
+                            .with_note("declare the type: `[] as [i32; 0]`"),
+                    );
+                    Type::Unknown
+                }
+                None => literals.fold(Type::I32, |widest_type, (_, value_type)| {
+                    widest_literal(widest_type, value_type.clone())
+                }),
+            }
+        }
+    };
+
+    for (element, value_type) in node.elements.iter_mut().zip(&value_types) {
+        ctx.coerce(&element_type, value_type, element);
+    }
+
+    let length = Type::Const(node.elements.len() as i64);
+    let array_type = Type::Array(Box::new(element_type), Box::new(length));
+    node.array_type = Some(array_type.clone());
+    array_type
+}
+
+fn check_array_repeat(
+    node: &mut ArrayRepeatNode,
+    expected: Option<&Type>,
+    ctx: &mut Context,
+) -> Type {
+    let element_type = match expected {
+        Some(Type::Array(element, _)) => {
+            let value_type = check_expected(&mut node.value, element, ctx);
+            ctx.coerce(element, &value_type, &mut node.value);
+            element.as_ref().clone()
+        }
+        _ => check_expression(&mut node.value, ctx),
+    };
+
+    let array_type = Type::Array(Box::new(element_type), Box::new(node.length.clone()));
+    node.array_type = Some(array_type.clone());
+    array_type
+}
+
+/// Type of one value of an array, checking the index used to get it.
+fn element_type(array_type: &Type, index: &mut ExpressionNode, ctx: &mut Context) -> Type {
+    let index_type = check_expression(index, ctx);
+    if !index_type.is_integer() && index_type != Type::Unknown {
+        ctx.error(
+            Diagnostic::error(format!(
+                "arrays are indexed by integers, found `{}`",
+                index_type
+            ))
+            .with_span(index.span)
+            .with_label("expected an integer"),
+        );
+    }
+
+    match array_type {
+        Type::Array(element, length) => {
+            if let (ExpressionKind::IntLit(value), Type::Const(length)) = (&index.kind, &**length)
+                && *value >= i128::from(*length)
+            {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "index out of bounds: the length is {} but the index is {}",
+                        length, value
+                    ))
+                    .with_span(index.span),
+                );
+            }
+            element.as_ref().clone()
+        }
+        Type::Unknown => Type::Unknown,
+        _ => {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "cannot index into a value of type `{}`",
+                    array_type
+                ))
+                .with_span(index.span),
+            );
+            Type::Unknown
+        }
+    }
+}
+
+/// Checks a struct literal, `expected` is the type its context requires if
+/// known.
+fn check_struct_init(
+    node: &mut StructInitNode,
+    expected: Option<&Type>,
+    ctx: &mut Context,
+) -> Type {
+    let value_types = node
+        .fields
+        .iter_mut()
+        .map(|field| check_expression(&mut field.value, ctx))
+        .collect::<Vec<_>>();
+
+    let (type_params, fields) = match ctx.lookup(&node.struct_name) {
+        Some(Binding::Struct {
+            type_params,
+            fields,
+            ..
+        }) => (type_params.clone(), fields.clone()),
+        // Name resolution already reported anything else.
+        _ => return Type::Unknown,
+    };
+
+    let struct_type = if type_params.is_empty() {
+        Type::Named(node.struct_name.clone())
+    } else {
+        let mut param_types = vec![];
+        let mut args = vec![];
+        for (field, value_type) in node.fields.iter().zip(&value_types) {
+            if let Some(definition) = fields.iter().find(|f| f.name == field.name) {
+                param_types.push(definition.field_type.clone());
+                args.push((&field.value, value_type));
+            }
+        }
+        let inferred = unify_type_args(&type_params, &param_types, &args, ctx);
+
+        let literal = format!("{}<...>{{ ... }}", node.struct_name);
+        node.generic_params = literal_type_args(
+            (&node.struct_name, &type_params),
+            &node.generic_params,
+            inferred,
+            expected,
+            (literal, node.span),
+            ctx,
+        );
+        ctx.check_bounds(&type_params, &mut node.generic_params, node.span);
+        Type::Generic(node.struct_name.clone(), node.generic_params.clone())
+    };
+    let fields = ctx.struct_fields(&struct_type).unwrap_or(fields);
+
+    for (i, value_type) in value_types.iter().enumerate() {
+        let field = &node.fields[i];
+        if let Some(first) = node.fields[..i].iter().find(|f| f.name == field.name) {
+            ctx.error(
+                Diagnostic::error(format!("field `{}` is specified twice", field.name))
+                    .with_span(field.span)
+                    .with_label("specified again here")
+                    .with_secondary(first.span, "first specified here"),
+            );
+            continue;
+        }
+
+        match fields.iter().find(|f| f.name == field.name) {
+            Some(definition) => ctx.coerce(
+                &definition.field_type,
+                value_type,
+                &mut node.fields[i].value,
+            ),
+            None => ctx.error(
+                Diagnostic::error(format!(
+                    "no field `{}` on type `{}`",
+                    field.name, struct_type
+                ))
+                .with_span(field.span)
+                .with_label("unknown field"),
+            ),
+        }
+    }
+
+    let omitted = fields
+        .into_iter()
+        .filter(|field| !node.fields.iter().any(|f| f.name == field.name))
+        .collect::<Vec<_>>();
+
+    let missing = omitted
+        .iter()
+        .filter(|field| field.default.is_none())
+        .map(|field| format!("`{}`", field.name))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "missing field(s) {} in initializer of `{}`",
+                missing.join(", "),
+                struct_type
+            ))
+            .with_span(node.span)
+            .with_note("only fields with a default value may be omitted"),
+        );
+    }
+
+    // Omitted fields are filled in with their defaults for code generation.
+    // Errors in the defaults are reported once, at the struct definition.
+    for field in omitted {
+        let Some(mut default) = field.default else {
+            continue;
+        };
+        let errors_before = ctx.diagnostics.len();
+        check_expression(&mut default, ctx);
+        ctx.diagnostics.truncate(errors_before);
+
+        node.fields.push(StructFieldInitNode {
+            name: field.name,
+            value: default,
+            span: node.span,
+        });
+    }
+
+    struct_type
+}
+
+/// Signature of a method built into a type, like `len` on strings.
+fn builtin_method(receiver_type: &Type, method: &str) -> Option<FunctionSignature> {
+    match (receiver_type, method) {
+        (Type::String, "len") => Some(FunctionSignature {
+            params: vec![],
+            return_type: Type::U64,
+            generic_params: vec![],
+        }),
+        _ => None,
+    }
+}
+
+/// Checks `object.method(args)`. When the object names a struct, like in
+/// `X.add_two(5)`, it calls a method without `self` instead.
+fn check_method_call(node: &mut MethodCallNode, ctx: &mut Context) -> Type {
+    let struct_name = match &node.object.kind {
+        ExpressionKind::VarAccess(object) => match ctx.lookup(&object.name) {
+            Some(Binding::Struct { type_params, .. }) => {
+                Some((object.name.clone(), !type_params.is_empty()))
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    node.static_call = struct_name.is_some();
+
+    // Calls on the struct itself have nothing to instantiate it with.
+    if let Some((struct_name, true)) = &struct_name {
+        ctx.error(
+            Diagnostic::error(format!(
+                "cannot call `{}.{}` on the generic struct `{}`",
+                struct_name, node.method, struct_name
+            ))
+            .with_span(node.span)
+            .with_label("the type arguments of the struct are unknown")
+            .with_note("use a function taking the values as arguments instead"),
+        );
+        return Type::Unknown;
+    }
+    let struct_name = struct_name.map(|(name, _)| name);
+
+    let errors_before = ctx.diagnostics.len();
+    let receiver_type = match struct_name {
+        Some(struct_name) => Type::Named(struct_name),
+        None => check_expression(&mut node.object, ctx),
+    };
+    // Methods are looked up by type, values from C have none.
+    if receiver_type == Type::Unknown && ctx.diagnostics.len() == errors_before {
+        ctx.error(
+            Diagnostic::error(format!(
+                "cannot call `{}` on a value of unknown type",
+                node.method
+            ))
+            .with_span(node.object.span)
+            .with_note("store it in a variable with an explicit type first"),
+        );
+    }
+
+    let arg_types = node
+        .args
+        .iter_mut()
+        .map(|arg| check_expression(arg, ctx))
+        .collect::<Vec<_>>();
+    node.receiver_type = Some(receiver_type.clone());
+
+    if receiver_type == Type::Unknown {
+        return Type::Unknown;
+    }
+
+    let method = find_method(&receiver_type, &node.method, node.span, ctx);
+    let Some((method, trait_name)) = method else {
+        let diagnostic = Diagnostic::error(format!(
+            "no method `{}` on type `{}`",
+            node.method, receiver_type
+        ))
+        .with_span(node.span)
+        .with_label("unknown method");
+        // Type parameters only have the methods of their bounds.
+        let diagnostic = match ctx.type_param(&receiver_type) {
+            Some(type_param) => match trait_with_method(&node.method, ctx) {
+                Some(trait_name) => diagnostic.with_note(format!(
+                    "add a bound to the type parameter: `{} impl {}`",
+                    type_param.name, trait_name
+                )),
+                None => diagnostic.with_note(format!(
+                    "only the methods of the traits `{}` is bounded by can be called on it",
+                    type_param.name
+                )),
+            },
+            None => diagnostic,
+        };
+        ctx.error(diagnostic);
+        return Type::Unknown;
+    };
+    node.trait_name = trait_name;
+
+    match (node.static_call, method.self_param) {
+        (true, Some(_)) => ctx.error(
+            Diagnostic::error(format!(
+                "`{}.{}` takes `self`, so it has to be called on a value",
+                receiver_type, node.method
+            ))
+            .with_span(node.span)
+            .with_note(format!(
+                "call it on a `{}`: `value.{}(...)`",
+                receiver_type, node.method
+            )),
+        ),
+        (false, None) => ctx.error(
+            Diagnostic::error(format!(
+                "`{}.{}` takes no `self`, so it can't be called on a value",
+                receiver_type, node.method
+            ))
+            .with_span(node.span)
+            .with_note(format!(
+                "call it on the struct: `{}.{}(...)`",
+                receiver_type, node.method
+            )),
+        ),
+        (false, Some(self_param)) if self_param.mutable => {
+            check_mutable_receiver(&node.object, &node.method, ctx)
+        }
+        _ => {}
+    }
+
+    let signature = method.signature;
+    if signature.params.len() != node.args.len() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "method `{}` takes {} argument(s) but {} were supplied",
+                node.method,
+                signature.params.len(),
+                node.args.len()
+            ))
+            .with_span(node.span),
+        );
+        return signature.return_type;
+    }
+
+    for ((param_type, arg_type), arg) in signature.params.iter().zip(&arg_types).zip(&mut node.args)
+    {
+        ctx.coerce(param_type, arg_type, arg);
+    }
+
+    signature.return_type
+}
+
+/// Name of a trait in scope having a method called `method`.
+fn trait_with_method(method: &str, ctx: &Context) -> Option<String> {
+    ctx.scopes
+        .iter()
+        .rev()
+        .flatten()
+        .find_map(|(name, binding)| match binding {
+            Binding::Trait(methods) if methods.iter().any(|m| m.method.name == method) => {
+                Some(name.clone())
+            }
+            _ => None,
+        })
+}
+
+/// Looks up a method of a type. Methods defined in a struct come first,
+/// then the ones of the traits the type implements and finally the built-in
+/// ones. Also returns the trait of methods from `impl` blocks. Type
+/// parameters only have the methods of the traits they are bounded by.
+fn find_method(
+    receiver_type: &Type,
+    name: &str,
+    span: Span,
+    ctx: &mut Context,
+) -> Option<(Method, Option<String>)> {
+    match ctx.type_binding(receiver_type) {
+        (
+            Some(Binding::Struct {
+                type_params,
+                methods,
+                ..
+            }),
+            type_args,
+        ) => {
+            if let Some(method) = methods.iter().find(|m| m.name == name) {
+                let signature = &method.signature;
+                let signature = FunctionSignature {
+                    params: signature
+                        .params
+                        .iter()
+                        .map(|p| substitute(p, type_params, type_args))
+                        .collect(),
+                    return_type: substitute(&signature.return_type, type_params, type_args),
+                    generic_params: signature.generic_params.clone(),
+                };
+                return Some((
+                    Method {
+                        signature,
+                        ..method.clone()
+                    },
+                    None,
+                ));
+            }
+        }
+        // Inside default bodies `self` is only known to implement the trait.
+        (Some(Binding::Trait(methods)), _) => {
+            let method = methods.iter().find(|m| m.method.name == name)?;
+            return Some((method.method.clone(), Some(receiver_type.to_string())));
+        }
+        _ => {}
+    }
+
+    if let Type::Dyn(trait_name) = receiver_type {
+        let Some(Binding::Trait(methods)) = ctx.lookup(trait_name) else {
+            return None;
+        };
+        let method = methods.iter().find(|m| m.method.name == name)?;
+        return Some((method.method.clone(), Some(trait_name.clone())));
+    }
+
+    let candidates: Vec<(Method, String)> = match ctx.type_param(receiver_type) {
+        Some(type_param) => type_param
+            .bounds
+            .iter()
+            .filter_map(|trait_name| {
+                let Some(Binding::Trait(methods)) = ctx.lookup(trait_name) else {
+                    return None;
+                };
+                let method = methods.iter().find(|m| m.method.name == name)?;
+                Some((method.method.clone(), trait_name.clone()))
+            })
+            .collect(),
+        None => ctx
+            .impls
+            .iter()
+            .filter(|i| ctx.impl_applies(i, receiver_type))
+            .filter_map(|i| {
+                let method = i.methods.iter().find(|m| m.name == name)?;
+                Some((method.clone(), i.trait_name.clone()))
+            })
+            .collect(),
+    };
+    if let [(_, first), (_, second), ..] = candidates.as_slice() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "multiple methods named `{}` on type `{}`",
+                name, receiver_type
+            ))
+            .with_span(span)
+            .with_note(format!(
+                "both `{}` and `{}` define it, rename one of them",
+                first, second
+            )),
+        );
+    }
+    if let Some((method, trait_name)) = candidates.into_iter().next() {
+        return Some((method, Some(trait_name)));
+    }
+
+    let signature = builtin_method(receiver_type, name)?;
+    let method = Method {
+        name: name.to_string(),
+        self_param: Some(SelfParam {
+            mutable: false,
+            span,
+        }),
+        signature,
+    };
+    Some((method, None))
+}
+
+/// Reports calls of `&mut self` methods on values that live in immutable
+/// variables. Temporaries may be changed freely.
+fn check_mutable_receiver(object: &ExpressionNode, method: &str, ctx: &mut Context) {
+    let mut root = object;
+    while let ExpressionKind::StructFieldAccess(node) = &root.kind {
+        root = &node.object;
+    }
+
+    let ExpressionKind::VarAccess(variable) = &root.kind else {
+        return;
+    };
+    if let Some(Binding::Variable { mutable: false, .. }) = ctx.lookup(&variable.name) {
+        let note = match variable.name.as_str() {
+            "self" => String::from("declare the method with `&mut self`"),
+            name => format!("declare it with `mut {} := ...`", name),
+        };
+        ctx.error(
+            Diagnostic::error(format!(
+                "cannot call `{}` on the immutable `{}`",
+                method, variable.name
+            ))
+            .with_span(object.span)
+            .with_label(format!("`{}` takes `&mut self`", method))
+            .with_note(note),
+        );
+    }
+}
+
+fn signature(node: &FuncDefNode) -> FunctionSignature {
+    FunctionSignature {
+        params: node.params.iter().map(|p| p.param_type.clone()).collect(),
+        return_type: node.return_type.clone().unwrap_or(Type::Void),
+        generic_params: type_params(node.generic_typing.as_ref()),
+    }
+}
+
+fn type_params(generic_typing: Option<&GenericTypingNode>) -> Vec<TypeParamNode> {
+    generic_typing.map(|g| g.params.clone()).unwrap_or_default()
+}
+
+fn declare_func_def(node: &FuncDefNode, ctx: &mut Context) {
+    ctx.declare(&node.name, Binding::Function(signature(node)));
+}
+
+/// Declares a function whose return type is inferred, returning its index
+/// in `Context::inferred`.
+fn declare_inferred_func_def(node: &FuncDefNode, ctx: &mut Context) -> usize {
+    let id = ctx.inferred.len();
+    ctx.inferred.push(InferredFunction {
+        node: node.clone(),
+        depth: ctx.scopes.len(),
+        type_params: ctx.type_params.clone(),
+        checked: false,
+        recursive: false,
+        cycle_start: None,
+    });
+    ctx.declare(&node.name, Binding::InferredFunction(id));
+    id
+}
+
+/// Signature of a function with an inferred return type, checking its body
+/// first if that didn't happen yet. A recursive call, made while the body is
+/// being checked, returns an unknown type.
+fn inferred_signature(id: usize, ctx: &mut Context) -> FunctionSignature {
+    if let Some(position) = ctx.inferring.iter().position(|&f| f == id) {
+        for &function in &ctx.inferring[position..] {
+            let function = &mut ctx.inferred[function];
+            function.recursive = true;
+            function.cycle_start = Some(function.cycle_start.map_or(position, |p| p.min(position)));
+        }
+        return FunctionSignature {
+            return_type: Type::Unknown,
+            ..signature(&ctx.inferred[id].node)
+        };
+    }
+
+    if ctx.inferred[id].checked {
+        return signature(&ctx.inferred[id].node);
+    }
+    check_inferred_func_def(id, ctx)
+}
+
+/// Checks a function with an inferred return type in the scopes of its
+/// definition, wherever the call needing it is, and returns its signature.
+///
+/// A recursive function is checked a second time with the type inferred
+/// from its other returns, so the recursive calls have a type too. Functions
+/// of a cycle entered through another function are checked again once that
+/// one has its type.
+fn check_inferred_func_def(id: usize, ctx: &mut Context) -> FunctionSignature {
+    let position = ctx.inferring.len();
+    ctx.inferring.push(id);
+    let function = &mut ctx.inferred[id];
+    function.cycle_start = None;
+    let original = function.node.clone();
+    let type_params = std::mem::replace(&mut ctx.type_params, function.type_params.clone());
+    let inner_scopes = ctx.scopes.split_off(function.depth);
+
+    let errors_before = ctx.diagnostics.len();
+    let mut node = original.clone();
+    check_func_def(&mut node, None, ctx);
+    let signature = signature(&node);
+
+    let function = &mut ctx.inferred[id];
+    match function.cycle_start {
+        Some(start) if start < position => {
+            ctx.diagnostics.truncate(errors_before);
+            ctx.declare(&node.name, Binding::InferredFunction(id));
+        }
+        Some(_) if node.return_type != Some(Type::Unknown) => {
+            ctx.diagnostics.truncate(errors_before);
+            node = original;
+            check_func_def(&mut node, None, ctx);
+            ctx.inferred[id].node = node;
+            ctx.inferred[id].checked = true;
+        }
+        _ => {
+            function.node = node;
+            function.checked = true;
+        }
+    }
+
+    ctx.scopes.extend(inner_scopes);
+    ctx.type_params = type_params;
+    ctx.inferring.pop();
+    signature
+}
+
+/// Checks a function or, given the type it is defined for, a method.
+/// Methods taking `self` see the fields of a struct by their bare names.
+fn check_func_def(node: &mut FuncDefNode, owner: Option<&Type>, ctx: &mut Context) {
+    let generic_params = type_params(node.generic_typing.as_ref());
+    let type_param_count = ctx.type_params.len();
+    ctx.type_params.extend(generic_params);
+
+    ctx.scopes.push(HashMap::new());
+    ctx.functions.push(FunctionContext {
+        name: node.name.clone(),
+        return_type: node.return_type.clone(),
+        inferred: None,
+    });
+
+    // Const parameters of the function and of the struct or impl around it
+    // are values in the body.
+    let const_params: Vec<_> = ctx
+        .type_params
+        .iter()
+        .filter_map(|p| Some((p.name.clone(), p.const_type.clone()?)))
+        .collect();
+    for (name, var_type) in const_params {
+        let binding = Binding::Variable {
+            var_type,
+            mutable: false,
+        };
+        ctx.declare(&name, binding);
+    }
+
+    if let (Some(owner), Some(self_param)) = (owner, node.self_param) {
+        let self_binding = Binding::Variable {
+            var_type: owner.clone(),
+            mutable: self_param.mutable,
+        };
+        ctx.declare("self", self_binding);
+
+        for field in ctx.struct_fields(owner).unwrap_or_default() {
+            ctx.declare(&field.name, Binding::Field(field.field_type));
+        }
+        // Parameters and variables may shadow the fields.
+        ctx.scopes.push(HashMap::new());
+    }
+
+    for param in &node.params {
+        if param.param_type == Type::Void {
+            ctx.error(
+                Diagnostic::error(format!("parameter `{}` can't have type `void`", param.name))
+                    .with_span(param.span),
+            );
+        }
+        let binding = Binding::Variable {
+            var_type: param.param_type.clone(),
+            mutable: false,
+        };
+        ctx.declare(&param.name, binding);
+    }
+
+    let errors_before = ctx.diagnostics.len();
+    check_expressions(&mut node.body.expressions, ctx);
+    let body_failed = ctx.diagnostics.len() > errors_before;
+
+    let function = ctx.functions.pop().expect("pushed above");
+    if owner.is_some() && node.self_param.is_some() {
+        ctx.scopes.pop();
+    }
+    ctx.scopes.pop();
+    ctx.type_params.truncate(type_param_count);
+
+    if let Some(return_type) = &node.return_type
+        && *return_type != Type::Void
+        && !always_returns(&node.body.expressions)
+    {
+        ctx.error(
+            Diagnostic::error(format!(
+                "function `{}` doesn't return a value on every path",
+                node.name
+            ))
+            .with_span(closing_brace(&node.body))
+            .with_label(format!(
+                "can end here without returning a `{}`",
+                return_type
+            )),
+        );
+    }
+
+    if node.return_type.is_none() {
+        let returns_value = contains_return(&node.body.expressions);
+        let return_type = match function.inferred {
+            // Reaching the closing brace returns nothing.
+            Some((inferred, first_span))
+                if inferred != Type::Void && !always_returns(&node.body.expressions) =>
+            {
+                ctx.error(
+                    Diagnostic::error(format!(
+                        "function `{}` returns both `{}` and `void`",
+                        node.name, inferred
+                    ))
+                    .with_span(closing_brace(&node.body))
+                    .with_label("returns `void` when it ends here")
+                    .with_secondary(first_span, format!("first returns `{}` here", inferred))
+                    .with_note(format!(
+                        "declare the return type: `fn {}(...) <type>`",
+                        node.name
+                    )),
+                );
+                inferred
+            }
+            Some((inferred, _)) => inferred,
+            None if !returns_value => Type::Void,
+            None => {
+                let recursive = match ctx.lookup(&node.name) {
+                    Some(&Binding::InferredFunction(id)) if owner.is_none() => {
+                        ctx.inferred[id].recursive
+                    }
+                    _ => false,
+                };
+                if !body_failed || recursive {
+                    let function = if recursive { "recursive function " } else { "" };
+                    ctx.error(
+                        Diagnostic::error(format!(
+                            "cannot infer the return type of {}`{}`",
+                            function, node.name
+                        ))
+                        .with_span(node.span)
+                        .with_note(format!(
+                            "declare the return type: `fn {}(...) <type>`",
+                            node.name
+                        )),
+                    );
+                }
+                Type::Unknown
+            }
+        };
+
+        node.return_type = Some(return_type);
+        if owner.is_none() {
+            declare_func_def(node, ctx);
+        }
+    }
+
+    check_return_type(node, ctx);
+}
+
+fn check_return_type(node: &FuncDefNode, ctx: &mut Context) {
+    let return_type = node.return_type.clone().unwrap_or(Type::Void);
+    let error = Diagnostic::error(format!(
+        "function `{}` can't return `{}`",
+        node.name, return_type
+    ))
+    .with_span(node.span);
+    reject_escaping_dyn(&return_type, error, ctx);
+}
+
+/// `self.field`, what a bare field name inside a method stands for.
+fn self_field(field_name: &str, span: Span) -> StructFieldAccessNode {
+    StructFieldAccessNode {
+        object: Box::new(ExpressionNode {
+            kind: ExpressionKind::VarAccess(VarAccessNode {
+                name: String::from("self"),
+                span,
+            }),
+            span,
+        }),
+        field_name: field_name.to_string(),
+        span,
+    }
+}
+
+/// Whether values of a type hold a trait object, which points to the value
+/// it was made from.
+fn holds_dyn(value_type: &Type) -> bool {
+    match value_type {
+        Type::Dyn(_) => true,
+        Type::Array(element, _) => holds_dyn(element),
+        Type::Generic(_, args) => args.iter().any(holds_dyn),
+        _ => false,
+    }
+}
+
+/// Trait objects may only be passed down to functions and kept in variables
+/// declared next to the value they point to, so they can't outlive it.
+fn reject_escaping_dyn(value_type: &Type, error: Diagnostic, ctx: &mut Context) {
+    if holds_dyn(value_type) {
+        ctx.error(error.with_note(
+            "a trait object points to the value it was made from and must not outlive it",
+        ));
+    }
+}
+
+/// Whether a function body returns a value anywhere. Nested function
+/// definitions have their own returns.
+fn contains_return(expressions: &[ExpressionNode]) -> bool {
+    expressions.iter().any(|expr| match &expr.kind {
+        ExpressionKind::ReturnExpr(_) => true,
+        ExpressionKind::If(node) => {
+            contains_return(&node.then_block.expressions)
+                || node
+                    .else_block
+                    .as_ref()
+                    .is_some_and(|block| contains_return(&block.expressions))
+        }
+        ExpressionKind::Match(node) => node
+            .arms
+            .iter()
+            .any(|arm| contains_return(&arm.body.expressions)),
+        ExpressionKind::While(node) => contains_return(&node.body.expressions),
+        ExpressionKind::For(node) => contains_return(&node.body.expressions),
+        _ => false,
+    })
+}
+
+/// Whether a function body ends in a `return` however it runs, so it never
+/// reaches its closing brace. Loops may run zero times, so they don't count.
+fn always_returns(expressions: &[ExpressionNode]) -> bool {
+    expressions.iter().any(|expr| match &expr.kind {
+        ExpressionKind::ReturnExpr(_) => true,
+        ExpressionKind::If(node) => {
+            always_returns(&node.then_block.expressions)
+                && node
+                    .else_block
+                    .as_ref()
+                    .is_some_and(|block| always_returns(&block.expressions))
+        }
+        ExpressionKind::Match(node) => node
+            .arms
+            .iter()
+            .all(|arm| always_returns(&arm.body.expressions)),
+        _ => false,
+    })
+}
+
+/// Span of the `}` ending a block.
+fn closing_brace(block: &BlockNode) -> Span {
+    Span {
+        start: block.span.end - 1,
+        end: block.span.end,
+    }
+}
+
+fn declare_struct_def(node: &StructDefNode, ctx: &mut Context) {
+    let methods = node
+        .methods
+        .iter()
+        .map(|method| Method {
+            name: method.name.clone(),
+            self_param: method.self_param,
+            signature: signature(method),
+        })
+        .collect();
+
+    let binding = Binding::Struct {
+        type_params: type_params(node.generic_typing.as_ref()),
+        fields: node.fields.clone(),
+        methods,
+    };
+    ctx.declare(&node.name, binding);
+}
+
+/// Checks the methods of a struct and declares it again with the inferred
+/// return types. Inside a generic struct, `self` has the struct's own type
+/// parameters as arguments.
+fn check_methods(node: &mut StructDefNode, ctx: &mut Context) {
+    let type_params = type_params(node.generic_typing.as_ref());
+    let self_type = match type_params.is_empty() {
+        true => Type::Named(node.name.clone()),
+        false => Type::Generic(
+            node.name.clone(),
+            type_params
+                .iter()
+                .map(|p| Type::Named(p.name.clone()))
+                .collect(),
+        ),
+    };
+
+    let type_param_count = ctx.type_params.len();
+    ctx.type_params.extend(type_params);
+    for method in &mut node.methods {
+        check_func_def(method, Some(&self_type), ctx);
+    }
+    ctx.type_params.truncate(type_param_count);
+
+    declare_struct_def(node, ctx);
+}
+
+fn check_struct_def(node: &mut StructDefNode, ctx: &mut Context) {
+    let type_param_count = ctx.type_params.len();
+    ctx.type_params
+        .extend(type_params(node.generic_typing.as_ref()));
+
+    for field in &mut node.fields {
+        if field.field_type == Type::Void {
+            ctx.error(
+                Diagnostic::error(format!("field `{}` can't have type `void`", field.name))
+                    .with_span(field.span),
+            );
+        }
+        let error = Diagnostic::error(format!(
+            "field `{}` can't hold `{}`",
+            field.name, field.field_type
+        ))
+        .with_span(field.span);
+        reject_escaping_dyn(&field.field_type, error, ctx);
+
+        if let Some(default) = &mut field.default {
+            let default_type = check_expression(default, ctx);
+            ctx.expect_type(&field.field_type, &default_type, default);
+        }
+    }
+
+    ctx.type_params.truncate(type_param_count);
+}
+
+fn declare_trait_def(node: &TraitDefNode, ctx: &mut Context) {
+    let methods = node
+        .methods
+        .iter()
+        .map(|method| TraitMethod {
+            method: Method {
+                name: method.func.name.clone(),
+                self_param: method.func.self_param,
+                signature: signature(&method.func),
+            },
+            default: (!method.required).then(|| method.func.clone()),
+            span: method.func.span,
+        })
+        .collect();
+
+    ctx.declare(&node.name, Binding::Trait(methods));
+}
+
+/// Checks the default bodies once with `self` being any implementing type.
+fn check_trait_def(node: &mut TraitDefNode, ctx: &mut Context) {
+    for method in &mut node.methods {
+        if method.required {
+            check_return_type(&method.func, ctx);
+        } else {
+            check_func_def(&mut method.func, Some(&Type::Named(node.name.clone())), ctx);
+        }
+    }
+}
+
+fn declare_impl_block(node: &ImplBlockNode, ctx: &mut Context) {
+    let Some(Binding::Trait(methods)) = ctx.lookup(&node.trait_name) else {
+        // Name resolution already reported it.
+        return;
+    };
+    let methods = methods.iter().map(|m| m.method.clone()).collect();
+
+    // Generic impls conflict with every impl for one of their instances,
+    // whatever the bounds are.
+    let type_params = type_params(node.generic_typing.as_ref());
+    let overlaps = |i: &Impl| {
+        let mut type_args = vec![None; i.type_params.len()];
+        let mut own_type_args = vec![None; type_params.len()];
+        match_type(&i.for_type, &node.for_type, &i.type_params, &mut type_args)
+            || match_type(
+                &node.for_type,
+                &i.for_type,
+                &type_params,
+                &mut own_type_args,
+            )
+    };
+    let previous = ctx
+        .impls
+        .iter()
+        .find(|i| i.trait_name == node.trait_name && overlaps(i));
+    if let Some(previous) = previous {
+        let diagnostic = Diagnostic::error(format!(
+            "conflicting implementations of trait `{}` for type `{}`",
+            node.trait_name, node.for_type
+        ))
+        .with_span(node.span)
+        .with_label("conflicting implementation")
+        .with_secondary(previous.span, "first implemented here");
+        ctx.error(diagnostic);
+        return;
+    }
+
+    ctx.impls.push(Impl {
+        trait_name: node.trait_name.clone(),
+        type_params,
+        for_type: node.for_type.clone(),
+        methods,
+        span: node.span,
+    });
+}
+
+/// Checks the methods of an impl against the trait and adds the default
+/// methods it doesn't override, so they are generated for the type.
+fn check_impl_block(node: &mut ImplBlockNode, ctx: &mut Context) {
+    let Some(Binding::Trait(trait_methods)) = ctx.lookup(&node.trait_name).cloned() else {
+        return;
+    };
+    let for_type = node.for_type.clone();
+    let type_param_count = ctx.type_params.len();
+    ctx.type_params
+        .extend(type_params(node.generic_typing.as_ref()));
+
+    for method in &mut node.methods {
+        let Some(expected) = trait_methods.iter().find(|m| m.method.name == method.name) else {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "method `{}` is not a member of trait `{}`",
+                    method.name, node.trait_name
+                ))
+                .with_span(method.span)
+                .with_label(format!("not a member of `{}`", node.trait_name)),
+            );
+            check_func_def(method, Some(&for_type), ctx);
+            continue;
+        };
+
+        // The trait already declares what the method returns.
+        method
+            .return_type
+            .get_or_insert_with(|| expected.method.signature.return_type.clone());
+
+        let expected_signature =
+            describe_method(expected.method.self_param, &expected.method.signature);
+        let found_signature = describe_method(method.self_param, &signature(method));
+        if expected_signature != found_signature {
+            ctx.error(
+                Diagnostic::error(format!(
+                    "method `{}` has an incompatible signature for trait `{}`",
+                    method.name, node.trait_name
+                ))
+                .with_span(method.span)
+                .with_label(format!(
+                    "expected `{}`, found `{}`",
+                    expected_signature, found_signature
+                ))
+                .with_secondary(expected.span, "declared in the trait here"),
+            );
+        }
+
+        check_func_def(method, Some(&for_type), ctx);
+    }
+
+    let implemented = |name: &str| node.methods.iter().any(|m| m.name == name);
+    let missing: Vec<String> = trait_methods
+        .iter()
+        .filter(|m| m.default.is_none() && !implemented(&m.method.name))
+        .map(|m| format!("`{}`", m.method.name))
+        .collect();
+    if !missing.is_empty() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "not all methods of trait `{}` are implemented, missing: {}",
+                node.trait_name,
+                missing.join(", ")
+            ))
+            .with_span(node.span)
+            .with_label(format!("missing {}", missing.join(", "))),
+        );
+    }
+
+    // Errors in default bodies were reported when checking the trait.
+    let defaults: Vec<FuncDefNode> = trait_methods
+        .into_iter()
+        .filter(|m| !implemented(&m.method.name))
+        .filter_map(|m| m.default)
+        .collect();
+    for mut default in defaults {
+        let errors_before = ctx.diagnostics.len();
+        check_func_def(&mut default, Some(&for_type), ctx);
+        ctx.diagnostics.truncate(errors_before);
+        node.methods.push(default);
+    }
+    ctx.type_params.truncate(type_param_count);
+}
+
+/// `fn(&self, i32) string`, how signatures are shown in diagnostics.
+fn describe_method(self_param: Option<SelfParam>, signature: &FunctionSignature) -> String {
+    let receiver = self_param.map(|p| if p.mutable { "&mut self" } else { "&self" });
+    let params = signature.params.iter().map(|p| p.to_string());
+    let params: Vec<String> = receiver
+        .map(String::from)
+        .into_iter()
+        .chain(params)
+        .collect();
+
+    match signature.return_type {
+        Type::Void => format!("fn({})", params.join(", ")),
+        ref return_type => format!("fn({}) {}", params.join(", "), return_type),
+    }
+}
+
+fn declare_enum_def(node: &EnumDefNode, ctx: &mut Context) {
+    let variants = node
+        .variants
+        .iter()
+        .map(|variant| {
+            let field_types = variant.fields.iter().map(|f| f.field_type.clone());
+            (variant.name.clone(), field_types.collect())
+        })
+        .collect();
+
+    let binding = Binding::Enum {
+        type_params: type_params(node.generic_typing.as_ref()),
+        variants,
+    };
+    ctx.declare(&node.name, binding);
+}
+
+fn check_enum_def(node: &EnumDefNode, ctx: &mut Context) {
+    for field in node.variants.iter().flat_map(|v| &v.fields) {
+        if field.field_type == Type::Void {
+            ctx.error(
+                Diagnostic::error("enum variants can't hold `void` values").with_span(field.span),
+            );
+        }
+        let error = Diagnostic::error(format!("enum variants can't hold `{}`", field.field_type))
+            .with_span(field.span);
+        reject_escaping_dyn(&field.field_type, error, ctx);
+    }
+}
+
+/// Checks an enum literal, `expected` is the type its context requires if
+/// known.
+fn check_enum_init(node: &mut EnumInitNode, expected: Option<&Type>, ctx: &mut Context) -> Type {
+    let arg_types = node
+        .args
+        .iter_mut()
+        .map(|arg| check_expression(arg, ctx))
+        .collect::<Vec<_>>();
+
+    let (type_params, variants) = match ctx.lookup(&node.enum_name) {
+        Some(Binding::Enum {
+            type_params,
+            variants,
+        }) => (type_params.clone(), variants.clone()),
+        // Name resolution already reported anything else.
+        _ => return Type::Unknown,
+    };
+    let variant = variants.iter().find(|(name, _)| *name == node.variant);
+
+    let enum_type = if type_params.is_empty() {
+        Type::Named(node.enum_name.clone())
+    } else {
+        let param_types = variant.map_or(&[][..], |(_, field_types)| field_types);
+        let args: Vec<_> = node.args.iter().zip(&arg_types).collect();
+        let inferred = unify_type_args(&type_params, param_types, &args, ctx);
+
+        let literal = format!("{}<...>::{}", node.enum_name, node.variant);
+        node.generic_params = literal_type_args(
+            (&node.enum_name, &type_params),
+            &node.generic_params,
+            inferred,
+            expected,
+            (literal, node.span),
+            ctx,
+        );
+        ctx.check_bounds(&type_params, &mut node.generic_params, node.span);
+        Type::Generic(node.enum_name.clone(), node.generic_params.clone())
+    };
+    let variants = ctx.enum_variants(&enum_type).unwrap_or(variants);
+
+    let Some((_, field_types)) = variants.iter().find(|(name, _)| *name == node.variant) else {
+        ctx.error(
+            Diagnostic::error(format!(
+                "no variant `{}` on enum `{}`",
+                node.variant, node.enum_name
+            ))
+            .with_span(node.span)
+            .with_label("unknown variant"),
+        );
+        return enum_type;
+    };
+
+    if field_types.len() != node.args.len() {
+        ctx.error(
+            Diagnostic::error(format!(
+                "variant `{}::{}` holds {} value(s) but {} were supplied",
+                node.enum_name,
+                node.variant,
+                field_types.len(),
+                node.args.len()
+            ))
+            .with_span(node.span),
+        );
+        return enum_type;
+    }
+
+    for ((field_type, arg_type), arg) in field_types
+        .clone()
+        .iter()
+        .zip(&arg_types)
+        .zip(&mut node.args)
+    {
+        ctx.coerce(field_type, arg_type, arg);
+    }
+
+    enum_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast_builder::build_ast_from_pairs;
+    use crate::modules::resolve_modules;
+    use crate::parser::parse_program;
+    use crate::resolver::resolve_names;
+
+    /// Messages of the errors checking `source` reports.
+    fn errors(source: &str) -> Vec<String> {
+        let pair = parse_program(source, 0).expect("source parses");
+        let mut program = build_ast_from_pairs(pair, 0).expect("source builds");
+        resolve_modules(&mut program).expect("modules resolve");
+        resolve_names(&program).expect("names resolve");
+        match check_types(&mut program) {
+            Ok(()) => vec![],
+            Err(diagnostics) => diagnostics.into_iter().map(|d| d.message).collect(),
+        }
+    }
+
+    const OPTION: &str = "enum Option = {\n    Some(i32),\n    None\n}\nenum Pair = {\n    Both(Option, Option)\n}\no := Option::Some(1)\n";
+
+    #[test]
+    fn match_covering_every_variant_is_exhaustive() {
+        let source = format!(
+            "{}x := match o {{\n    Some(v) => v,\n    None => 0\n}}\n",
+            OPTION
+        );
+        assert_eq!(errors(&source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_missing_variant() {
+        let source = format!("{}x := match o {{\n    Some(v) => v\n}}\n", OPTION);
+        assert_eq!(
+            errors(&source),
+            ["non-exhaustive patterns: `Option::None` not covered"]
+        );
+    }
+
+    #[test]
+    fn reports_every_missing_variant() {
+        let source = "enum E = { A, B(i32), C }\ne := E::A\nx := match e {\n    A => 1\n}\n";
+        assert_eq!(
+            errors(source),
+            ["non-exhaustive patterns: `E::B(_)` and `E::C` not covered"]
+        );
+    }
+
+    #[test]
+    fn reports_missing_nested_variant() {
+        let source = format!(
+            "{}p := Pair::Both(o, o)\nx := match p {{\n    Both(Some(a), _) => a,\n    Both(None, None) => 0\n}}\n",
+            OPTION
+        );
+        assert_eq!(
+            errors(&source),
+            ["non-exhaustive patterns: `Pair::Both(Option::None, Option::Some(_))` not covered"]
+        );
+    }
+
+    #[test]
+    fn guarded_arms_cover_nothing() {
+        let source = format!(
+            "{}x := match o {{\n    Some(v) if v > 0 => v,\n    None => 0\n}}\n",
+            OPTION
+        );
+        assert_eq!(
+            errors(&source),
+            ["non-exhaustive patterns: `Option::Some(_)` not covered"]
+        );
+    }
+
+    #[test]
+    fn reports_arm_after_wildcard_as_unreachable() {
+        let source = format!(
+            "{}x := match o {{\n    _ => 0,\n    None => 1\n}}\n",
+            OPTION
+        );
+        assert_eq!(errors(&source), ["unreachable pattern"]);
+    }
+
+    #[test]
+    fn reports_arm_after_all_variants_as_unreachable() {
+        let source = format!(
+            "{}x := match o {{\n    Some(v) => v,\n    None => 0,\n    _ => 1\n}}\n",
+            OPTION
+        );
+        assert_eq!(errors(&source), ["unreachable pattern"]);
+    }
+
+    #[test]
+    fn reports_repeated_literal_and_missing_wildcard() {
+        let source = "n := 3\nx := match n {\n    1 => 10,\n    -2 => 20,\n    1 => 30\n}\n";
+        assert_eq!(
+            errors(source),
+            [
+                "unreachable pattern",
+                "non-exhaustive patterns: `_` not covered"
+            ]
+        );
+    }
+
+    #[test]
+    fn infers_return_type_of_function_defined_later() {
+        let source = "fn a() { return b() + 1 }\nfn b() { return 41 }\nx := a() as i32\n";
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn infers_return_type_of_recursive_function_from_other_returns() {
+        let source = "fn fact(n: i32) {\n    if n == 0 { return 1 }\n    return n * fact(n - 1)\n}\nx := fact(5) as i32\n";
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn infers_return_types_of_mutually_recursive_functions() {
+        let source = "fn g(n: i32) {\n    if n == 0 { return 1 }\n    return h(n - 1)\n}\nfn h(n: i32) {\n    x := g(n)\n    return x\n}\n";
+        assert_eq!(errors(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn integer_literals_are_checked_against_their_type() {
+        let source =
+            "a := -2147483648\nmut b := 3000000000 as i64\nc := 300 as u8\nd := -1 as u32\n";
+        assert_eq!(
+            errors(source),
+            [
+                "literal out of range for `u8`",
+                "literal out of range for `u32`"
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_return_types_include_falling_off_the_end() {
+        let source = "fn fb(x: i32) { if x > 0 { return 1 } }\nfn bad(x: i32) i32 { return }\nfn mixed(x: i32) {\n    if x > 0 { return }\n    return 1\n}\nfn early(x: i32) {\n    if x > 0 { return }\n    y := x\n}\nfn both(x: i32) { if x > 0 { return 1 } else { return 2 } }\n";
+        assert_eq!(
+            errors(source),
+            [
+                "function `fb` returns both `i32` and `void`",
+                "function `bad` has to return a value",
+                "function `mixed` returns both `void` and `i32`"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_recursion_without_other_returns() {
+        assert_eq!(
+            errors("fn f(n: i32) { return f(n) }\n"),
+            ["cannot infer the return type of recursive function `f`"]
+        );
+    }
+
+    #[test]
+    fn functions_return_a_value_on_every_path() {
+        let source = "enum E = { A, B }\nfn h(x: i32) i32 { x }\nfn g(x: i32) i32 {\n    if x > 0 { return 1 } else if x < 0 { return 2 } else { return 3 }\n}\nfn m(e: E) i32 {\n    match e {\n        E::A => { return 1 }\n        E::B => { return 2 }\n    }\n}\nfn n(x: i32) i32 {\n    while x > 0 { return 1 }\n}\n";
+        assert_eq!(
+            errors(source),
+            [
+                "function `h` doesn't return a value on every path",
+                "function `n` doesn't return a value on every path"
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_only_widen_without_losing_values() {
+        let source = "fn u(y: u64) {}\nfn w(y: i64) {}\nfn f(y: f32) {}\nfn d(y: f64) {}\nfn g(a: i8, b: u16, c: i32, e: i64) {\n    u(c)\n    u(a)\n    f(c)\n    d(e)\n    x := b + a\n    y := a < b\n    w(a)\n    w(b)\n    f(b)\n    d(c)\n    z := b + c\n    v := c + 2.5\n}\n";
+        assert_eq!(
+            errors(source),
+            [
+                "mismatched types: expected `u64`, found `i32`",
+                "mismatched types: expected `u64`, found `i8`",
+                "mismatched types: expected `f32`, found `i32`",
+                "mismatched types: expected `f64`, found `i64`",
+                "mismatched types: expected `u16`, found `i8`",
+                "cannot compare a `i8` with a `u16`"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_meaningless_arithmetic_once() {
+        assert_eq!(
+            errors(
+                "fn f(x: u32) u32 { return -x }\ns := \"a\"\nt := s + s\np := 2 ** -1\nq := 2.0 ** -1.0\n"
+            ),
+            [
+                "cannot apply `-` to a `u32`",
+                "cannot apply `+` to a `string`",
+                "integers can't be raised to a negative power"
+            ]
+        );
+    }
+
+    const SHOW: &str = "trait Show {\n    fn show(&self)\n}\nstruct A = {\n    v: i32\n}\nimpl Show for A {\n    fn show(&self) {}\n}\n";
+
+    #[test]
+    fn trait_objects_may_not_outlive_their_value() {
+        let source = format!(
+            "{SHOW}struct Holder = {{ s: dyn Show }}\nfn make(n: i32) Holder {{\n    a := A{{ v: n }}\n    return Holder{{ s: a }}\n}}\nfn pick(s: dyn Show) {{ return s }}\nfn f(c: i32, s: dyn Show) {{\n    mut d := s\n    a := A{{ v: 1 }}\n    d = a\n    e := if c > 0 {{ s }} else {{ d }}\n}}\n"
+        );
+        assert_eq!(
+            errors(&source),
+            [
+                "function `pick` can't return `dyn Show`",
+                "cannot assign to a `dyn Show`",
+                "`if` can't have a value of type `dyn Show`",
+                "field `s` can't hold `dyn Show`"
+            ]
+        );
+    }
+
+    #[test]
+    fn trait_objects_may_be_passed_down_and_kept_in_variables() {
+        let source = format!(
+            "{SHOW}fn show(s: dyn Show) {{ s.show() }}\nfn f(s: dyn Show) {{\n    a := A{{ v: 1 }}\n    d := a as dyn Show\n    both := [a, a] as [dyn Show; 2]\n    show(d)\n    show(both[1])\n    show(s)\n}}\n"
+        );
+        assert_eq!(errors(&source), Vec::<String>::new());
+    }
+}